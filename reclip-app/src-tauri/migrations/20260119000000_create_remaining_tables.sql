@@ -0,0 +1,53 @@
+-- Tables that used to be created by hand in init_db.
+-- Installs that already have them get their missing columns from schema::upgrade_legacy_tables before this runs.
+CREATE TABLE IF NOT EXISTS regex_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pattern TEXT NOT NULL,
+    action_type TEXT NOT NULL,
+    action_payload TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS notes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title TEXT DEFAULT '',
+    content TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    is_pinned BOOLEAN NOT NULL DEFAULT 0,
+    color TEXT DEFAULT NULL,
+    is_archived BOOLEAN NOT NULL DEFAULT 0,
+    tags TEXT DEFAULT '',
+    position INTEGER DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS reminders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    content TEXT NOT NULL,
+    due_date DATETIME,
+    completed BOOLEAN NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    position INTEGER DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS alarms (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time TEXT NOT NULL,
+    label TEXT NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT 1,
+    days TEXT DEFAULT '',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    position INTEGER DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS workflows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    trigger_type TEXT NOT NULL DEFAULT 'content_match',
+    trigger_pattern TEXT NOT NULL DEFAULT '',
+    action_type TEXT NOT NULL DEFAULT 'tag',
+    action_value TEXT NOT NULL DEFAULT '',
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,76 @@
+-- Full-text search index (FTS5) for clips, snippets and notes.
+-- External-content tables: the text lives in the source tables, triggers keep the index in sync.
+
+CREATE VIRTUAL TABLE IF NOT EXISTS clips_fts USING fts5(
+    content,
+    tags,
+    content = 'clips',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3 4'
+);
+
+CREATE TRIGGER IF NOT EXISTS clips_fts_ai AFTER INSERT ON clips BEGIN
+    INSERT INTO clips_fts (rowid, content, tags) VALUES (new.id, new.content, new.tags);
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_fts_ad AFTER DELETE ON clips BEGIN
+    INSERT INTO clips_fts (clips_fts, rowid, content, tags) VALUES ('delete', old.id, old.content, old.tags);
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_fts_au AFTER UPDATE OF content, tags ON clips BEGIN
+    INSERT INTO clips_fts (clips_fts, rowid, content, tags) VALUES ('delete', old.id, old.content, old.tags);
+    INSERT INTO clips_fts (rowid, content, tags) VALUES (new.id, new.content, new.tags);
+END;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS snippets_fts USING fts5(
+    title,
+    content,
+    tags,
+    description,
+    content = 'snippets',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3 4'
+);
+
+CREATE TRIGGER IF NOT EXISTS snippets_fts_ai AFTER INSERT ON snippets BEGIN
+    INSERT INTO snippets_fts (rowid, title, content, tags, description) VALUES (new.id, new.title, new.content, new.tags, new.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS snippets_fts_ad AFTER DELETE ON snippets BEGIN
+    INSERT INTO snippets_fts (snippets_fts, rowid, title, content, tags, description) VALUES ('delete', old.id, old.title, old.content, old.tags, old.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS snippets_fts_au AFTER UPDATE OF title, content, tags, description ON snippets BEGIN
+    INSERT INTO snippets_fts (snippets_fts, rowid, title, content, tags, description) VALUES ('delete', old.id, old.title, old.content, old.tags, old.description);
+    INSERT INTO snippets_fts (rowid, title, content, tags, description) VALUES (new.id, new.title, new.content, new.tags, new.description);
+END;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts USING fts5(
+    title,
+    content,
+    tags,
+    content = 'notes',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3 4'
+);
+
+CREATE TRIGGER IF NOT EXISTS notes_fts_ai AFTER INSERT ON notes BEGIN
+    INSERT INTO notes_fts (rowid, title, content, tags) VALUES (new.id, new.title, new.content, new.tags);
+END;
+
+CREATE TRIGGER IF NOT EXISTS notes_fts_ad AFTER DELETE ON notes BEGIN
+    INSERT INTO notes_fts (notes_fts, rowid, title, content, tags) VALUES ('delete', old.id, old.title, old.content, old.tags);
+END;
+
+CREATE TRIGGER IF NOT EXISTS notes_fts_au AFTER UPDATE OF title, content, tags ON notes BEGIN
+    INSERT INTO notes_fts (notes_fts, rowid, title, content, tags) VALUES ('delete', old.id, old.title, old.content, old.tags);
+    INSERT INTO notes_fts (rowid, title, content, tags) VALUES (new.id, new.title, new.content, new.tags);
+END;
+
+-- Index rows that existed before this migration
+INSERT INTO clips_fts (clips_fts) VALUES ('rebuild');
+INSERT INTO snippets_fts (snippets_fts) VALUES ('rebuild');
+INSERT INTO notes_fts (notes_fts) VALUES ('rebuild');
@@ -0,0 +1,2 @@
+-- Which selection a clip came from: 'clipboard' or 'primary' (X11/Wayland middle-click selection)
+ALTER TABLE clips ADD COLUMN origin TEXT NOT NULL DEFAULT 'clipboard';
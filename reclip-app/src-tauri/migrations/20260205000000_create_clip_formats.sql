@@ -0,0 +1,13 @@
+-- Every representation the clipboard offered for a clip (text/html, text/rtf, image/png, ...).
+-- Small payloads live in `data`, large ones are written to disk and referenced by `path`.
+CREATE TABLE IF NOT EXISTS clip_formats (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    clip_id INTEGER NOT NULL REFERENCES clips(id) ON DELETE CASCADE,
+    mime TEXT NOT NULL,
+    data BLOB,
+    path TEXT,
+    size INTEGER NOT NULL,
+    UNIQUE(clip_id, mime)
+);
+
+CREATE INDEX IF NOT EXISTS idx_clip_formats_clip_id ON clip_formats(clip_id);
@@ -0,0 +1,43 @@
+-- Content-addressed blob store: image files and large clip formats live under blobs/ keyed by their BLAKE3 hash.
+-- `refcount` counts the clips and clip formats pointing at a blob, triggers keep it up to date.
+CREATE TABLE IF NOT EXISTS blobs (
+    hash TEXT PRIMARY KEY,
+    path TEXT NOT NULL,
+    thumb_path TEXT,
+    mime TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    refcount INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE clips ADD COLUMN blob_hash TEXT;
+ALTER TABLE clip_formats ADD COLUMN blob_hash TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_clips_blob_hash ON clips(blob_hash);
+CREATE INDEX IF NOT EXISTS idx_clip_formats_blob_hash ON clip_formats(blob_hash);
+
+CREATE TRIGGER IF NOT EXISTS clips_blob_ai AFTER INSERT ON clips WHEN new.blob_hash IS NOT NULL BEGIN
+    UPDATE blobs SET refcount = refcount + 1 WHERE hash = new.blob_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_blob_ad AFTER DELETE ON clips WHEN old.blob_hash IS NOT NULL BEGIN
+    UPDATE blobs SET refcount = refcount - 1 WHERE hash = old.blob_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_blob_au AFTER UPDATE OF blob_hash ON clips BEGIN
+    UPDATE blobs SET refcount = refcount - 1 WHERE hash = old.blob_hash;
+    UPDATE blobs SET refcount = refcount + 1 WHERE hash = new.blob_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clip_formats_blob_ai AFTER INSERT ON clip_formats WHEN new.blob_hash IS NOT NULL BEGIN
+    UPDATE blobs SET refcount = refcount + 1 WHERE hash = new.blob_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clip_formats_blob_ad AFTER DELETE ON clip_formats WHEN old.blob_hash IS NOT NULL BEGIN
+    UPDATE blobs SET refcount = refcount - 1 WHERE hash = old.blob_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clip_formats_blob_au AFTER UPDATE OF blob_hash ON clip_formats BEGIN
+    UPDATE blobs SET refcount = refcount - 1 WHERE hash = old.blob_hash;
+    UPDATE blobs SET refcount = refcount + 1 WHERE hash = new.blob_hash;
+END;
@@ -0,0 +1,132 @@
+-- Sync: clips are identified across devices by their content hash, snippets and notes by a random `uid`.
+ALTER TABLE snippets ADD COLUMN uid TEXT;
+ALTER TABLE notes ADD COLUMN uid TEXT;
+
+UPDATE snippets SET uid = lower(hex(randomblob(16))) WHERE uid IS NULL;
+UPDATE notes SET uid = lower(hex(randomblob(16))) WHERE uid IS NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_snippets_uid ON snippets(uid);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_notes_uid ON notes(uid);
+
+CREATE TRIGGER IF NOT EXISTS snippets_uid_ai AFTER INSERT ON snippets WHEN new.uid IS NULL BEGIN
+    UPDATE snippets SET uid = lower(hex(randomblob(16))) WHERE id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS notes_uid_ai AFTER INSERT ON notes WHEN new.uid IS NULL BEGIN
+    UPDATE notes SET uid = lower(hex(randomblob(16))) WHERE id = new.id;
+END;
+
+-- Hybrid logical clock of this device: the last change made or received, as wall clock millis and a counter.
+-- The triggers below advance it, so a change is stamped when it is made and not when it is sent.
+CREATE TABLE IF NOT EXISTS sync_clock (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    millis INTEGER NOT NULL,
+    counter INTEGER NOT NULL
+);
+
+INSERT OR IGNORE INTO sync_clock (id, millis, counter) VALUES (1, CAST(unixepoch('subsec') * 1000 AS INTEGER), 0);
+
+-- Local changes not yet sent with the clock at the time of the change. `deleted` rows become tombstones.
+-- Marking an item again replaces its row and so changes `id`, a sync only clears the rows it read.
+CREATE TABLE IF NOT EXISTS sync_dirty (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entity TEXT NOT NULL,
+    key TEXT NOT NULL,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    millis INTEGER NOT NULL,
+    counter INTEGER NOT NULL,
+    UNIQUE (entity, key)
+);
+
+-- HLC timestamp of the last change sent or applied per item, remote changes only win when newer
+CREATE TABLE IF NOT EXISTS sync_versions (
+    entity TEXT NOT NULL,
+    key TEXT NOT NULL,
+    hlc TEXT NOT NULL,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (entity, key)
+);
+
+-- Last change segment read from each remote device
+CREATE TABLE IF NOT EXISTS sync_cursors (
+    device TEXT PRIMARY KEY,
+    seq INTEGER NOT NULL
+);
+
+-- Holds a row while a transaction writes changes that must not be sent back (remote changes, pruning)
+CREATE TABLE IF NOT EXISTS sync_paused (
+    reason TEXT
+);
+
+-- Sensitive clips and file lists (paths only valid on this machine) are never synced
+CREATE TRIGGER IF NOT EXISTS clips_sync_ai AFTER INSERT ON clips
+WHEN new.sensitive = 0 AND new.type != 'files' AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'clip', new.hash, 0, millis, counter FROM sync_clock;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_sync_au AFTER UPDATE OF content, created_at, pinned, favorite, tags ON clips
+WHEN new.sensitive = 0 AND new.type != 'files' AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'clip', new.hash, 0, millis, counter FROM sync_clock;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_sync_ad AFTER DELETE ON clips
+WHEN old.sensitive = 0 AND old.type != 'files' AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'clip', old.hash, 1, millis, counter FROM sync_clock;
+END;
+
+-- Inserts without a uid are picked up by the update the uid trigger makes
+CREATE TRIGGER IF NOT EXISTS snippets_sync_ai AFTER INSERT ON snippets
+WHEN new.uid IS NOT NULL AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'snippet', new.uid, 0, millis, counter FROM sync_clock;
+END;
+
+CREATE TRIGGER IF NOT EXISTS snippets_sync_au AFTER UPDATE ON snippets
+WHEN new.uid IS NOT NULL AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'snippet', new.uid, 0, millis, counter FROM sync_clock;
+END;
+
+CREATE TRIGGER IF NOT EXISTS snippets_sync_ad AFTER DELETE ON snippets
+WHEN old.uid IS NOT NULL AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'snippet', old.uid, 1, millis, counter FROM sync_clock;
+END;
+
+CREATE TRIGGER IF NOT EXISTS notes_sync_ai AFTER INSERT ON notes
+WHEN new.uid IS NOT NULL AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'note', new.uid, 0, millis, counter FROM sync_clock;
+END;
+
+CREATE TRIGGER IF NOT EXISTS notes_sync_au AFTER UPDATE ON notes
+WHEN new.uid IS NOT NULL AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'note', new.uid, 0, millis, counter FROM sync_clock;
+END;
+
+CREATE TRIGGER IF NOT EXISTS notes_sync_ad AFTER DELETE ON notes
+WHEN old.uid IS NOT NULL AND NOT EXISTS (SELECT 1 FROM sync_paused) BEGIN
+    UPDATE sync_clock SET counter = CASE WHEN CAST(unixepoch('subsec') * 1000 AS INTEGER) > millis THEN 0 ELSE counter + 1 END,
+        millis = MAX(millis, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+    INSERT OR REPLACE INTO sync_dirty (entity, key, deleted, millis, counter) SELECT 'note', old.uid, 1, millis, counter FROM sync_clock;
+END;
+
+-- Everything that exists already goes out with the first sync
+INSERT OR IGNORE INTO sync_dirty (entity, key, millis, counter)
+    SELECT 'clip', hash, sync_clock.millis, sync_clock.counter FROM clips, sync_clock WHERE sensitive = 0 AND type != 'files';
+INSERT OR IGNORE INTO sync_dirty (entity, key, millis, counter)
+    SELECT 'snippet', uid, sync_clock.millis, sync_clock.counter FROM snippets, sync_clock;
+INSERT OR IGNORE INTO sync_dirty (entity, key, millis, counter)
+    SELECT 'note', uid, sync_clock.millis, sync_clock.counter FROM notes, sync_clock;
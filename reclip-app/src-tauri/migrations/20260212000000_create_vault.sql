@@ -0,0 +1,49 @@
+-- Vault mode stores clip content, tags and snippet bodies encrypted (prefixed 'enc:v1:').
+-- Ciphertext is kept out of the search index: encrypted values are indexed as empty text.
+-- The delete commands must pass the values that were indexed, so they use the same expressions.
+
+DROP TRIGGER IF EXISTS clips_fts_ai;
+DROP TRIGGER IF EXISTS clips_fts_ad;
+DROP TRIGGER IF EXISTS clips_fts_au;
+
+CREATE TRIGGER clips_fts_ai AFTER INSERT ON clips BEGIN
+    INSERT INTO clips_fts (rowid, content, tags) VALUES (new.id,
+        CASE WHEN substr(new.content, 1, 7) = 'enc:v1:' THEN '' ELSE new.content END,
+        CASE WHEN substr(new.tags, 1, 7) = 'enc:v1:' THEN '' ELSE new.tags END);
+END;
+
+CREATE TRIGGER clips_fts_ad AFTER DELETE ON clips BEGIN
+    INSERT INTO clips_fts (clips_fts, rowid, content, tags) VALUES ('delete', old.id,
+        CASE WHEN substr(old.content, 1, 7) = 'enc:v1:' THEN '' ELSE old.content END,
+        CASE WHEN substr(old.tags, 1, 7) = 'enc:v1:' THEN '' ELSE old.tags END);
+END;
+
+CREATE TRIGGER clips_fts_au AFTER UPDATE OF content, tags ON clips BEGIN
+    INSERT INTO clips_fts (clips_fts, rowid, content, tags) VALUES ('delete', old.id,
+        CASE WHEN substr(old.content, 1, 7) = 'enc:v1:' THEN '' ELSE old.content END,
+        CASE WHEN substr(old.tags, 1, 7) = 'enc:v1:' THEN '' ELSE old.tags END);
+    INSERT INTO clips_fts (rowid, content, tags) VALUES (new.id,
+        CASE WHEN substr(new.content, 1, 7) = 'enc:v1:' THEN '' ELSE new.content END,
+        CASE WHEN substr(new.tags, 1, 7) = 'enc:v1:' THEN '' ELSE new.tags END);
+END;
+
+DROP TRIGGER IF EXISTS snippets_fts_ai;
+DROP TRIGGER IF EXISTS snippets_fts_ad;
+DROP TRIGGER IF EXISTS snippets_fts_au;
+
+CREATE TRIGGER snippets_fts_ai AFTER INSERT ON snippets BEGIN
+    INSERT INTO snippets_fts (rowid, title, content, tags, description) VALUES (new.id, new.title,
+        CASE WHEN substr(new.content, 1, 7) = 'enc:v1:' THEN '' ELSE new.content END, new.tags, new.description);
+END;
+
+CREATE TRIGGER snippets_fts_ad AFTER DELETE ON snippets BEGIN
+    INSERT INTO snippets_fts (snippets_fts, rowid, title, content, tags, description) VALUES ('delete', old.id, old.title,
+        CASE WHEN substr(old.content, 1, 7) = 'enc:v1:' THEN '' ELSE old.content END, old.tags, old.description);
+END;
+
+CREATE TRIGGER snippets_fts_au AFTER UPDATE OF title, content, tags, description ON snippets BEGIN
+    INSERT INTO snippets_fts (snippets_fts, rowid, title, content, tags, description) VALUES ('delete', old.id, old.title,
+        CASE WHEN substr(old.content, 1, 7) = 'enc:v1:' THEN '' ELSE old.content END, old.tags, old.description);
+    INSERT INTO snippets_fts (rowid, title, content, tags, description) VALUES (new.id, new.title,
+        CASE WHEN substr(new.content, 1, 7) = 'enc:v1:' THEN '' ELSE new.content END, new.tags, new.description);
+END;
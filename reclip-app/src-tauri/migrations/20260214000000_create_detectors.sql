@@ -0,0 +1,13 @@
+-- User-defined sensitive content detectors, checked next to the built-in catalog
+CREATE TABLE IF NOT EXISTS detectors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    confidence REAL NOT NULL DEFAULT 0.9,
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Which detector flagged a sensitive clip, and how sure it was
+ALTER TABLE clips ADD COLUMN sensitive_detector TEXT;
+ALTER TABLE clips ADD COLUMN sensitive_confidence REAL;
@@ -0,0 +1,6 @@
+-- REGEX_MASK rules replace matches with a placeholder, REGEX_DROP rules discard the clip.
+-- keep_original stores the unmasked text encrypted with the redaction key from the secret store.
+ALTER TABLE privacy_rules ADD COLUMN placeholder TEXT;
+ALTER TABLE privacy_rules ADD COLUMN keep_original BOOLEAN NOT NULL DEFAULT 0;
+
+ALTER TABLE clips ADD COLUMN redacted_original TEXT;
@@ -0,0 +1,10 @@
+-- APP_POLICY rules decide what is captured from an app instead of ignoring it outright.
+-- match_kind: 'substring' (name or title, how APP_IGNORE always matched), 'path' (exact process path),
+-- 'title_regex' or 'wildcard' (* and ? over name, path or title).
+-- capture: 'all', 'no_images', 'text_only' or 'none'. tags is a JSON array added to every clip.
+ALTER TABLE privacy_rules ADD COLUMN match_kind TEXT NOT NULL DEFAULT 'substring';
+ALTER TABLE privacy_rules ADD COLUMN capture TEXT NOT NULL DEFAULT 'all';
+ALTER TABLE privacy_rules ADD COLUMN mark_sensitive BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE privacy_rules ADD COLUMN delete_after INTEGER;
+ALTER TABLE privacy_rules ADD COLUMN tags TEXT;
+ALTER TABLE privacy_rules ADD COLUMN strip_formatting BOOLEAN NOT NULL DEFAULT 0;
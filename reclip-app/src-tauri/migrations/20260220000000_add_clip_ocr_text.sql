@@ -0,0 +1,51 @@
+-- Text recognized in image clips (screen captures). Searchable, but never written back to the clipboard.
+-- clips_fts gets a third column; the triggers keep the vault rule of indexing encrypted values as empty text.
+ALTER TABLE clips ADD COLUMN ocr_text TEXT;
+
+DROP TRIGGER IF EXISTS clips_fts_ai;
+DROP TRIGGER IF EXISTS clips_fts_ad;
+DROP TRIGGER IF EXISTS clips_fts_au;
+DROP TABLE IF EXISTS clips_fts;
+
+CREATE VIRTUAL TABLE clips_fts USING fts5(
+    content,
+    tags,
+    ocr_text,
+    content = 'clips',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3 4'
+);
+
+CREATE TRIGGER clips_fts_ai AFTER INSERT ON clips BEGIN
+    INSERT INTO clips_fts (rowid, content, tags, ocr_text) VALUES (new.id,
+        CASE WHEN substr(new.content, 1, 7) = 'enc:v1:' THEN '' ELSE new.content END,
+        CASE WHEN substr(new.tags, 1, 7) = 'enc:v1:' THEN '' ELSE new.tags END,
+        CASE WHEN substr(new.ocr_text, 1, 7) = 'enc:v1:' THEN '' ELSE new.ocr_text END);
+END;
+
+CREATE TRIGGER clips_fts_ad AFTER DELETE ON clips BEGIN
+    INSERT INTO clips_fts (clips_fts, rowid, content, tags, ocr_text) VALUES ('delete', old.id,
+        CASE WHEN substr(old.content, 1, 7) = 'enc:v1:' THEN '' ELSE old.content END,
+        CASE WHEN substr(old.tags, 1, 7) = 'enc:v1:' THEN '' ELSE old.tags END,
+        CASE WHEN substr(old.ocr_text, 1, 7) = 'enc:v1:' THEN '' ELSE old.ocr_text END);
+END;
+
+CREATE TRIGGER clips_fts_au AFTER UPDATE OF content, tags, ocr_text ON clips BEGIN
+    INSERT INTO clips_fts (clips_fts, rowid, content, tags, ocr_text) VALUES ('delete', old.id,
+        CASE WHEN substr(old.content, 1, 7) = 'enc:v1:' THEN '' ELSE old.content END,
+        CASE WHEN substr(old.tags, 1, 7) = 'enc:v1:' THEN '' ELSE old.tags END,
+        CASE WHEN substr(old.ocr_text, 1, 7) = 'enc:v1:' THEN '' ELSE old.ocr_text END);
+    INSERT INTO clips_fts (rowid, content, tags, ocr_text) VALUES (new.id,
+        CASE WHEN substr(new.content, 1, 7) = 'enc:v1:' THEN '' ELSE new.content END,
+        CASE WHEN substr(new.tags, 1, 7) = 'enc:v1:' THEN '' ELSE new.tags END,
+        CASE WHEN substr(new.ocr_text, 1, 7) = 'enc:v1:' THEN '' ELSE new.ocr_text END);
+END;
+
+-- Not 'rebuild': it would read the ciphertext straight from clips
+INSERT INTO clips_fts (rowid, content, tags, ocr_text)
+SELECT id,
+    CASE WHEN substr(content, 1, 7) = 'enc:v1:' THEN '' ELSE content END,
+    CASE WHEN substr(tags, 1, 7) = 'enc:v1:' THEN '' ELSE tags END,
+    NULL
+FROM clips;
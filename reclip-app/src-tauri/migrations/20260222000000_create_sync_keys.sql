@@ -0,0 +1,7 @@
+-- Vault mode stores clip hashes keyed with the vault key, so a hash can't confirm a guess at the content.
+-- Other devices know a clip by its plain hash, which is kept here encrypted for sending changes.
+-- Keyed hash -> encrypted plain hash, also for deleted clips still known to sync.
+CREATE TABLE IF NOT EXISTS sync_keys (
+    hash TEXT PRIMARY KEY NOT NULL,
+    sync_key TEXT NOT NULL
+);
@@ -1,11 +1,30 @@
 use axum::{
     routing::get,
     Json, Router,
-    extract::State as AxumState,
+    extract::{Path, Query, Request, State as AxumState},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response, sse::{Event, KeepAlive, Sse}},
 };
-use serde::Serialize;
-use std::net::SocketAddr;
-use crate::db::{DbState, Clip, Snippet};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use log::{error, info, warn};
+use sqlx::{Pool, Sqlite};
+use tauri::AppHandle;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::oneshot;
+use crate::capture::{self, PendingClip};
+use crate::db::{self, DbState, Clip, Snippet};
+use crate::events::{self, publish, ClipEvent};
+use crate::search;
+
+/// Settings key of the bearer token API clients must send
+const API_TOKEN_KEY: &str = "api_token";
 
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
@@ -14,25 +33,255 @@ pub struct ApiResponse<T> {
     pub error: Option<String>,
 }
 
-pub async fn start_api_server(state: DbState) {
-    let app = Router::new()
+#[derive(Clone)]
+struct ApiState {
+    db: DbState,
+    app: AppHandle,
+}
+
+type ApiResult<T> = Result<Json<ApiResponse<T>>, (StatusCode, Json<ApiResponse<()>>)>;
+
+fn ok<T>(data: T) -> ApiResult<T> {
+    Ok(Json(ApiResponse { success: true, data: Some(data), error: None }))
+}
+
+fn fail(status: StatusCode, error: impl ToString) -> (StatusCode, Json<ApiResponse<()>>) {
+    (status, Json(ApiResponse { success: false, data: None, error: Some(error.to_string()) }))
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, Json<ApiResponse<()>>) {
+    fail(StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+fn new_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The API token of this install, generated on first use
+pub async fn get_or_create_token(pool: &Pool<Sqlite>) -> Result<String, String> {
+    if let Some(token) = db::get_setting(pool, API_TOKEN_KEY).await.filter(|t| !t.is_empty()) {
+        return Ok(token);
+    }
+    regenerate_token(pool).await
+}
+
+/// Replace the API token, clients holding the old one are locked out
+pub async fn regenerate_token(pool: &Pool<Sqlite>) -> Result<String, String> {
+    let token = new_token()?;
+    db::set_setting(pool, API_TOKEN_KEY, &token).await.map_err(|e| e.to_string())?;
+    Ok(token)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn require_token(AxumState(state): AxumState<ApiState>, req: Request, next: Next) -> Response {
+    // Browsers send Origin with every cross-origin request, web pages don't get to read the clipboard
+    if req.headers().contains_key(header::ORIGIN) {
+        return fail(StatusCode::FORBIDDEN, "Cross-origin requests are not allowed").into_response();
+    }
+
+    let expected = match get_or_create_token(&state.db.pool).await {
+        Ok(token) => token,
+        Err(e) => return fail(StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    let provided = req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if constant_time_eq(token.trim().as_bytes(), expected.as_bytes()) => next.run(req).await,
+        _ => fail(StatusCode::UNAUTHORIZED, "Missing or invalid API token").into_response(),
+    }
+}
+
+/// Settings keys of the server configuration
+const API_ENABLED_KEY: &str = "api_enabled";
+const API_BIND_KEY: &str = "api_bind_address";
+const API_PORT_KEY: &str = "api_port";
+
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 14201;
+
+/// Server configuration from settings: (enabled, bind address, port)
+pub async fn get_config(pool: &Pool<Sqlite>) -> (bool, String, u16) {
+    let enabled = db::get_setting(pool, API_ENABLED_KEY).await
+        .map(|v| v != "false")
+        .unwrap_or(true);
+    let bind = db::get_setting(pool, API_BIND_KEY).await
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| DEFAULT_BIND_ADDRESS.to_string());
+    let port = db::get_setting(pool, API_PORT_KEY).await
+        .and_then(|v| v.parse::<u16>().ok())
+        .unwrap_or(DEFAULT_PORT);
+    (enabled, bind, port)
+}
+
+pub async fn set_config(pool: &Pool<Sqlite>, enabled: bool, bind: &str, port: u16) -> Result<(), String> {
+    socket_addr(bind, port)?;
+    db::set_setting(pool, API_ENABLED_KEY, if enabled { "true" } else { "false" }).await.map_err(|e| e.to_string())?;
+    db::set_setting(pool, API_BIND_KEY, bind).await.map_err(|e| e.to_string())?;
+    db::set_setting(pool, API_PORT_KEY, &port.to_string()).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn socket_addr(bind: &str, port: u16) -> Result<SocketAddr, String> {
+    let ip: IpAddr = match bind.trim() {
+        "localhost" => IpAddr::from([127, 0, 0, 1]),
+        other => other.parse().map_err(|_| format!("Invalid bind address '{}'", bind))?,
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+struct RunningServer {
+    addr: SocketAddr,
+    started: Instant,
+    shutdown: oneshot::Sender<()>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+// The server currently running, if any. Async mutex: start/stop wait for the previous server to go away.
+static SERVER: LazyLock<tokio::sync::Mutex<Option<RunningServer>>> = LazyLock::new(|| tokio::sync::Mutex::new(None));
+static REQUEST_COUNT: AtomicU64 = AtomicU64::new(0);
+static LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+fn set_last_error(error: String) {
+    error!("API server: {}", error);
+    *LAST_ERROR.lock().unwrap() = Some(error);
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiStatus {
+    pub enabled: bool,
+    pub running: bool,
+    /// Address the server is listening on
+    pub address: Option<String>,
+    pub uptime_secs: Option<u64>,
+    /// Requests handled since the app started
+    pub request_count: u64,
+    pub last_error: Option<String>,
+}
+
+pub async fn status(pool: &Pool<Sqlite>) -> ApiStatus {
+    let (enabled, _, _) = get_config(pool).await;
+    let server = SERVER.lock().await;
+    // A server whose task ended (serve error) is not running even though it was never stopped
+    let running = server.as_ref().filter(|s| !s.task.inner().is_finished());
+    ApiStatus {
+        enabled,
+        running: running.is_some(),
+        address: running.map(|s| s.addr.to_string()),
+        uptime_secs: running.map(|s| s.started.elapsed().as_secs()),
+        request_count: REQUEST_COUNT.load(Ordering::Relaxed),
+        last_error: LAST_ERROR.lock().unwrap().clone(),
+    }
+}
+
+/// Start the server with the configuration in settings. Does nothing when it is disabled or already running.
+pub async fn start(app: AppHandle, state: DbState) -> Result<(), String> {
+    let mut server = SERVER.lock().await;
+    if server.as_ref().is_some_and(|s| !s.task.inner().is_finished()) {
+        return Ok(());
+    }
+
+    let (enabled, bind, port) = get_config(&state.pool).await;
+    if !enabled {
+        info!("API server disabled in settings");
+        return Ok(());
+    }
+    let addr = socket_addr(&bind, port)?;
+    if !addr.ip().is_loopback() {
+        warn!("API server bound to non-loopback address {}, it is reachable from other machines", addr);
+    }
+
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            // Port taken, e.g. by the API of a second session
+            let error = format!("Failed to bind {}: {}", addr, e);
+            set_last_error(error.clone());
+            return Err(error);
+        }
+    };
+    *LAST_ERROR.lock().unwrap() = None;
+
+    let router = router(ApiState { db: state, app });
+    let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+    let task = tauri::async_runtime::spawn(async move {
+        let result = axum::serve(listener, router)
+            .with_graceful_shutdown(async { let _ = shutdown_rx.await; })
+            .await;
+        if let Err(e) = result {
+            set_last_error(format!("Server stopped: {}", e));
+        }
+    });
+
+    info!("API Server listening on {}", addr);
+    *server = Some(RunningServer { addr, started: Instant::now(), shutdown, task });
+    Ok(())
+}
+
+pub async fn stop() {
+    let Some(server) = SERVER.lock().await.take() else { return };
+    let _ = server.shutdown.send(());
+    let mut task = server.task;
+    // Event streams never finish on their own, cut them off after a grace period
+    if tokio::time::timeout(Duration::from_secs(2), &mut task).await.is_err() {
+        task.abort();
+    }
+    info!("API Server on {} stopped", server.addr);
+}
+
+pub async fn restart(app: AppHandle, state: DbState) -> Result<(), String> {
+    stop().await;
+    start(app, state).await
+}
+
+async fn count_requests(req: Request, next: Next) -> Response {
+    REQUEST_COUNT.fetch_add(1, Ordering::Relaxed);
+    next.run(req).await
+}
+
+fn router(state: ApiState) -> Router {
+    let v1 = Router::new()
+        .route("/events", get(event_stream))
+        .route("/clips", get(list_clips).post(create_clip))
+        .route("/clips/:id", get(get_clip).patch(update_clip).delete(delete_clip))
+        .route("/snippets", get(get_all_snippets).post(create_snippet))
+        .route("/snippets/:id", get(get_snippet).put(update_snippet).delete(delete_snippet));
+
+    Router::new()
         .route("/latest", get(get_latest_clip))
         .route("/snippets", get(get_all_snippets))
-        .with_state(state);
+        .nest("/v1", v1)
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        // Routes below don't need the token
+        .route("/health", get(health))
+        .layer(middleware::from_fn(count_requests))
+        .with_state(state)
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    version: &'static str,
+}
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 14201));
-    println!("API Server listening on {}", addr);
-    
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+async fn health() -> Json<Health> {
+    Json(Health { status: "ok", version: env!("CARGO_PKG_VERSION") })
 }
 
 async fn get_latest_clip(
-    AxumState(state): AxumState<DbState>,
+    AxumState(state): AxumState<ApiState>,
 ) -> Json<ApiResponse<Clip>> {
     let result = sqlx::query_as::<_, Clip>("SELECT * FROM clips ORDER BY created_at DESC LIMIT 1")
-        .fetch_optional(&state.pool)
-        .await;
+        .fetch_optional(&state.db.pool)
+        .await
+        .and_then(|clip| clip.map(crate::db::unseal_clip).transpose());
 
     match result {
         Ok(Some(clip)) => Json(ApiResponse { success: true, data: Some(clip), error: None }),
@@ -41,15 +290,217 @@ async fn get_latest_clip(
     }
 }
 
+/// Server-sent events: `clip-created`, `clip-updated` and `clip-deleted` with the clip id as data,
+/// the same events the webview gets
+async fn event_stream() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = stream::unfold(events::subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let sse = Event::default().event(event.name()).data(event.clip_id().to_string());
+                    return Some((Ok(sse), rx));
+                }
+                // Too slow, events were dropped; carry on with the next one
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[derive(Deserialize)]
+struct ClipListParams {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Search query, same syntax as the search bar
+    q: Option<String>,
+    #[serde(rename = "type")]
+    type_: Option<String>,
+    favorites: Option<bool>,
+    include_primary: Option<bool>,
+}
+
+async fn list_clips(
+    AxumState(state): AxumState<ApiState>,
+    Query(params): Query<ClipListParams>,
+) -> ApiResult<Vec<Clip>> {
+    let query = params.q.as_deref()
+        .filter(|q| !q.trim().is_empty())
+        .map(search::parse_clip_query)
+        .transpose()
+        .map_err(|e| fail(StatusCode::BAD_REQUEST, e.message))?;
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let clips = db::get_clips(&state.db.pool, limit, offset, query.as_ref(), params.type_, params.favorites.unwrap_or(false), params.include_primary.unwrap_or(true))
+        .await
+        .map_err(db_error)?;
+    ok(clips)
+}
+
+async fn find_clip(pool: &Pool<Sqlite>, id: i64) -> Result<Clip, (StatusCode, Json<ApiResponse<()>>)> {
+    db::get_clip(pool, id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| fail(StatusCode::NOT_FOUND, format!("Clip {} not found", id)))
+}
+
+async fn get_clip(
+    AxumState(state): AxumState<ApiState>,
+    Path(id): Path<i64>,
+) -> ApiResult<Clip> {
+    ok(find_clip(&state.db.pool, id).await?)
+}
+
+#[derive(Deserialize)]
+struct NewClip {
+    content: String,
+    #[serde(rename = "type")]
+    type_: Option<String>,
+    tags: Option<Vec<String>>,
+    sender_app: Option<String>,
+}
+
+async fn create_clip(
+    AxumState(state): AxumState<ApiState>,
+    Json(input): Json<NewClip>,
+) -> ApiResult<Clip> {
+    if input.content.is_empty() {
+        return Err(fail(StatusCode::BAD_REQUEST, "Clip content is empty"));
+    }
+    let type_ = input.type_.unwrap_or_else(|| "text".to_string());
+    if !matches!(type_.as_str(), "text" | "html") {
+        return Err(fail(StatusCode::BAD_REQUEST, format!("Clips of type '{}' can't be created through the API", type_)));
+    }
+    let pool = &state.db.pool;
+    let mut clip = PendingClip::submitted(input.content, type_ == "html", input.tags.unwrap_or_default(), input.sender_app);
+
+    // The same rules as copied clips, for the sender app
+    let rules = capture::Rules::load(pool).await.map_err(|e| fail(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    capture::privacy(&mut clip, &rules).map_err(|skip| fail(StatusCode::UNPROCESSABLE_ENTITY, format!("Clip not stored: {}", skip)))?;
+    capture::detect(pool, &mut clip).await;
+    let id = capture::insert(&state.app, pool, clip).await.map_err(|e| fail(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    ok(find_clip(pool, id).await?)
+}
+
+#[derive(Deserialize)]
+struct ClipUpdate {
+    pinned: Option<bool>,
+    favorite: Option<bool>,
+    tags: Option<Vec<String>>,
+}
+
+async fn update_clip(
+    AxumState(state): AxumState<ApiState>,
+    Path(id): Path<i64>,
+    Json(update): Json<ClipUpdate>,
+) -> ApiResult<Clip> {
+    let pool = &state.db.pool;
+    find_clip(pool, id).await?;
+
+    if let Some(pinned) = update.pinned {
+        db::set_clip_pinned(pool, id, pinned).await.map_err(db_error)?;
+    }
+    if let Some(favorite) = update.favorite {
+        db::set_clip_favorite(pool, id, favorite).await.map_err(db_error)?;
+    }
+    if let Some(tags) = update.tags {
+        let tags = serde_json::to_string(&tags).unwrap_or_default();
+        db::update_clip_tags(pool, id, tags).await.map_err(db_error)?;
+    }
+
+    publish(&state.app, ClipEvent::Updated(id));
+    let _ = crate::tray::update_tray_history(&state.app).await;
+    ok(find_clip(pool, id).await?)
+}
+
+async fn delete_clip(
+    AxumState(state): AxumState<ApiState>,
+    Path(id): Path<i64>,
+) -> ApiResult<i64> {
+    find_clip(&state.db.pool, id).await?;
+    db::delete_clip(&state.db.pool, id).await.map_err(db_error)?;
+    publish(&state.app, ClipEvent::Deleted(id));
+    let _ = crate::tray::update_tray_history(&state.app).await;
+    ok(id)
+}
+
 async fn get_all_snippets(
-    AxumState(state): AxumState<DbState>,
-) -> Json<ApiResponse<Vec<Snippet>>> {
-    let result = sqlx::query_as::<_, Snippet>("SELECT * FROM snippets ORDER BY updated_at DESC")
-        .fetch_all(&state.pool)
-        .await;
+    AxumState(state): AxumState<ApiState>,
+) -> ApiResult<Vec<Snippet>> {
+    ok(db::get_snippets(&state.db.pool).await.map_err(db_error)?)
+}
 
-    match result {
-        Ok(snippets) => Json(ApiResponse { success: true, data: Some(snippets), error: None }),
-        Err(e) => Json(ApiResponse { success: false, data: None, error: Some(e.to_string()) }),
+async fn find_snippet(pool: &Pool<Sqlite>, id: i64) -> Result<Snippet, (StatusCode, Json<ApiResponse<()>>)> {
+    db::get_snippet(pool, id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| fail(StatusCode::NOT_FOUND, format!("Snippet {} not found", id)))
+}
+
+async fn get_snippet(
+    AxumState(state): AxumState<ApiState>,
+    Path(id): Path<i64>,
+) -> ApiResult<Snippet> {
+    ok(find_snippet(&state.db.pool, id).await?)
+}
+
+#[derive(Deserialize)]
+struct SnippetInput {
+    title: String,
+    content: String,
+    #[serde(default = "default_language")]
+    language: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    folder: String,
+}
+
+fn default_language() -> String {
+    "plaintext".to_string()
+}
+
+impl SnippetInput {
+    /// Snippet tags are stored as a JSON array
+    fn tags_json(&self) -> String {
+        serde_json::to_string(&self.tags).unwrap_or_else(|_| "[]".to_string())
     }
 }
+
+async fn create_snippet(
+    AxumState(state): AxumState<ApiState>,
+    Json(input): Json<SnippetInput>,
+) -> ApiResult<Snippet> {
+    let tags = input.tags_json();
+    let id = db::add_snippet(&state.db.pool, input.title, input.content, input.language, tags, input.description, input.folder)
+        .await
+        .map_err(db_error)?;
+    ok(find_snippet(&state.db.pool, id).await?)
+}
+
+async fn update_snippet(
+    AxumState(state): AxumState<ApiState>,
+    Path(id): Path<i64>,
+    Json(input): Json<SnippetInput>,
+) -> ApiResult<Snippet> {
+    let pool = &state.db.pool;
+    find_snippet(pool, id).await?;
+    let tags = input.tags_json();
+    db::update_snippet(pool, id, input.title, input.content, input.language, tags, input.description, input.folder)
+        .await
+        .map_err(db_error)?;
+    ok(find_snippet(pool, id).await?)
+}
+
+async fn delete_snippet(
+    AxumState(state): AxumState<ApiState>,
+    Path(id): Path<i64>,
+) -> ApiResult<i64> {
+    find_snippet(&state.db.pool, id).await?;
+    db::delete_snippet(&state.db.pool, id).await.map_err(db_error)?;
+    ok(id)
+}
@@ -0,0 +1,374 @@
+use std::sync::{Arc, RwLock};
+use log::warn;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use crate::db::{get_privacy_rules, PrivacyRule};
+
+pub const APP_IGNORE: &str = "APP_IGNORE";
+pub const APP_POLICY: &str = "APP_POLICY";
+
+/// The parts of the focused window a policy can match on
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SourceWindow {
+    pub name: String,
+    pub path: String,
+    pub title: String,
+}
+
+impl From<&x_win::WindowInfo> for SourceWindow {
+    fn from(window: &x_win::WindowInfo) -> Self {
+        SourceWindow {
+            name: window.info.name.clone(),
+            path: window.info.path.clone(),
+            title: window.title.clone(),
+        }
+    }
+}
+
+/// Which clips an app may produce, from least to most restrictive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capture {
+    #[default]
+    All,
+    NoImages,
+    TextOnly,
+    None,
+}
+
+impl Capture {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Capture::All => "all",
+            Capture::NoImages => "no_images",
+            Capture::TextOnly => "text_only",
+            Capture::None => "none",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "no_images" => Capture::NoImages,
+            "text_only" => Capture::TextOnly,
+            "none" => Capture::None,
+            _ => Capture::All,
+        }
+    }
+
+    /// Whether a clip of `clip_type` may be stored
+    pub fn allows(self, clip_type: &str) -> bool {
+        match self {
+            Capture::All => true,
+            Capture::NoImages => clip_type != "image",
+            // A copied path is still plain text
+            Capture::TextOnly => clip_type == "text" || clip_type == "file",
+            Capture::None => false,
+        }
+    }
+}
+
+/// An app policy as the settings page submits it
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppPolicyRule {
+    /// "substring", "path", "title_regex" or "wildcard"
+    pub match_kind: String,
+    pub value: String,
+    pub capture: Capture,
+    #[serde(default)]
+    pub mark_sensitive: bool,
+    /// Seconds until sensitive clips from the app are deleted, the global timer when unset
+    pub delete_after: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub strip_formatting: bool,
+}
+
+impl AppPolicyRule {
+    /// Reject patterns that can never match, before they are saved
+    pub fn check(&self) -> Result<(), String> {
+        if self.value.trim().is_empty() {
+            return Err("Enter what to match".to_string());
+        }
+        match self.match_kind.as_str() {
+            "substring" | "path" | "wildcard" => Ok(()),
+            "title_regex" => Regex::new(&self.value).map(|_| ()).map_err(|e| format!("Invalid pattern: {}", e)),
+            other => Err(format!("Unknown match kind '{}'", other)),
+        }
+    }
+
+    /// The rule as it would be stored, to test it before saving
+    pub fn to_rule(&self) -> PrivacyRule {
+        PrivacyRule {
+            id: 0,
+            rule_type: APP_POLICY.to_string(),
+            value: self.value.clone(),
+            is_active: true,
+            placeholder: None,
+            keep_original: false,
+            match_kind: self.match_kind.clone(),
+            capture: self.capture.as_str().to_string(),
+            mark_sensitive: self.mark_sensitive,
+            delete_after: self.delete_after,
+            tags: (!self.tags.is_empty()).then(|| serde_json::to_string(&self.tags).unwrap_or_default()),
+            strip_formatting: self.strip_formatting,
+        }
+    }
+}
+
+/// What the listener does with clips from the focused app, merged from every matching rule
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AppPolicy {
+    pub capture: Capture,
+    pub mark_sensitive: bool,
+    pub delete_after: Option<u64>,
+    pub tags: Vec<String>,
+    pub strip_formatting: bool,
+    /// Values of the rules that matched
+    pub matched: Vec<String>,
+}
+
+impl AppPolicy {
+    /// Add the policy's tags to a clip's JSON tag list
+    pub fn with_tags(&self, tags: Option<String>) -> Option<String> {
+        if self.tags.is_empty() {
+            return tags;
+        }
+        let mut all: Vec<String> = tags.as_deref().and_then(|t| serde_json::from_str(t).ok()).unwrap_or_default();
+        for tag in &self.tags {
+            if !all.contains(tag) {
+                all.push(tag.clone());
+            }
+        }
+        serde_json::to_string(&all).ok()
+    }
+
+    fn merge(&mut self, rule: &PrivacyRule) {
+        let capture = match rule.rule_type.as_str() {
+            APP_IGNORE => Capture::None,
+            _ => Capture::parse(&rule.capture),
+        };
+        self.capture = self.capture.max(capture);
+        self.mark_sensitive |= rule.mark_sensitive;
+        if let Some(secs) = rule.delete_after.and_then(|s| u64::try_from(s).ok()) {
+            self.delete_after = Some(self.delete_after.map_or(secs, |d| d.min(secs)));
+        }
+        let tags: Vec<String> = rule.tags.as_deref().and_then(|t| serde_json::from_str(t).ok()).unwrap_or_default();
+        for tag in tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        self.strip_formatting |= rule.strip_formatting;
+        self.matched.push(rule.value.clone());
+    }
+}
+
+/// How an APP_IGNORE or APP_POLICY rule recognizes a window, patterns compiled
+enum Matcher {
+    Path(String),
+    TitleRegex(Regex),
+    Wildcard(Regex),
+    /// Earlier APP_IGNORE rules: part of the app name or window title, ignoring case
+    Substring(String),
+}
+
+impl Matcher {
+    fn new(rule: &PrivacyRule) -> Option<Self> {
+        let value = rule.value.as_str();
+        if value.is_empty() {
+            return None;
+        }
+        Some(match rule.match_kind.as_str() {
+            "path" => Matcher::Path(value.to_string()),
+            "title_regex" => match Regex::new(value) {
+                Ok(re) => Matcher::TitleRegex(re),
+                Err(e) => {
+                    warn!("Skipping app rule '{}': {}", value, e);
+                    return None;
+                }
+            },
+            "wildcard" => Matcher::Wildcard(wildcard(value)?),
+            _ => Matcher::Substring(value.to_lowercase()),
+        })
+    }
+
+    fn matches(&self, window: &SourceWindow) -> bool {
+        match self {
+            Matcher::Path(path) => !window.path.is_empty() && paths_equal(&window.path, path),
+            Matcher::TitleRegex(re) => re.is_match(&window.title),
+            Matcher::Wildcard(re) => re.is_match(&window.name) || re.is_match(&window.path) || re.is_match(&window.title),
+            Matcher::Substring(value) => window.name.to_lowercase().contains(value) || window.title.to_lowercase().contains(value),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn paths_equal(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn paths_equal(a: &str, b: &str) -> bool {
+    a == b
+}
+
+/// `*` for any run of characters and `?` for one, matching the whole value without case
+fn wildcard(pattern: &str) -> Option<Regex> {
+    let body: String = pattern
+        .chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect();
+    RegexBuilder::new(&format!("^{}$", body)).case_insensitive(true).build().ok()
+}
+
+/// The APP_IGNORE and APP_POLICY rules, compiled
+#[derive(Default)]
+pub struct AppPolicies {
+    rules: Vec<(Matcher, PrivacyRule)>,
+}
+
+impl AppPolicies {
+    /// Compile the app rules among `rules`, skipping ones that can never match
+    pub fn new(rules: &[PrivacyRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|r| r.rule_type == APP_IGNORE || r.rule_type == APP_POLICY)
+            .filter_map(|r| Some((Matcher::new(r)?, r.clone())))
+            .collect();
+        AppPolicies { rules }
+    }
+
+    /// The policy for clips copied in `window`, everything is captured when no rule matches
+    pub fn resolve(&self, window: Option<&SourceWindow>) -> AppPolicy {
+        let mut policy = AppPolicy::default();
+        let Some(window) = window else { return policy };
+        for (matcher, rule) in &self.rules {
+            if matcher.matches(window) {
+                policy.merge(rule);
+            }
+        }
+        policy
+    }
+}
+
+// Compiled rules, cleared when the `privacy_rules` table changes
+static COMPILED: RwLock<Option<Arc<AppPolicies>>> = RwLock::new(None);
+
+/// Call after changing the `privacy_rules` table
+pub fn invalidate() {
+    *COMPILED.write().unwrap() = None;
+}
+
+/// The compiled rules, read from the database when they changed since the last clip.
+/// A failed read is an error rather than "no rules", so ignored apps stay ignored.
+pub async fn policies(pool: &Pool<Sqlite>) -> Result<Arc<AppPolicies>, String> {
+    if let Some(compiled) = COMPILED.read().unwrap().clone() {
+        return Ok(compiled);
+    }
+    let rules = get_privacy_rules(pool).await.map_err(|e| format!("Failed to load app rules: {}", e))?;
+    let compiled = Arc::new(AppPolicies::new(&rules));
+    *COMPILED.write().unwrap() = Some(compiled.clone());
+    Ok(compiled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rule_type: &str, match_kind: &str, value: &str) -> PrivacyRule {
+        PrivacyRule {
+            id: 0,
+            rule_type: rule_type.to_string(),
+            value: value.to_string(),
+            is_active: true,
+            placeholder: None,
+            keep_original: false,
+            match_kind: match_kind.to_string(),
+            capture: Capture::All.as_str().to_string(),
+            mark_sensitive: false,
+            delete_after: None,
+            tags: None,
+            strip_formatting: false,
+        }
+    }
+
+    fn window(name: &str, path: &str, title: &str) -> SourceWindow {
+        SourceWindow { name: name.to_string(), path: path.to_string(), title: title.to_string() }
+    }
+
+    fn matches(match_kind: &str, value: &str, window: &SourceWindow) -> bool {
+        Matcher::new(&rule(APP_POLICY, match_kind, value)).is_some_and(|m| m.matches(window))
+    }
+
+    #[test]
+    fn path_matches_the_whole_executable_path() {
+        let firefox = window("firefox", "/usr/lib/firefox/firefox", "Mozilla Firefox");
+        assert!(matches("path", "/usr/lib/firefox/firefox", &firefox));
+        assert!(!matches("path", "/usr/lib/firefox", &firefox));
+        // Windows without a known path never match
+        assert!(!matches("path", "/usr/lib/firefox/firefox", &window("firefox", "", "Mozilla Firefox")));
+    }
+
+    #[test]
+    fn title_regex_only_looks_at_the_title() {
+        let bank = window("firefox", "/usr/bin/firefox", "My Bank - Login");
+        assert!(matches("title_regex", r"(?i)bank\b.*login", &bank));
+        assert!(!matches("title_regex", "firefox", &bank));
+        // Invalid patterns are skipped rather than matching everything
+        assert!(Matcher::new(&rule(APP_POLICY, "title_regex", "(")).is_none());
+    }
+
+    #[test]
+    fn wildcard_matches_a_whole_field_without_case() {
+        let keepass = window("KeePassXC", "/usr/bin/keepassxc", "Passwords.kdbx - KeePassXC");
+        assert!(matches("wildcard", "keepass*", &keepass));
+        assert!(matches("wildcard", "*/keepass??", &keepass));
+        assert!(matches("wildcard", "*.kdbx*", &keepass));
+        assert!(!matches("wildcard", "keepass", &keepass));
+        // Other regex syntax is literal
+        assert!(!matches("wildcard", "keepass.+", &keepass));
+    }
+
+    #[test]
+    fn substring_looks_at_name_and_title_without_case() {
+        let slack = window("Slack", "/usr/bin/slack", "general - Acme");
+        assert!(matches("substring", "slac", &slack));
+        assert!(matches("substring", "ACME", &slack));
+        assert!(!matches("substring", "/usr/bin", &slack));
+        assert!(Matcher::new(&rule(APP_POLICY, "substring", "")).is_none());
+    }
+
+    #[test]
+    fn merge_keeps_the_strictest_settings() {
+        let mut no_images = rule(APP_POLICY, "substring", "slack");
+        no_images.capture = Capture::NoImages.as_str().to_string();
+        no_images.delete_after = Some(60);
+        no_images.tags = Some(r#"["work"]"#.to_string());
+        let mut sensitive = rule(APP_POLICY, "substring", "acme");
+        sensitive.mark_sensitive = true;
+        sensitive.delete_after = Some(10);
+        sensitive.tags = Some(r#"["work","acme"]"#.to_string());
+        sensitive.strip_formatting = true;
+
+        let mut policy = AppPolicy::default();
+        policy.merge(&no_images);
+        policy.merge(&sensitive);
+        assert_eq!(policy.capture, Capture::NoImages);
+        assert!(policy.mark_sensitive);
+        assert_eq!(policy.delete_after, Some(10));
+        assert_eq!(policy.tags, vec!["work", "acme"]);
+        assert!(policy.strip_formatting);
+        assert_eq!(policy.matched, vec!["slack", "acme"]);
+
+        // An ignore rule captures nothing, whatever its capture column says
+        policy.merge(&rule(APP_IGNORE, "substring", "general"));
+        assert_eq!(policy.capture, Capture::None);
+        assert_eq!(policy.delete_after, Some(10));
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use base64::Engine;
+use sqlx::{Pool, Sqlite, Row};
+use log::{info, warn};
+
+/// Longest edge of the thumbnails shown in list views
+const THUMB_SIZE: u32 = 256;
+
+/// Unreferenced blobs and files younger than this survive GC, a clip may be about to point at them
+const GC_GRACE_SECONDS: u64 = 60 * 60;
+
+/// A file in the blob store, named after the BLAKE3 hash of its bytes
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Blob {
+    pub hash: String,
+    pub path: String,
+    pub thumb_path: Option<String>,
+    pub size: i64,
+}
+
+/// What a GC pass removed
+#[derive(Debug, Default, serde::Serialize)]
+pub struct GcReport {
+    pub removed_blobs: u64,
+    pub removed_files: u64,
+    pub reclaimed_bytes: u64,
+}
+
+pub fn blobs_dir(app_dir: &Path) -> PathBuf {
+    app_dir.join("blobs")
+}
+
+// blobs/ab/abcdef....png, fanned out so no directory grows too large
+fn blob_file(app_dir: &Path, hash: &str, mime: &str) -> PathBuf {
+    let ext = match mime {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/bmp" => "bmp",
+        _ => "bin",
+    };
+    blobs_dir(app_dir).join(&hash[..2]).join(format!("{}.{}", hash, ext))
+}
+
+fn thumb_file(app_dir: &Path, hash: &str) -> PathBuf {
+    blobs_dir(app_dir).join("thumbs").join(format!("{}.png", hash))
+}
+
+/// Write `data` to `path` unless a complete copy is already there
+fn write_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if path.metadata().map(|m| m.len() == data.len() as u64).unwrap_or(false) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Write next to the target and rename, readers never see half a blob
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)
+}
+
+fn make_thumbnail(app_dir: &Path, hash: &str, data: &[u8]) -> Option<PathBuf> {
+    let img = image::load_from_memory(data).ok()?;
+    if img.width() <= THUMB_SIZE && img.height() <= THUMB_SIZE {
+        // Small enough to be its own thumbnail
+        return None;
+    }
+    let mut buf = Cursor::new(Vec::new());
+    img.thumbnail(THUMB_SIZE, THUMB_SIZE).write_to(&mut buf, image::ImageFormat::Png).ok()?;
+    let path = thumb_file(app_dir, hash);
+    match write_file(&path, buf.get_ref()) {
+        Ok(_) => Some(path),
+        Err(e) => {
+            warn!("Failed to write thumbnail for blob {}: {}", hash, e);
+            None
+        }
+    }
+}
+
+/// Store `data` under its hash. Storing the same bytes again returns the existing blob.
+pub async fn put(pool: &Pool<Sqlite>, app_dir: &Path, data: &[u8], mime: &str) -> Result<Blob, String> {
+    let hash = blake3::hash(data).to_string();
+
+    if let Some(blob) = get(pool, &hash).await.map_err(|e| e.to_string())? {
+        if Path::new(&blob.path).exists() {
+            // Touch it so a GC running right now doesn't take it away from the clip about to use it
+            sqlx::query("UPDATE blobs SET created_at = CURRENT_TIMESTAMP WHERE hash = ?")
+                .bind(&hash)
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            return Ok(blob);
+        }
+    }
+
+    let path = blob_file(app_dir, &hash, mime);
+    write_file(&path, data).map_err(|e| format!("Failed to write blob: {}", e))?;
+    let thumb_path = if mime.starts_with("image/") {
+        make_thumbnail(app_dir, &hash, data).map(|p| p.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    let blob = Blob {
+        hash,
+        path: path.to_string_lossy().to_string(),
+        thumb_path,
+        size: data.len() as i64,
+    };
+    sqlx::query("INSERT INTO blobs (hash, path, thumb_path, mime, size) VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(hash) DO UPDATE SET path = excluded.path, thumb_path = excluded.thumb_path, created_at = CURRENT_TIMESTAMP")
+        .bind(&blob.hash)
+        .bind(&blob.path)
+        .bind(&blob.thumb_path)
+        .bind(mime)
+        .bind(blob.size)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(blob)
+}
+
+/// Encode an RGBA buffer as PNG and store it
+pub async fn put_rgba(pool: &Pool<Sqlite>, app_dir: &Path, width: u32, height: u32, rgba: Vec<u8>) -> Result<Blob, String> {
+    let img = image::RgbaImage::from_raw(width, height, rgba)
+        .ok_or_else(|| "Image buffer does not match its size".to_string())?;
+    let mut buf = Cursor::new(Vec::new());
+    img.write_to(&mut buf, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    put(pool, app_dir, buf.get_ref(), "image/png").await
+}
+
+pub async fn get(pool: &Pool<Sqlite>, hash: &str) -> Result<Option<Blob>, sqlx::Error> {
+    let row = sqlx::query("SELECT hash, path, thumb_path, size FROM blobs WHERE hash = ?")
+        .bind(hash)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|r| Blob {
+        hash: r.get("hash"),
+        path: r.get("path"),
+        thumb_path: r.get("thumb_path"),
+        size: r.get("size"),
+    }))
+}
+
+/// Move image clips and large clip formats saved before the blob store existed into it.
+/// The old files are left for `gc` to sweep.
+pub async fn adopt_legacy(pool: &Pool<Sqlite>, app_dir: &Path) -> Result<u64, String> {
+    let mut adopted = 0;
+
+    // Listener images were files under images/, screen captures were base64 data URIs in the content
+    let clips = sqlx::query("SELECT id, content FROM clips WHERE type = 'image' AND blob_hash IS NULL")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    for row in clips {
+        let id: i64 = row.get("id");
+        let content: String = row.get("content");
+        let loaded = if let Some(uri) = content.strip_prefix("data:") {
+            uri.split_once(',').and_then(|(meta, payload)| {
+                let data = base64::engine::general_purpose::STANDARD.decode(payload).ok()?;
+                Some((data, meta.trim_end_matches(";base64").to_string()))
+            })
+        } else {
+            let mime = image::ImageFormat::from_path(&content)
+                .map(|f| f.to_mime_type().to_string())
+                .unwrap_or_else(|_| "image/png".to_string());
+            fs::read(&content).ok().map(|data| (data, mime))
+        };
+        let Some((data, mime)) = loaded else {
+            warn!("Image of clip {} is missing, leaving it as is", id);
+            continue;
+        };
+        let blob = put(pool, app_dir, &data, &mime).await?;
+        sqlx::query("UPDATE clips SET content = ?, blob_hash = ? WHERE id = ?")
+            .bind(&blob.path)
+            .bind(&blob.hash)
+            .bind(id)
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        adopted += 1;
+    }
+
+    let formats = sqlx::query("SELECT id, mime, path FROM clip_formats WHERE path IS NOT NULL AND blob_hash IS NULL")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    for row in formats {
+        let id: i64 = row.get("id");
+        let mime: String = row.get("mime");
+        let path: String = row.get("path");
+        let Ok(data) = fs::read(&path) else { continue };
+        let blob = put(pool, app_dir, &data, &mime).await?;
+        sqlx::query("UPDATE clip_formats SET path = ?, blob_hash = ? WHERE id = ?")
+            .bind(&blob.path)
+            .bind(&blob.hash)
+            .bind(id)
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        adopted += 1;
+    }
+
+    if adopted > 0 {
+        info!("Moved {} legacy files into the blob store", adopted);
+    }
+    Ok(adopted)
+}
+
+/// Delete unreferenced blobs and stray files under the blob store and the legacy image/format dirs
+pub async fn gc(pool: &Pool<Sqlite>, app_dir: &Path) -> Result<GcReport, String> {
+    let mut report = GcReport::default();
+
+    // Recount first, a drifted counter must never delete a blob that is still in use
+    sqlx::query("UPDATE blobs SET refcount =
+        (SELECT COUNT(*) FROM clips WHERE blob_hash = blobs.hash) + (SELECT COUNT(*) FROM clip_formats WHERE blob_hash = blobs.hash)")
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let orphans = sqlx::query("SELECT hash, path, thumb_path FROM blobs WHERE refcount <= 0 AND created_at < datetime('now', ?)")
+        .bind(format!("-{} seconds", GC_GRACE_SECONDS))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    for row in orphans {
+        let hash: String = row.get("hash");
+        let deleted = sqlx::query("DELETE FROM blobs WHERE hash = ? AND refcount <= 0")
+            .bind(&hash)
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        // Picked up again since the select
+        if deleted.rows_affected() == 0 {
+            continue;
+        }
+        report.removed_blobs += 1;
+        let path: String = row.get("path");
+        let thumb_path: Option<String> = row.get("thumb_path");
+        for file in std::iter::once(path).chain(thumb_path) {
+            remove_file(Path::new(&file), &mut report);
+        }
+    }
+
+    // Files without a row: interrupted writes, rows removed by hand
+    let known: HashSet<String> = sqlx::query("SELECT path FROM blobs UNION SELECT thumb_path FROM blobs WHERE thumb_path IS NOT NULL")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    sweep(&blobs_dir(app_dir), &known, &mut report);
+
+    // images/ and formats/ predate the blob store, their files were never removed with their clips
+    let legacy: HashSet<String> = sqlx::query("SELECT content FROM clips WHERE type = 'image' UNION SELECT path FROM clip_formats WHERE path IS NOT NULL")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    sweep(&app_dir.join("images"), &legacy, &mut report);
+    sweep(&app_dir.join("formats"), &legacy, &mut report);
+
+    info!("Blob GC removed {} blobs, {} files, {} bytes", report.removed_blobs, report.removed_files, report.reclaimed_bytes);
+    Ok(report)
+}
+
+fn remove_file(path: &Path, report: &mut GcReport) {
+    let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+    if fs::remove_file(path).is_ok() {
+        report.removed_files += 1;
+        report.reclaimed_bytes += size;
+    }
+}
+
+fn sweep(dir: &Path, keep: &HashSet<String>, report: &mut GcReport) {
+    if !dir.exists() {
+        return;
+    }
+    let grace = Duration::from_secs(GC_GRACE_SECONDS);
+    for entry in walkdir::WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() || keep.contains(entry.path().to_string_lossy().as_ref()) {
+            continue;
+        }
+        let recent = entry.metadata().ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .map(|age| age < grace)
+            .unwrap_or(true);
+        if !recent {
+            remove_file(entry.path(), report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn png(color: u8) -> Vec<u8> {
+        let img = image::RgbaImage::from_pixel(2, 2, image::Rgba([color, 0, 0, 255]));
+        let mut buf = Cursor::new(Vec::new());
+        img.write_to(&mut buf, image::ImageFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
+    async fn refcount(pool: &Pool<Sqlite>, hash: &str) -> i64 {
+        sqlx::query_scalar("SELECT refcount FROM blobs WHERE hash = ?").bind(hash).fetch_one(pool).await.unwrap()
+    }
+
+    /// Make a blob and its files look older than the grace period
+    async fn age(pool: &Pool<Sqlite>, blob: &Blob) {
+        sqlx::query("UPDATE blobs SET created_at = datetime('now', '-2 hours') WHERE hash = ?").bind(&blob.hash).execute(pool).await.unwrap();
+        age_file(Path::new(&blob.path));
+    }
+
+    fn age_file(path: &Path) {
+        let old = SystemTime::now() - Duration::from_secs(GC_GRACE_SECONDS * 2);
+        fs::File::options().write(true).open(path).unwrap().set_modified(old).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refcounts_follow_clips_and_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::open_db(dir.path()).await.unwrap();
+        let first = put(&pool, dir.path(), &png(1), "image/png").await.unwrap();
+        let second = put(&pool, dir.path(), &png(2), "image/png").await.unwrap();
+        assert_eq!(refcount(&pool, &first.hash).await, 0);
+
+        let id = db::insert_clip(&pool, first.path.clone(), "image".to_string(), "c".to_string(), None, None).await.unwrap();
+        db::set_clip_blob(&pool, id, &first.hash).await.unwrap();
+        db::insert_clip_format(&pool, id, "image/png", None, Some(&first), first.size).await.unwrap();
+        assert_eq!(refcount(&pool, &first.hash).await, 2);
+
+        db::set_clip_blob(&pool, id, &second.hash).await.unwrap();
+        assert_eq!(refcount(&pool, &first.hash).await, 1);
+        assert_eq!(refcount(&pool, &second.hash).await, 1);
+
+        // The format goes with the clip
+        db::delete_clip(&pool, id).await.unwrap();
+        assert_eq!(refcount(&pool, &first.hash).await, 0);
+        assert_eq!(refcount(&pool, &second.hash).await, 0);
+    }
+
+    #[tokio::test]
+    async fn orphans_outlive_the_grace_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::open_db(dir.path()).await.unwrap();
+        let orphan = put(&pool, dir.path(), &png(1), "image/png").await.unwrap();
+        let used = put(&pool, dir.path(), &png(2), "image/png").await.unwrap();
+        let id = db::insert_clip(&pool, used.path.clone(), "image".to_string(), "c".to_string(), None, None).await.unwrap();
+        db::set_clip_blob(&pool, id, &used.hash).await.unwrap();
+
+        let report = gc(&pool, dir.path()).await.unwrap();
+        assert_eq!(report.removed_blobs, 0);
+        assert!(Path::new(&orphan.path).exists());
+
+        age(&pool, &orphan).await;
+        age(&pool, &used).await;
+        let report = gc(&pool, dir.path()).await.unwrap();
+        assert_eq!(report.removed_blobs, 1);
+        assert!(!Path::new(&orphan.path).exists());
+        assert!(get(&pool, &orphan.hash).await.unwrap().is_none());
+        assert!(Path::new(&used.path).exists());
+
+        // A stray file in the store only goes once it is old too
+        let stray = Path::new(&used.path).with_file_name("stray.tmp");
+        fs::write(&stray, b"half").unwrap();
+        gc(&pool, dir.path()).await.unwrap();
+        assert!(stray.exists());
+        age_file(&stray);
+        gc(&pool, dir.path()).await.unwrap();
+        assert!(!stray.exists());
+    }
+
+    #[tokio::test]
+    async fn legacy_files_without_a_clip_are_swept() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::open_db(dir.path()).await.unwrap();
+        let images = dir.path().join("images");
+        let formats = dir.path().join("formats");
+        fs::create_dir_all(&images).unwrap();
+        fs::create_dir_all(&formats).unwrap();
+        let kept = images.join("kept.png");
+        let dropped = images.join("dropped.png");
+        let recent = images.join("recent.png");
+        let dropped_format = formats.join("1-text_rtf.bin");
+        for file in [&kept, &dropped, &recent, &dropped_format] {
+            fs::write(file, b"old").unwrap();
+        }
+        for file in [&kept, &dropped, &dropped_format] {
+            age_file(file);
+        }
+        db::insert_clip(&pool, kept.to_string_lossy().to_string(), "image".to_string(), "c".to_string(), None, None).await.unwrap();
+
+        let report = gc(&pool, dir.path()).await.unwrap();
+        assert_eq!(report.removed_files, 2);
+        assert!(kept.exists() && recent.exists());
+        assert!(!dropped.exists() && !dropped_format.exists());
+    }
+
+    #[tokio::test]
+    async fn screen_captures_stored_as_data_uris_are_adopted() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::open_db(dir.path()).await.unwrap();
+        let data = png(3);
+        let uri = format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(&data));
+        let id = db::insert_clip(&pool, uri, "image".to_string(), "c".to_string(), None, None).await.unwrap();
+
+        assert_eq!(adopt_legacy(&pool, dir.path()).await.unwrap(), 1);
+        let clip = db::get_clip(&pool, id).await.unwrap().unwrap();
+        let hash = blake3::hash(&data).to_string();
+        let blob = get(&pool, &hash).await.unwrap().unwrap();
+        assert_eq!(clip.content, blob.path);
+        assert_eq!(fs::read(&blob.path).unwrap(), data);
+        assert_eq!(refcount(&pool, &hash).await, 1);
+        // Nothing left to adopt
+        assert_eq!(adopt_legacy(&pool, dir.path()).await.unwrap(), 0);
+    }
+}
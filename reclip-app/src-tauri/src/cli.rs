@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use clap::{Args, Subcommand};
+use serde_json::{json, Value};
+use crate::ipc;
+
+/// Answered by the running instance, or straight from the database when it isn't running (e.g. over SSH)
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List recent clips
+    #[command(alias = "history")]
+    List(ListArgs),
+    /// Search clips, same syntax as the search bar
+    Search {
+        query: Vec<String>,
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Print a clip
+    #[command(alias = "get")]
+    Show { id: i64 },
+    /// Add a clip from a file, or stdin when no file is given
+    Add {
+        file: Option<PathBuf>,
+        /// text, html or image; detected from the content when unset
+        #[arg(long = "type")]
+        type_: Option<String>,
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// Delete clips
+    Delete {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Pin a clip
+    Pin {
+        id: i64,
+        /// Unpin instead
+        #[arg(long)]
+        unpin: bool,
+    },
+    /// Write clips and snippets as JSON to a file, or stdout when no file is given
+    Export { file: Option<PathBuf> },
+    /// Add the clips and snippets of an export, skipping ones already there
+    Import { file: PathBuf },
+    /// Snippet commands
+    #[command(alias = "snippet")]
+    Snippets {
+        #[command(subcommand)]
+        command: SnippetCommand,
+    },
+    /// Counts by day, type and source app
+    Stats,
+}
+
+#[derive(Args, Debug)]
+pub struct ListArgs {
+    #[arg(short = 'n', long, default_value_t = 20)]
+    limit: i64,
+    #[arg(long, default_value_t = 0)]
+    offset: i64,
+    /// text, image, html, links, colors...
+    #[arg(long = "type")]
+    type_: Option<String>,
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Source app, matched case-insensitively on part of the name
+    #[arg(long)]
+    app: Option<String>,
+    #[arg(long)]
+    pinned: bool,
+    #[arg(long)]
+    favorites: bool,
+    /// YYYY-MM-DD, today or yesterday
+    #[arg(long)]
+    after: Option<String>,
+    #[arg(long)]
+    before: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnippetCommand {
+    List,
+    /// Add a snippet, content from a file or stdin
+    Add {
+        #[arg(long)]
+        title: String,
+        file: Option<PathBuf>,
+        #[arg(long, default_value = "")]
+        language: String,
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        #[arg(long, default_value = "")]
+        description: String,
+        #[arg(long, default_value = "")]
+        folder: String,
+    },
+    /// Print a snippet by title with {{date}}, {{time}}, {{datetime}} and --var placeholders filled in
+    #[command(alias = "get")]
+    Render {
+        name: String,
+        /// name=value for a {{name}} placeholder
+        #[arg(long = "var", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+    },
+}
+
+fn parse_var(input: &str) -> Result<(String, String), String> {
+    input.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("expected name=value, got '{}'", input))
+}
+
+/// Quote filter values with spaces, e.g. app names
+fn filter(key: &str, value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("{}:\"{}\"", key, value)
+    } else {
+        format!("{}:{}", key, value)
+    }
+}
+
+impl ListArgs {
+    /// The filters as a search bar query
+    fn query(&self) -> String {
+        let mut parts: Vec<String> = self.tags.iter().map(|t| filter("tag", t)).collect();
+        if let Some(app) = &self.app {
+            parts.push(filter("app", app));
+        }
+        if let Some(after) = &self.after {
+            parts.push(filter("after", after));
+        }
+        if let Some(before) = &self.before {
+            parts.push(filter("before", before));
+        }
+        if self.pinned {
+            parts.push("is:pinned".to_string());
+        }
+        parts.join(" ")
+    }
+}
+
+fn read_input(file: Option<&PathBuf>) -> Result<Vec<u8>, String> {
+    match file.filter(|f| f.as_os_str() != "-") {
+        Some(path) => std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e)),
+        None => {
+            let mut data = Vec::new();
+            std::io::stdin().read_to_end(&mut data).map_err(|e| format!("Failed to read stdin: {}", e))?;
+            Ok(data)
+        }
+    }
+}
+
+fn read_text(file: Option<&PathBuf>) -> Result<String, String> {
+    String::from_utf8(read_input(file)?).map_err(|_| "Input is not UTF-8 text".to_string())
+}
+
+/// Fill in the placeholders the paste flow fills in, plus user values
+fn render_snippet(content: &str, vars: &[(String, String)]) -> Result<String, String> {
+    let now = chrono::Local::now();
+    let mut values: HashMap<&str, String> = HashMap::from([
+        ("date", now.format("%Y-%m-%d").to_string()),
+        ("time", now.format("%H:%M").to_string()),
+        ("datetime", now.format("%Y-%m-%d %H:%M:%S").to_string()),
+    ]);
+    values.extend(vars.iter().map(|(k, v)| (k.as_str(), v.clone())));
+
+    let placeholder = regex::Regex::new(r"\{\{(.*?)\}\}").unwrap();
+    let mut missing = Vec::new();
+    let rendered = placeholder.replace_all(content, |caps: &regex::Captures| {
+        match values.get(&caps[1]) {
+            Some(value) => value.clone(),
+            None => {
+                missing.push(caps[1].to_string());
+                caps[0].to_string()
+            }
+        }
+    }).into_owned();
+
+    if missing.is_empty() {
+        Ok(rendered)
+    } else {
+        Err(format!("No value for {}, pass --var NAME=VALUE", missing.iter().map(|m| format!("{{{{{}}}}}", m)).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+/// One line per clip: id, type, pin marker and the start of the content
+fn print_clips(clips: &Value) {
+    for clip in clips.as_array().into_iter().flatten() {
+        let content = clip["content"].as_str().unwrap_or_default();
+        let first_line = content.lines().find(|l| !l.trim().is_empty()).unwrap_or_default().trim();
+        let mut preview: String = first_line.chars().take(80).collect();
+        if preview.len() < first_line.len() {
+            preview.push('…');
+        }
+        let pin = if clip["pinned"].as_bool().unwrap_or(false) { "*" } else { " " };
+        // Ignore a closed pipe, e.g. `reclip list | head`
+        let _ = writeln!(std::io::stdout(), "{:>6} {} {:<9} {}", clip["id"], pin, clip["type"].as_str().unwrap_or_default(), preview);
+    }
+}
+
+fn print_json(value: &Value) {
+    let _ = writeln!(std::io::stdout(), "{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+/// What `--copy` puts on the clipboard
+enum CopyContent {
+    Text(String),
+    Html { html: String, plain: String },
+    /// The PNG or JPEG bytes for history, decoded for the clipboard
+    Image { data: Vec<u8>, rgba: image::RgbaImage },
+}
+
+impl CopyContent {
+    /// `value` is the content itself, or `-` for stdin. Without a MIME type, PNG and JPEG data become an image.
+    fn read(value: &str, mime: Option<&str>) -> Result<CopyContent, String> {
+        let data = if value == "-" { read_input(None)? } else { value.as_bytes().to_vec() };
+        let text = |data: Vec<u8>| String::from_utf8(data).map_err(|_| "Input is neither UTF-8 text nor a PNG/JPEG image".to_string());
+        let image = |data: Vec<u8>| {
+            let rgba = image::load_from_memory(&data).map_err(|e| format!("Failed to load image: {}", e))?.to_rgba8();
+            Ok(CopyContent::Image { data, rgba })
+        };
+        match mime.map(|m| m.to_lowercase()).as_deref() {
+            Some("text/html") => {
+                let html = text(data)?;
+                let plain = html2text::from_read(html.as_bytes(), 80).unwrap_or_else(|_| html.clone());
+                Ok(CopyContent::Html { html, plain })
+            }
+            Some(m) if m.starts_with("text/") => Ok(CopyContent::Text(text(data)?)),
+            Some(m) if m.starts_with("image/") => image(data),
+            Some(m) => Err(format!("Unsupported MIME type '{}', expected text/plain, text/html or an image type", m)),
+            None => match image::guess_format(&data) {
+                Ok(image::ImageFormat::Png | image::ImageFormat::Jpeg) => image(data),
+                _ => Ok(CopyContent::Text(text(data)?)),
+            },
+        }
+    }
+
+    /// What to record in history, and the hashes the clipboard listener will compute for this content
+    fn clip(&self) -> Option<(Value, Vec<String>)> {
+        let hash = |data: &[u8]| blake3::hash(data).to_string();
+        match self {
+            CopyContent::Text(text) if text.trim().is_empty() => None,
+            CopyContent::Text(text) => Some((json!({ "type": "text", "content": text }), vec![hash(text.as_bytes())])),
+            CopyContent::Html { html, .. } if html.trim().is_empty() => None,
+            // Short or unstructured HTML is captured as its text
+            CopyContent::Html { html, plain } => Some((json!({ "type": "html", "content": html }), vec![hash(html.as_bytes()), hash(plain.as_bytes())])),
+            CopyContent::Image { data, rgba } => Some((json!({ "type": "image", "data": BASE64.encode(data) }), vec![hash(rgba.as_raw())])),
+        }
+    }
+}
+
+/// `reclip --copy`: put content on the clipboard and record it in history as coming from `cli`
+pub fn copy(value: &str, mime: Option<&str>, tags: &[String]) -> Result<(), String> {
+    let content = CopyContent::read(value, mime)?;
+
+    // Recorded before copying, so a running ReClip knows to skip the clipboard change
+    if let Some((mut params, clipboard_hashes)) = content.clip() {
+        params["tags"] = json!(tags);
+        params["sender_app"] = json!("cli");
+        params["clipboard_hashes"] = json!(clipboard_hashes);
+        // The copy still matters when history can't be written
+        if let Err(e) = ipc::call("add", params) {
+            eprintln!("Warning: not recorded in history: {}", e);
+        }
+    }
+
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| format!("Failed to initialize clipboard: {}", e))?;
+    let result = match content {
+        CopyContent::Text(text) => clipboard.set_text(text),
+        CopyContent::Html { html, plain } => clipboard.set_html(html, Some(plain)),
+        CopyContent::Image { rgba, .. } => {
+            let (width, height) = rgba.dimensions();
+            clipboard.set_image(arboard::ImageData {
+                width: width as usize,
+                height: height as usize,
+                bytes: std::borrow::Cow::Owned(rgba.into_raw()),
+            })
+        }
+    };
+    result.map_err(|e| format!("Error copying to clipboard: {}", e))
+}
+
+/// Run a subcommand, printing JSON instead of text when `json` is set
+pub fn run(command: Command, json: bool) -> Result<(), String> {
+    match command {
+        Command::List(args) => {
+            let params = json!({
+                "limit": args.limit,
+                "offset": args.offset,
+                "query": args.query(),
+                "type": args.type_,
+                "favorites": args.favorites,
+            });
+            let clips = ipc::call("history", params)?;
+            if json { print_json(&clips) } else { print_clips(&clips) }
+        }
+        Command::Search { query, limit } => {
+            let clips = ipc::call("search", json!({ "query": query.join(" "), "limit": limit }))?;
+            if json { print_json(&clips) } else { print_clips(&clips) }
+        }
+        Command::Show { id } => {
+            let clip = ipc::call("get", json!({ "id": id }))?;
+            if json {
+                print_json(&clip);
+            } else {
+                // Image clips print the path of the image file
+                print!("{}", clip["content"].as_str().unwrap_or_default());
+            }
+        }
+        Command::Add { file, type_, tags } => {
+            let data = read_input(file.as_ref())?;
+            let is_image = type_.as_deref().map_or_else(|| image::guess_format(&data).is_ok(), |t| t == "image");
+            let params = if is_image {
+                json!({ "type": "image", "data": BASE64.encode(&data), "tags": tags, "sender_app": "cli" })
+            } else {
+                let content = String::from_utf8(data).map_err(|_| "Input is neither UTF-8 text nor an image".to_string())?;
+                json!({ "type": type_.unwrap_or_else(|| "text".to_string()), "content": content, "tags": tags, "sender_app": "cli" })
+            };
+            let clip = ipc::call("add", params)?;
+            if json { print_json(&clip) } else { println!("Added clip {}", clip["id"]) }
+        }
+        Command::Delete { ids } => {
+            for id in &ids {
+                ipc::call("delete", json!({ "id": id }))?;
+                if !json {
+                    println!("Deleted clip {}", id);
+                }
+            }
+            if json {
+                print_json(&json!({ "deleted": ids }));
+            }
+        }
+        Command::Pin { id, unpin } => {
+            let result = ipc::call("pin", json!({ "id": id, "pinned": !unpin }))?;
+            if json { print_json(&result) } else { println!("{} clip {}", if unpin { "Unpinned" } else { "Pinned" }, id) }
+        }
+        Command::Export { file } => {
+            let export = ipc::call("export", Value::Null)?;
+            let Some(path) = file.filter(|f| f.as_os_str() != "-") else {
+                // The export itself is the output
+                print_json(&export);
+                return Ok(());
+            };
+            let mut out = std::fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+            serde_json::to_writer_pretty(&mut out, &export).map_err(|e| e.to_string())?;
+            out.flush().map_err(|e| e.to_string())?;
+
+            let clips = export["clips"].as_array().map_or(0, |c| c.len());
+            let snippets = export["snippets"].as_array().map_or(0, |s| s.len());
+            if json {
+                print_json(&json!({ "path": path, "clips": clips, "snippets": snippets }));
+            } else {
+                println!("Exported {} clips and {} snippets to {}", clips, snippets, path.display());
+            }
+        }
+        Command::Import { file } => {
+            let export: Value = serde_json::from_str(&read_text(Some(&file))?)
+                .map_err(|e| format!("{} is not a ReClip export: {}", file.display(), e))?;
+            let result = ipc::call("import", export)?;
+            if json {
+                print_json(&result);
+            } else {
+                println!(
+                    "Imported {} clips ({} already there) and {} snippets ({} already there)",
+                    result["clips"], result["skipped_clips"], result["snippets"], result["skipped_snippets"]
+                );
+            }
+        }
+        Command::Snippets { command: SnippetCommand::List } => {
+            let snippets = ipc::call("snippets.list", Value::Null)?;
+            if json {
+                print_json(&snippets);
+            } else {
+                for snippet in snippets.as_array().into_iter().flatten() {
+                    let language = snippet["language"].as_str().filter(|l| !l.is_empty()).map(|l| format!(" [{}]", l)).unwrap_or_default();
+                    println!("{:>6} {}{}", snippet["id"], snippet["title"].as_str().unwrap_or_default(), language);
+                }
+            }
+        }
+        Command::Snippets { command: SnippetCommand::Add { title, file, language, tags, description, folder } } => {
+            let content = read_text(file.as_ref())?;
+            let result = ipc::call("snippets.add", json!({
+                "title": title,
+                "content": content,
+                "language": language,
+                "tags": tags,
+                "description": description,
+                "folder": folder,
+            }))?;
+            if json { print_json(&result) } else { println!("Added snippet {}", result["id"]) }
+        }
+        Command::Snippets { command: SnippetCommand::Render { name, vars } } => {
+            let snippet = ipc::call("snippet.get", json!({ "name": name }))?;
+            let content = render_snippet(snippet["content"].as_str().unwrap_or_default(), &vars)?;
+            if json { print_json(&json!({ "title": snippet["title"], "content": content })) } else { print!("{}", content) }
+        }
+        Command::Stats => {
+            let stats = ipc::call("stats", Value::Null)?;
+            if json {
+                print_json(&stats);
+                return Ok(());
+            }
+            println!("Clips:     {} ({} today, {} this week)", stats["total_clips"], stats["clips_today"], stats["clips_this_week"]);
+            println!("Snippets:  {}", stats["total_snippets"]);
+            println!("Notes:     {}", stats["total_notes"]);
+            println!("By type:");
+            for row in stats["type_breakdown"].as_array().into_iter().flatten() {
+                println!("  {:<12} {}", row["type_name"].as_str().unwrap_or_default(), row["count"]);
+            }
+            println!("Top apps:");
+            for row in stats["top_source_apps"].as_array().into_iter().flatten() {
+                println!("  {:<12} {}", row["type_name"].as_str().unwrap_or_default(), row["count"]);
+            }
+        }
+    }
+    Ok(())
+}
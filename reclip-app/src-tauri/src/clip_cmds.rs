@@ -1,9 +1,27 @@
 use tauri::State;
 use crate::db::{self, DbState, Clip};
+use crate::search::{self, SearchError};
+use crate::events::{publish, ClipEvent};
 
 #[tauri::command]
-pub async fn get_recent_clips(state: State<'_, DbState>, limit: i64, offset: i64, search: Option<String>, type_filter: Option<String>, favorites_only: Option<bool>) -> Result<Vec<Clip>, String> {
-    db::get_clips(&state.pool, limit, offset, search, type_filter, favorites_only.unwrap_or(false))
+pub async fn get_recent_clips(state: State<'_, DbState>, limit: i64, offset: i64, search: Option<String>, type_filter: Option<String>, favorites_only: Option<bool>, include_primary: Option<bool>) -> Result<Vec<Clip>, SearchError> {
+    let query = search.as_deref().map(search::parse_clip_query).transpose()?;
+    let clips = db::get_clips(&state.pool, limit, offset, query.as_ref(), type_filter, favorites_only.unwrap_or(false), include_primary.unwrap_or(true)).await?;
+    Ok(clips)
+}
+
+/// The unmasked text of a clip a privacy rule masked, the UI asks before calling this
+#[tauri::command]
+pub async fn reveal_redacted(state: State<'_, DbState>, id: i64) -> Result<String, String> {
+    let sealed = db::get_clip_redacted_original(&state.pool, id).await.map_err(|e| e.to_string())?
+        .ok_or("No original was kept for this clip")?;
+    crate::redaction::reveal(&state.pool, &sealed).await
+}
+
+/// Formats stored with a clip (mime type and size, without the data)
+#[tauri::command]
+pub async fn get_clip_formats(state: State<'_, DbState>, id: i64) -> Result<Vec<db::ClipFormat>, String> {
+    db::get_clip_formats(&state.pool, id)
         .await
         .map_err(|e| e.to_string())
 }
@@ -30,10 +48,10 @@ pub async fn get_usage_stats(state: State<'_, DbState>) -> Result<db::UsageStats
 }
 
 #[tauri::command]
-pub async fn get_clip_stats(state: State<'_, DbState>, search: Option<String>) -> Result<db::ClipStats, String> {
-    db::get_clip_stats(&state.pool, search)
-        .await
-        .map_err(|e| e.to_string())
+pub async fn get_clip_stats(state: State<'_, DbState>, search: Option<String>) -> Result<db::ClipStats, SearchError> {
+    let query = search.as_deref().map(search::parse_clip_query).transpose()?;
+    let stats = db::get_clip_stats(&state.pool, query.as_ref()).await?;
+    Ok(stats)
 }
 
 #[tauri::command]
@@ -48,15 +66,19 @@ pub async fn delete_clip(app: tauri::AppHandle, state: State<'_, DbState>, id: i
     db::delete_clip(&state.pool, id)
         .await
         .map_err(|e| e.to_string())?;
+    publish(&app, ClipEvent::Deleted(id));
     let _ = crate::tray::update_tray_history(&app).await;
     Ok(())
 }
 
 #[tauri::command]
 pub async fn clear_clips(app: tauri::AppHandle, state: State<'_, DbState>) -> Result<(), String> {
-    db::delete_all_clips(&state.pool)
+    let ids = db::delete_all_clips(&state.pool)
         .await
         .map_err(|e| e.to_string())?;
+    for id in ids {
+        publish(&app, ClipEvent::Deleted(id));
+    }
     let _ = crate::tray::update_tray_history(&app).await;
     Ok(())
 }
@@ -66,6 +88,7 @@ pub async fn reorder_clip(app: tauri::AppHandle, state: State<'_, DbState>, id:
     db::update_clip_position(&state.pool, id, position)
         .await
         .map_err(|e| e.to_string())?;
+    publish(&app, ClipEvent::Updated(id));
     let _ = crate::tray::update_tray_history(&app).await;
     Ok(())
 }
@@ -75,6 +98,7 @@ pub async fn update_clip_tags(app: tauri::AppHandle, state: State<'_, DbState>,
     db::update_clip_tags(&state.pool, id, tags)
         .await
         .map_err(|e| e.to_string())?;
+    publish(&app, ClipEvent::Updated(id));
     let _ = crate::tray::update_tray_history(&app).await;
     Ok(())
 }
@@ -82,6 +106,7 @@ pub async fn update_clip_tags(app: tauri::AppHandle, state: State<'_, DbState>,
 #[tauri::command]
 pub async fn toggle_clip_pin(app: tauri::AppHandle, state: State<'_, DbState>, id: i64) -> Result<bool, String> {
     let res = db::toggle_pin(&state.pool, id).await.map_err(|e| e.to_string())?;
+    publish(&app, ClipEvent::Updated(id));
     let _ = crate::tray::update_tray_history(&app).await;
     Ok(res)
 }
@@ -91,6 +116,7 @@ pub async fn update_clip_content(app: tauri::AppHandle, state: State<'_, DbState
     db::update_clip_content(&state.pool, id, content)
         .await
         .map_err(|e| e.to_string())?;
+    publish(&app, ClipEvent::Updated(id));
     let _ = crate::tray::update_tray_history(&app).await;
     Ok(())
 }
@@ -98,6 +124,7 @@ pub async fn update_clip_content(app: tauri::AppHandle, state: State<'_, DbState
 #[tauri::command]
 pub async fn toggle_clip_favorite(app: tauri::AppHandle, state: State<'_, DbState>, id: i64) -> Result<bool, String> {
     let res = db::toggle_favorite(&state.pool, id).await.map_err(|e| e.to_string())?;
+    publish(&app, ClipEvent::Updated(id));
     let _ = crate::tray::update_tray_history(&app).await;
     Ok(res)
 }
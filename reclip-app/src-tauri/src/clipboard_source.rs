@@ -0,0 +1,624 @@
+use std::thread;
+use std::time::Duration;
+use arboard::Clipboard;
+use log::{info, warn};
+
+/// What woke the listener loop up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipboardChange {
+    /// The OS reported a new clipboard owner. This is a real copy, even if the content is identical.
+    Changed,
+    /// No notification available, the content has to be compared with the last capture
+    Poll,
+}
+
+/// Which system selection a clip was captured from, stored in `clips.origin`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipOrigin {
+    /// The regular clipboard (Ctrl+C)
+    Clipboard,
+    /// The X11/Wayland PRIMARY selection (select + middle-click)
+    Primary,
+}
+
+impl ClipOrigin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClipOrigin::Clipboard => "clipboard",
+            ClipOrigin::Primary => "primary",
+        }
+    }
+}
+
+/// Raw RGBA image read from the clipboard
+pub struct ClipboardImage {
+    pub width: usize,
+    pub height: usize,
+    pub bytes: Vec<u8>,
+}
+
+/// Where the listener gets clipboard changes and content from.
+/// The system implementation is `SystemClipboard`, tests can plug in a fake source.
+pub trait ClipboardSource {
+    /// Short backend name for logs
+    fn name(&self) -> &'static str;
+
+    /// Block until the clipboard may have new content
+    fn wait_for_change(&mut self) -> ClipboardChange;
+
+    fn get_text(&mut self) -> Option<String>;
+
+    fn get_image(&mut self) -> Option<ClipboardImage>;
+
+    /// Raw HTML (platform format, e.g. CF_HTML on Windows)
+    fn get_html(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Paths of copied files
+    fn get_files(&mut self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Every representation on the clipboard as (format name, bytes), MIME types where the platform has them
+    fn get_formats(&mut self) -> Vec<(String, Vec<u8>)> {
+        Vec::new()
+    }
+}
+
+/// Blocks until the platform reports a clipboard change
+trait ChangeWatcher {
+    fn name(&self) -> &'static str;
+    fn wait(&mut self) -> Result<ClipboardChange, String>;
+}
+
+/// Fallback watcher: wakes up on a fixed interval
+struct PollWatcher {
+    interval: Duration,
+}
+
+impl ChangeWatcher for PollWatcher {
+    fn name(&self) -> &'static str {
+        "polling"
+    }
+
+    fn wait(&mut self) -> Result<ClipboardChange, String> {
+        thread::sleep(self.interval);
+        Ok(ClipboardChange::Poll)
+    }
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Clipboard formats larger than this are not kept
+pub const MAX_FORMAT_BYTES: usize = 16 * 1024 * 1024;
+
+/// The formats kept for one clip add up to at most this
+pub const MAX_CLIP_FORMAT_BYTES: usize = 24 * 1024 * 1024;
+
+/// Formats read for one clip, within the size limits above
+#[derive(Default)]
+pub struct FormatBudget {
+    formats: Vec<(String, Vec<u8>)>,
+    total: usize,
+}
+
+impl FormatBudget {
+    /// Whether reading more is pointless, nothing else fits
+    pub fn is_full(&self) -> bool {
+        self.total >= MAX_CLIP_FORMAT_BYTES
+    }
+
+    /// Keep `data` if it fits in what is left
+    pub fn add(&mut self, mime: String, data: Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+        if data.len() > MAX_FORMAT_BYTES || self.total + data.len() > MAX_CLIP_FORMAT_BYTES {
+            info!("Skipping clipboard format '{}' ({} bytes)", mime, data.len());
+            return;
+        }
+        self.total += data.len();
+        self.formats.push((mime, data));
+    }
+
+    pub fn into_formats(self) -> Vec<(String, Vec<u8>)> {
+        self.formats
+    }
+}
+
+/// The system clipboard: a change watcher for notifications plus arboard for reading
+pub struct SystemClipboard {
+    clipboard: Clipboard,
+    watcher: Box<dyn ChangeWatcher>,
+    origin: ClipOrigin,
+}
+
+impl SystemClipboard {
+    /// Pick the best available change watcher for this platform, falling back to polling.
+    /// `ClipOrigin::Primary` is only available on Linux.
+    pub fn new(origin: ClipOrigin) -> Result<Self, String> {
+        if origin == ClipOrigin::Primary && !cfg!(target_os = "linux") {
+            return Err("PRIMARY selection is only available on Linux".to_string());
+        }
+        let clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+        let watcher = detect_watcher(origin);
+        info!("{} listener using '{}' backend", origin.as_str(), watcher.name());
+        Ok(Self { clipboard, watcher, origin })
+    }
+}
+
+impl ClipboardSource for SystemClipboard {
+    fn name(&self) -> &'static str {
+        self.watcher.name()
+    }
+
+    fn wait_for_change(&mut self) -> ClipboardChange {
+        match self.watcher.wait() {
+            Ok(change) => change,
+            Err(e) => {
+                // Lost the display connection or compositor, keep capturing by polling
+                warn!("Clipboard '{}' backend failed ({}), falling back to polling", self.watcher.name(), e);
+                self.watcher = Box::new(PollWatcher { interval: POLL_INTERVAL });
+                ClipboardChange::Poll
+            }
+        }
+    }
+
+    fn get_text(&mut self) -> Option<String> {
+        #[cfg(target_os = "linux")]
+        if self.origin == ClipOrigin::Primary {
+            use arboard::{GetExtLinux, LinuxClipboardKind};
+            return self.clipboard.get().clipboard(LinuxClipboardKind::Primary).text().ok();
+        }
+        self.clipboard.get_text().ok()
+    }
+
+    fn get_image(&mut self) -> Option<ClipboardImage> {
+        // Selecting never produces images worth keeping
+        if self.origin == ClipOrigin::Primary {
+            return None;
+        }
+        self.clipboard.get_image().ok().map(|image| ClipboardImage {
+            width: image.width,
+            height: image.height,
+            bytes: image.bytes.into_owned(),
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn get_files(&mut self) -> Option<Vec<String>> {
+        use crate::linux_clipboard::{self, MIME_GNOME_FILES, MIME_URI_LIST};
+        if self.origin != ClipOrigin::Clipboard {
+            return None;
+        }
+        let types = linux_clipboard::mime_types(self.origin);
+        // Nautilus offers both, gnome-copied-files also tells copy from cut
+        let mime = [MIME_GNOME_FILES, MIME_URI_LIST].into_iter().find(|m| types.iter().any(|t| t == m))?;
+        let data = linux_clipboard::read(self.origin, mime)?;
+        let files = linux_clipboard::paths_from_uri_list(&String::from_utf8_lossy(&data));
+        if files.is_empty() { None } else { Some(files) }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn get_html(&mut self) -> Option<String> {
+        if self.origin != ClipOrigin::Clipboard {
+            return None;
+        }
+        crate::linux_clipboard::read_text(self.origin, "text/html")
+    }
+
+    #[cfg(target_os = "linux")]
+    fn get_formats(&mut self) -> Vec<(String, Vec<u8>)> {
+        if self.origin != ClipOrigin::Clipboard {
+            return Vec::new();
+        }
+        crate::linux_clipboard::read_all(self.origin)
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    fn get_formats(&mut self) -> Vec<(String, Vec<u8>)> {
+        use clipboard_rs::{Clipboard as ClipboardRs, ClipboardContext};
+        let Ok(ctx) = ClipboardContext::new() else {
+            return Vec::new();
+        };
+        let mut budget = FormatBudget::default();
+        for format in ctx.available_formats().unwrap_or_default() {
+            if budget.is_full() {
+                break;
+            }
+            if let Ok(data) = ctx.get_buffer(&format) {
+                budget.add(format, data);
+            }
+        }
+        budget.into_formats()
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    fn get_html(&mut self) -> Option<String> {
+        use clipboard_rs::{Clipboard as ClipboardRs, ClipboardContext};
+        ClipboardContext::new().ok()?.get_html().ok()
+    }
+
+    #[cfg(target_os = "windows")]
+    fn get_files(&mut self) -> Option<Vec<String>> {
+        use clipboard_rs::{Clipboard as ClipboardRs, ClipboardContext};
+        ClipboardContext::new().ok()?.get_files().ok().filter(|files| !files.is_empty())
+    }
+}
+
+/// Waits until the content stops changing before reporting a change.
+/// Used for PRIMARY, which changes continuously while the user drags a selection.
+pub struct Debounced<S: ClipboardSource> {
+    inner: S,
+    delay: Duration,
+}
+
+impl<S: ClipboardSource> Debounced<S> {
+    pub fn new(inner: S, delay: Duration) -> Self {
+        Self { inner, delay }
+    }
+}
+
+impl<S: ClipboardSource> ClipboardSource for Debounced<S> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn wait_for_change(&mut self) -> ClipboardChange {
+        let change = self.inner.wait_for_change();
+        let mut current = self.inner.get_text();
+        loop {
+            thread::sleep(self.delay);
+            let latest = self.inner.get_text();
+            if latest == current {
+                return change;
+            }
+            current = latest;
+        }
+    }
+
+    fn get_text(&mut self) -> Option<String> {
+        self.inner.get_text()
+    }
+
+    fn get_image(&mut self) -> Option<ClipboardImage> {
+        self.inner.get_image()
+    }
+
+    fn get_html(&mut self) -> Option<String> {
+        self.inner.get_html()
+    }
+
+    fn get_files(&mut self) -> Option<Vec<String>> {
+        self.inner.get_files()
+    }
+
+    fn get_formats(&mut self) -> Vec<(String, Vec<u8>)> {
+        self.inner.get_formats()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn detect_watcher(origin: ClipOrigin) -> Box<dyn ChangeWatcher> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match wayland::WaylandWatcher::new(origin) {
+            Ok(w) => return Box::new(w),
+            Err(e) => info!("wlr-data-control not available ({}), trying X11", e),
+        }
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        match x11::X11Watcher::new(origin) {
+            Ok(w) => return Box::new(w),
+            Err(e) => info!("XFixes selection events not available ({}), using polling", e),
+        }
+    }
+    Box::new(PollWatcher { interval: POLL_INTERVAL })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect_watcher(_origin: ClipOrigin) -> Box<dyn ChangeWatcher> {
+    Box::new(PollWatcher { interval: POLL_INTERVAL })
+}
+
+/// X11: XFixes notifies us whenever a client takes ownership of the watched selection
+#[cfg(target_os = "linux")]
+mod x11 {
+    use super::{ChangeWatcher, ClipboardChange, ClipOrigin};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+    use x11rb::protocol::xproto::{ConnectionExt as _, CreateWindowAux, WindowClass};
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+
+    pub struct X11Watcher {
+        conn: RustConnection,
+        selection: u32,
+    }
+
+    impl X11Watcher {
+        pub fn new(origin: ClipOrigin) -> Result<Self, String> {
+            let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+            conn.xfixes_query_version(5, 0)
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map_err(|e| e.to_string())?;
+
+            // An unmapped window only used to receive selection events
+            let screen = &conn.setup().roots[screen_num];
+            let window = conn.generate_id().map_err(|e| e.to_string())?;
+            conn.create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                screen.root,
+                0, 0, 1, 1, 0,
+                WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &CreateWindowAux::new(),
+            ).map_err(|e| e.to_string())?;
+
+            let atom_name: &[u8] = match origin {
+                ClipOrigin::Clipboard => b"CLIPBOARD",
+                ClipOrigin::Primary => b"PRIMARY",
+            };
+            let selection = conn.intern_atom(false, atom_name)
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map_err(|e| e.to_string())?
+                .atom;
+
+            conn.xfixes_select_selection_input(window, selection, SelectionEventMask::SET_SELECTION_OWNER)
+                .map_err(|e| e.to_string())?;
+            conn.flush().map_err(|e| e.to_string())?;
+
+            Ok(Self { conn, selection })
+        }
+    }
+
+    impl ChangeWatcher for X11Watcher {
+        fn name(&self) -> &'static str {
+            "x11-xfixes"
+        }
+
+        fn wait(&mut self) -> Result<ClipboardChange, String> {
+            loop {
+                match self.conn.wait_for_event().map_err(|e| e.to_string())? {
+                    Event::XfixesSelectionNotify(ev) if ev.selection == self.selection && ev.owner != x11rb::NONE => {
+                        return Ok(ClipboardChange::Changed);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Wayland: wlr-data-control sends a new `selection` offer on every copy (wlroots, KDE, Hyprland...)
+/// and a `primary_selection` offer on every selection since version 2
+#[cfg(target_os = "linux")]
+mod wayland {
+    use super::{ChangeWatcher, ClipboardChange, ClipOrigin};
+    use wayland_client::globals::{registry_queue_init, GlobalListContents};
+    use wayland_client::protocol::{wl_registry, wl_seat::WlSeat};
+    use wayland_client::{event_created_child, Connection, Dispatch, EventQueue, Proxy, QueueHandle};
+    use wayland_protocols_wlr::data_control::v1::client::{
+        zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+        zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+        zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+    };
+
+    struct State {
+        origin: ClipOrigin,
+        changed: bool,
+        finished: bool,
+        /// Offer of the current selection, destroyed when replaced
+        current_offer: Option<ZwlrDataControlOfferV1>,
+    }
+
+    pub struct WaylandWatcher {
+        queue: EventQueue<State>,
+        state: State,
+        _device: ZwlrDataControlDeviceV1,
+    }
+
+    impl WaylandWatcher {
+        pub fn new(origin: ClipOrigin) -> Result<Self, String> {
+            let conn = Connection::connect_to_env().map_err(|e| e.to_string())?;
+            let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(|e| e.to_string())?;
+            let qh = queue.handle();
+
+            let seat: WlSeat = globals.bind(&qh, 1..=8, ()).map_err(|e| e.to_string())?;
+            let manager: ZwlrDataControlManagerV1 = globals.bind(&qh, 1..=2, ()).map_err(|e| e.to_string())?;
+            if origin == ClipOrigin::Primary && manager.version() < 2 {
+                return Err("compositor does not support primary selection in data control".to_string());
+            }
+            let device = manager.get_data_device(&seat, &qh, ());
+
+            let mut state = State { origin, changed: false, finished: false, current_offer: None };
+            // The compositor sends the current selection right away, don't treat it as a copy
+            queue.roundtrip(&mut state).map_err(|e| e.to_string())?;
+            state.changed = false;
+
+            Ok(Self { queue, state, _device: device })
+        }
+    }
+
+    impl ChangeWatcher for WaylandWatcher {
+        fn name(&self) -> &'static str {
+            "wayland-data-control"
+        }
+
+        fn wait(&mut self) -> Result<ClipboardChange, String> {
+            while !self.state.changed {
+                if self.state.finished {
+                    return Err("data control device was destroyed".to_string());
+                }
+                self.queue.blocking_dispatch(&mut self.state).map_err(|e| e.to_string())?;
+            }
+            self.state.changed = false;
+            Ok(ClipboardChange::Changed)
+        }
+    }
+
+    impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+        fn event(_: &mut Self, _: &wl_registry::WlRegistry, _: wl_registry::Event, _: &GlobalListContents, _: &Connection, _: &QueueHandle<Self>) {}
+    }
+
+    impl Dispatch<WlSeat, ()> for State {
+        fn event(_: &mut Self, _: &WlSeat, _: <WlSeat as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+    }
+
+    impl Dispatch<ZwlrDataControlManagerV1, ()> for State {
+        fn event(_: &mut Self, _: &ZwlrDataControlManagerV1, _: <ZwlrDataControlManagerV1 as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+    }
+
+    impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+        fn event(_: &mut Self, _: &ZwlrDataControlOfferV1, _: <ZwlrDataControlOfferV1 as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+    }
+
+    impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+        fn event(state: &mut Self, _: &ZwlrDataControlDeviceV1, event: zwlr_data_control_device_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+            let (origin, id) = match event {
+                zwlr_data_control_device_v1::Event::Selection { id } => (ClipOrigin::Clipboard, id),
+                zwlr_data_control_device_v1::Event::PrimarySelection { id } => (ClipOrigin::Primary, id),
+                zwlr_data_control_device_v1::Event::Finished => {
+                    state.finished = true;
+                    return;
+                }
+                _ => return,
+            };
+
+            if origin != state.origin {
+                // Offer for the selection we don't watch
+                if let Some(offer) = id {
+                    offer.destroy();
+                }
+                return;
+            }
+            if let Some(old) = state.current_offer.take() {
+                old.destroy();
+            }
+            // A cleared selection (no offer) is not a copy
+            if id.is_some() {
+                state.changed = true;
+            }
+            state.current_offer = id;
+        }
+
+        event_created_child!(State, ZwlrDataControlDeviceV1, [
+            zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+        ]);
+    }
+}
+
+/// Clipboard content a `ScriptedSource` step puts on the clipboard
+#[cfg(test)]
+#[derive(Default)]
+pub struct Scripted {
+    /// What successive `get_text` calls return, the last one sticks
+    pub text: Vec<&'static str>,
+    pub html: Option<&'static str>,
+    pub image: Option<Vec<u8>>,
+    pub files: Option<Vec<&'static str>>,
+    pub formats: Vec<(&'static str, &'static [u8])>,
+}
+
+#[cfg(test)]
+impl Scripted {
+    pub fn text(text: &'static str) -> Self {
+        Scripted { text: vec![text], ..Default::default() }
+    }
+}
+
+/// In-memory clipboard that replays a fixed list of changes
+#[cfg(test)]
+pub struct ScriptedSource {
+    steps: std::collections::VecDeque<(ClipboardChange, Scripted)>,
+    current: Scripted,
+    /// How many times `get_text` was called
+    pub text_reads: usize,
+}
+
+#[cfg(test)]
+impl ScriptedSource {
+    pub fn new(steps: Vec<(ClipboardChange, Scripted)>) -> Self {
+        ScriptedSource { steps: steps.into(), current: Scripted::default(), text_reads: 0 }
+    }
+}
+
+#[cfg(test)]
+impl ClipboardSource for ScriptedSource {
+    fn name(&self) -> &'static str {
+        "scripted"
+    }
+
+    fn wait_for_change(&mut self) -> ClipboardChange {
+        let (change, content) = self.steps.pop_front().expect("no clipboard changes left in the script");
+        self.current = content;
+        change
+    }
+
+    fn get_text(&mut self) -> Option<String> {
+        self.text_reads += 1;
+        let text = self.current.text.first().map(|t| t.to_string());
+        if self.current.text.len() > 1 {
+            self.current.text.remove(0);
+        }
+        text
+    }
+
+    fn get_image(&mut self) -> Option<ClipboardImage> {
+        self.current.image.clone().map(|bytes| ClipboardImage { width: 1, height: bytes.len() / 4, bytes })
+    }
+
+    fn get_html(&mut self) -> Option<String> {
+        self.current.html.map(str::to_string)
+    }
+
+    fn get_files(&mut self) -> Option<Vec<String>> {
+        self.current.files.as_ref().map(|files| files.iter().map(|f| f.to_string()).collect())
+    }
+
+    fn get_formats(&mut self) -> Vec<(String, Vec<u8>)> {
+        self.current.formats.iter().map(|(mime, data)| (mime.to_string(), data.to_vec())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn debounce_waits_for_the_selection_to_settle() {
+        let dragging = Scripted { text: vec!["he", "hell", "hello", "hello wor", "hello world"], ..Default::default() };
+        let mut source = Debounced::new(ScriptedSource::new(vec![(ClipboardChange::Changed, dragging)]), Duration::from_millis(5));
+
+        let started = Instant::now();
+        assert_eq!(source.wait_for_change(), ClipboardChange::Changed);
+        assert_eq!(source.get_text().as_deref(), Some("hello world"));
+        // One read before the first delay, then one per delay until two reads agree
+        assert_eq!(source.inner.text_reads, 7);
+        assert!(started.elapsed() >= Duration::from_millis(25));
+    }
+
+    #[test]
+    fn debounce_returns_after_one_delay_when_nothing_changes() {
+        let mut source = Debounced::new(ScriptedSource::new(vec![(ClipboardChange::Poll, Scripted::text("done"))]), Duration::from_millis(1));
+        assert_eq!(source.wait_for_change(), ClipboardChange::Poll);
+        assert_eq!(source.inner.text_reads, 2);
+    }
+
+    #[test]
+    fn format_budget_caps_each_format_and_the_clip() {
+        let mut budget = FormatBudget::default();
+        budget.add("text/plain".to_string(), Vec::new());
+        budget.add("image/png".to_string(), vec![0; MAX_FORMAT_BYTES + 1]);
+        budget.add("image/bmp".to_string(), vec![0; MAX_FORMAT_BYTES]);
+        budget.add("image/tiff".to_string(), vec![0; MAX_CLIP_FORMAT_BYTES - MAX_FORMAT_BYTES + 1]);
+        budget.add("text/html".to_string(), b"<b>hi</b>".to_vec());
+        assert!(!budget.is_full());
+        let kept: Vec<String> = budget.into_formats().into_iter().map(|(mime, _)| mime).collect();
+        assert_eq!(kept, vec!["image/bmp", "text/html"]);
+    }
+}
@@ -2,59 +2,70 @@ use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
 };
-use argon2::{
-    password_hash::{rand_core::OsRng, SaltString},
-    Argon2,
-};
+use argon2::{Algorithm, Argon2, Params, Version};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub struct EncryptedData {
-    pub ciphertext: Vec<u8>,
-    pub nonce: Vec<u8>,
-    pub salt: String,
+pub const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// Argon2id cost, stored next to the salt so it can be raised later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
 }
 
-pub fn derive_key(password: &str, salt: &SaltString) -> [u8; 32] {
-    let argon2 = Argon2::default();
-    let mut key = [0u8; 32];
-    let _ = argon2.hash_password_into(password.as_bytes(), salt.as_str().as_bytes(), &mut key);
-    key
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
 }
 
-pub fn encrypt(data: &[u8], password: &str) -> Result<EncryptedData, String> {
-    let salt = SaltString::generate(&mut OsRng);
-    let key_bytes = derive_key(password, &salt);
-    let key = aes_gcm::Key::<Aes256Gcm>::from_slice(&key_bytes);
-    let cipher = Aes256Gcm::new(key);
+pub fn derive_key(password: &str, salt: &[u8], params: &KdfParams) -> Result<[u8; KEY_LEN], String> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KEY_LEN)).map_err(|e| e.to_string())?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    let mut key = [0u8; KEY_LEN];
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
 
-    let mut nonce_bytes = [0u8; 12];
-    getrandom::getrandom(&mut nonce_bytes).map_err(|e| e.to_string())?;
+pub fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
 
-    let nonce = Nonce::from_slice(&nonce_bytes);
+/// AES-256-GCM with a random nonce, returned as nonce followed by ciphertext
+pub fn encrypt(data: &[u8], key: &[u8; KEY_LEN]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(key));
+    let nonce_bytes = random_bytes::<NONCE_LEN>()?;
 
     let ciphertext = cipher
-        .encrypt(nonce, data)
+        .encrypt(Nonce::from_slice(&nonce_bytes), data)
         .map_err(|e| format!("Encryption failed: {}", e))?;
 
-    Ok(EncryptedData {
-        ciphertext,
-        nonce: nonce_bytes.to_vec(),
-        salt: salt.to_string(),
-    })
+    let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(&nonce_bytes);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
 }
 
-pub fn decrypt(encrypted: &EncryptedData, password: &str) -> Result<Vec<u8>, String> {
-    let salt = SaltString::from_b64(&encrypted.salt).map_err(|e| e.to_string())?;
-    let key_bytes = derive_key(password, &salt);
-    let key = aes_gcm::Key::<Aes256Gcm>::from_slice(&key_bytes);
-    let cipher = Aes256Gcm::new(key);
-
-    let nonce = Nonce::from_slice(&encrypted.nonce);
-
-    let plaintext = cipher
-        .decrypt(nonce, encrypted.ciphertext.as_ref())
-        .map_err(|e| format!("Decryption failed (is the password correct?): {}", e))?;
+/// Fails when the key is wrong or the data was modified
+pub fn decrypt(sealed: &[u8], key: &[u8; KEY_LEN]) -> Result<Vec<u8>, String> {
+    if sealed.len() < NONCE_LEN {
+        return Err("Decryption failed: data too short".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(key));
 
-    Ok(plaintext)
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Decryption failed: wrong key or corrupted data".to_string())
 }
@@ -1,9 +1,13 @@
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite, Row};
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite, SqliteConnection, Row};
 use std::fs;
+use std::path::Path;
 use tauri::AppHandle;
 use tauri::Manager;
+use crate::search::ClipQuery;
+use crate::detectors::Detection;
+use crate::app_policy::AppPolicyRule;
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
 pub struct Clip {
     pub id: i64,
     pub content: String,
@@ -18,6 +22,33 @@ pub struct Clip {
     pub sender_app: Option<String>,
     pub sensitive: bool,
     pub position: Option<i64>,
+    /// "clipboard" or "primary"
+    #[sqlx(default)]
+    pub origin: String,
+    /// Small preview of image clips, unset when the image is small itself
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    /// Name of the detector that flagged the clip sensitive
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensitive_detector: Option<String>,
+    /// How sure that detector was, 0 to 1
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensitive_confidence: Option<f64>,
+    /// Whether a privacy rule masked the clip and kept the original encrypted
+    #[sqlx(default)]
+    #[serde(default)]
+    pub has_original: bool,
+    /// Text recognized in an image clip
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ocr_text: Option<String>,
+    /// Match excerpt with `<mark>` around hits, only set for search results
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
@@ -35,11 +66,88 @@ pub struct DbState {
     pub pool: Pool<Sqlite>,
 }
 
+// Vault mode: clip content and tags, clip formats and snippet bodies are encrypted on the way in
+// and decrypted on the way out. Plain values from before vault mode pass through.
+pub(crate) fn seal(text: String) -> Result<String, sqlx::Error> {
+    crate::vault::encrypt_text(&text).map_err(|e| sqlx::Error::Encode(e.into()))
+}
+
+pub(crate) fn seal_opt(text: Option<String>) -> Result<Option<String>, sqlx::Error> {
+    text.map(seal).transpose()
+}
+
+pub(crate) fn unseal(text: String) -> Result<String, sqlx::Error> {
+    crate::vault::decrypt_text(&text).map_err(|e| sqlx::Error::Decode(e.into()))
+}
+
+pub(crate) fn seal_bytes(data: Vec<u8>) -> Result<Vec<u8>, sqlx::Error> {
+    crate::vault::encrypt_bytes(&data).map_err(|e| sqlx::Error::Encode(e.into()))
+}
+
+pub(crate) fn unseal_bytes(data: Vec<u8>) -> Result<Vec<u8>, sqlx::Error> {
+    crate::vault::decrypt_bytes(&data).map_err(|e| sqlx::Error::Decode(e.into()))
+}
+
+/// The hash to store for a clip's content hash, keyed in vault mode
+pub(crate) fn clip_hash(hash: &str) -> Result<String, sqlx::Error> {
+    crate::vault::clip_hash(hash).map_err(|e| sqlx::Error::Encode(e.into()))
+}
+
+/// Store a clip's content hash for sync, which knows clips by it, when vault mode keyed the stored one
+pub(crate) async fn remember_sync_key(conn: &mut SqliteConnection, stored: &str, hash: &str) -> Result<(), sqlx::Error> {
+    if stored != hash {
+        sqlx::query("INSERT OR REPLACE INTO sync_keys (hash, sync_key) VALUES (?, ?)")
+            .bind(stored)
+            .bind(seal(hash.to_string())?)
+            .execute(conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Image clips keep the path of their blob in `content`, it isn't encrypted
+pub(crate) fn seal_clip_content(type_: &str, content: String) -> Result<String, sqlx::Error> {
+    if type_ == "image" { Ok(content) } else { seal(content) }
+}
+
+pub(crate) fn unseal_clip(clip: Clip) -> Result<Clip, sqlx::Error> {
+    Ok(Clip {
+        content: unseal(clip.content)?,
+        tags: clip.tags.map(unseal).transpose()?,
+        ocr_text: clip.ocr_text.map(unseal).transpose()?,
+        ..clip
+    })
+}
+
+fn unseal_snippet(snippet: Snippet) -> Result<Snippet, sqlx::Error> {
+    Ok(Snippet {
+        content: unseal(snippet.content)?,
+        version_history: unseal(snippet.version_history)?,
+        ..snippet
+    })
+}
+
 
 pub async fn init_db(app_handle: &AppHandle) -> Result<Pool<Sqlite>, Box<dyn std::error::Error>> {
     let app_dir = app_handle.path().app_data_dir()?;
+    let pool = open_db(&app_dir).await?;
+
+    // Report anything the migrations didn't produce instead of running on a half-upgraded schema unnoticed
+    if let Err(e) = crate::schema::check_drift(&pool).await {
+        log::error!("Failed to check database schema: {}", e);
+    }
+
+    if let Err(e) = crate::blob_store::adopt_legacy(&pool, &app_dir).await {
+        log::error!("Failed to move images into the blob store: {}", e);
+    }
+
+    Ok(pool)
+}
+
+/// Open (creating it if needed) and migrate the database in `app_dir`. Also used by the CLI, without the app.
+pub async fn open_db(app_dir: &Path) -> Result<Pool<Sqlite>, Box<dyn std::error::Error>> {
     if !app_dir.exists() {
-        fs::create_dir_all(&app_dir)?;
+        fs::create_dir_all(app_dir)?;
     }
     let db_path = app_dir.join("reclip.db");
     let db_url = format!("sqlite://{}", db_path.to_string_lossy());
@@ -54,75 +162,10 @@ pub async fn init_db(app_handle: &AppHandle) -> Result<Pool<Sqlite>, Box<dyn std
         .connect(&db_url)
         .await?;
 
-    sqlx::migrate!("./migrations")
-        .run(&pool)
-        .await?;
-
-    // Create regex_rules table if not exists (migrating manually for robustness in this step)
-    sqlx::query("CREATE TABLE IF NOT EXISTS regex_rules (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        pattern TEXT NOT NULL,
-        action_type TEXT NOT NULL,
-        action_payload TEXT NOT NULL,
-        enabled BOOLEAN NOT NULL DEFAULT 1,
-        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-    )").execute(&pool).await?;
-
-    // Create notes table
-    sqlx::query("CREATE TABLE IF NOT EXISTS notes (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        title TEXT DEFAULT '',
-        content TEXT NOT NULL,
-        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-    )").execute(&pool).await?;
-
-    // Migration: Add title column if it doesn't exist (for existing users)
-    // We ignore the error if column already exists
-    let _ = sqlx::query("ALTER TABLE notes ADD COLUMN title TEXT DEFAULT ''").execute(&pool).await;
-
-    // Migration: Add is_pinned, color, is_archived columns
-    let _ = sqlx::query("ALTER TABLE notes ADD COLUMN is_pinned BOOLEAN NOT NULL DEFAULT 0").execute(&pool).await;
-    let _ = sqlx::query("ALTER TABLE notes ADD COLUMN color TEXT DEFAULT NULL").execute(&pool).await;
-    let _ = sqlx::query("ALTER TABLE notes ADD COLUMN is_archived BOOLEAN NOT NULL DEFAULT 0").execute(&pool).await;
-
-    // Create reminders table
-    sqlx::query("CREATE TABLE IF NOT EXISTS reminders (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        content TEXT NOT NULL,
-        due_date DATETIME,
-        completed BOOLEAN NOT NULL DEFAULT 0,
-        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-    )").execute(&pool).await?;
-
-    // Create alarms table
-    sqlx::query("CREATE TABLE IF NOT EXISTS alarms (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        time TEXT NOT NULL,
-        label TEXT NOT NULL,
-        active BOOLEAN NOT NULL DEFAULT 1,
-        days TEXT DEFAULT '',
-        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-    )").execute(&pool).await?;
-
-    // Migration: Add tags and position columns to notes/reminders/alarms if needed
-    let _ = sqlx::query("ALTER TABLE notes ADD COLUMN tags TEXT DEFAULT ''").execute(&pool).await;
-    let _ = sqlx::query("ALTER TABLE notes ADD COLUMN position INTEGER DEFAULT 0").execute(&pool).await;
-    let _ = sqlx::query("ALTER TABLE reminders ADD COLUMN position INTEGER DEFAULT 0").execute(&pool).await;
-    let _ = sqlx::query("ALTER TABLE alarms ADD COLUMN position INTEGER DEFAULT 0").execute(&pool).await;
-
-    // Create workflows table for automation
-    sqlx::query("CREATE TABLE IF NOT EXISTS workflows (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        name TEXT NOT NULL,
-        trigger_type TEXT NOT NULL DEFAULT 'content_match',
-        trigger_pattern TEXT NOT NULL DEFAULT '',
-        action_type TEXT NOT NULL DEFAULT 'tag',
-        action_value TEXT NOT NULL DEFAULT '',
-        enabled BOOLEAN NOT NULL DEFAULT 1,
-        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-    )").execute(&pool).await?;
-
+    // Older versions created some tables by hand, give them the columns the migrations expect
+    crate::schema::upgrade_legacy_tables(&pool).await?;
+    crate::schema::MIGRATOR.run(&pool).await?;
+    crate::vault::init(&pool).await;
     Ok(pool)
 }
 
@@ -371,19 +414,27 @@ pub async fn get_active_alarms(pool: &Pool<Sqlite>) -> Result<Vec<Alarm>, sqlx::
 }
 
 pub async fn insert_clip(pool: &Pool<Sqlite>, content: String, type_: String, hash: String, tags: Option<String>, sender_app: Option<String>) -> Result<i64, sqlx::Error> {
-    insert_clip_with_sensitive(pool, content, type_, hash, tags, false, sender_app).await
+    insert_clip_with_sensitive(pool, content, type_, hash, tags, None, sender_app, "clipboard").await
 }
 
-pub async fn insert_clip_with_sensitive(pool: &Pool<Sqlite>, content: String, type_: String, hash: String, tags: Option<String>, sensitive: bool, sender_app: Option<String>) -> Result<i64, sqlx::Error> {
-    let id = sqlx::query("INSERT INTO clips (content, type, hash, tags, sensitive, sender_app) VALUES (?, ?, ?, ?, ?, ?) 
-        ON CONFLICT(hash) DO UPDATE SET created_at = CURRENT_TIMESTAMP, sender_app = COALESCE(excluded.sender_app, clips.sender_app)
+/// Insert a clip, flagged sensitive when a detector fired on it
+pub async fn insert_clip_with_sensitive(pool: &Pool<Sqlite>, content: String, type_: String, hash: String, tags: Option<String>, detection: Option<&Detection>, sender_app: Option<String>, origin: &str) -> Result<i64, sqlx::Error> {
+    let stored_hash = clip_hash(&hash)?;
+    remember_sync_key(&mut *pool.acquire().await?, &stored_hash, &hash).await?;
+    // A clip explicitly copied to the clipboard stays a clipboard clip even if it is selected again later
+    let id = sqlx::query("INSERT INTO clips (content, type, hash, tags, sensitive, sensitive_detector, sensitive_confidence, sender_app, origin) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) 
+        ON CONFLICT(hash) DO UPDATE SET created_at = CURRENT_TIMESTAMP, sender_app = COALESCE(excluded.sender_app, clips.sender_app),
+            origin = CASE WHEN excluded.origin = 'clipboard' THEN 'clipboard' ELSE clips.origin END
         RETURNING id")
-        .bind(content)
+        .bind(seal_clip_content(&type_, content)?)
         .bind(type_)
-        .bind(hash)
-        .bind(tags)
-        .bind(sensitive)
+        .bind(stored_hash)
+        .bind(seal_opt(tags)?)
+        .bind(detection.is_some())
+        .bind(detection.map(|d| d.detector.as_str()))
+        .bind(detection.map(|d| d.confidence))
         .bind(sender_app)
+        .bind(origin)
         .fetch_one(pool)
         .await?
         .get::<i64, _>(0);
@@ -391,36 +442,182 @@ pub async fn insert_clip_with_sensitive(pool: &Pool<Sqlite>, content: String, ty
     Ok(id)
 }
 
+/// One representation the clipboard offered for a clip. Large payloads live in the blob store (`path`).
+#[derive(Debug, Clone, serde::Serialize, sqlx::FromRow)]
+pub struct ClipFormat {
+    pub id: i64,
+    pub clip_id: i64,
+    pub mime: String,
+    #[serde(skip)]
+    pub data: Option<Vec<u8>>,
+    pub path: Option<String>,
+    pub size: i64,
+}
+
+pub async fn clear_clip_formats(pool: &Pool<Sqlite>, clip_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM clip_formats WHERE clip_id = ?")
+        .bind(clip_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn insert_clip_format(pool: &Pool<Sqlite>, clip_id: i64, mime: &str, data: Option<Vec<u8>>, blob: Option<&crate::blob_store::Blob>, size: i64) -> Result<(), sqlx::Error> {
+    // An upsert rather than INSERT OR REPLACE, replace deletes don't fire the blob refcount triggers
+    sqlx::query("INSERT INTO clip_formats (clip_id, mime, data, path, blob_hash, size) VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(clip_id, mime) DO UPDATE SET data = excluded.data, path = excluded.path, blob_hash = excluded.blob_hash, size = excluded.size")
+        .bind(clip_id)
+        .bind(mime)
+        .bind(data.map(seal_bytes).transpose()?)
+        .bind(blob.map(|b| b.path.as_str()))
+        .bind(blob.map(|b| b.hash.as_str()))
+        .bind(size)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Formats of a clip in the order they were offered
+pub async fn get_clip_formats(pool: &Pool<Sqlite>, clip_id: i64) -> Result<Vec<ClipFormat>, sqlx::Error> {
+    sqlx::query_as::<_, ClipFormat>("SELECT id, clip_id, mime, data, path, size FROM clip_formats WHERE clip_id = ? ORDER BY id")
+        .bind(clip_id)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|format| Ok(ClipFormat { data: format.data.map(unseal_bytes).transpose()?, ..format }))
+        .collect()
+}
+
+/// Store or clear the encrypted original of a masked clip
+pub async fn set_clip_redacted_original(pool: &Pool<Sqlite>, id: i64, sealed: Option<String>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE clips SET redacted_original = ? WHERE id = ?")
+        .bind(sealed)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_clip_redacted_original(pool: &Pool<Sqlite>, id: i64) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT redacted_original FROM clips WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map(Option::flatten)
+}
+
+/// Point a clip at the blob holding its image
+pub async fn set_clip_blob(pool: &Pool<Sqlite>, id: i64, blob_hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE clips SET blob_hash = ? WHERE id = ?")
+        .bind(blob_hash)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Attach the text recognized in an image clip, searchable but never pasted
+pub async fn set_clip_ocr_text(pool: &Pool<Sqlite>, id: i64, text: String) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE clips SET ocr_text = ? WHERE id = ?")
+        .bind(seal(text)?)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_clip_id_by_hash(pool: &Pool<Sqlite>, hash: &str) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM clips WHERE hash = ?")
+        .bind(clip_hash(hash)?)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Insert a clip from an export, keeping its timestamps and flags
+pub async fn import_clip(pool: &Pool<Sqlite>, clip: &Clip) -> Result<i64, sqlx::Error> {
+    let stored_hash = clip_hash(&clip.hash)?;
+    remember_sync_key(&mut *pool.acquire().await?, &stored_hash, &clip.hash).await?;
+    let id = sqlx::query("INSERT INTO clips (content, type, hash, created_at, pinned, favorite, tags, sender_app, origin) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id")
+        .bind(seal_clip_content(&clip.type_, clip.content.clone())?)
+        .bind(&clip.type_)
+        .bind(&stored_hash)
+        .bind(&clip.created_at)
+        .bind(clip.pinned)
+        .bind(clip.favorite)
+        .bind(seal_opt(clip.tags.clone())?)
+        .bind(&clip.sender_app)
+        .bind(&clip.origin)
+        .fetch_one(pool)
+        .await?
+        .get::<i64, _>(0);
+    Ok(id)
+}
+
 pub async fn update_clip_content(pool: &Pool<Sqlite>, id: i64, content: String) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
     sqlx::query("UPDATE clips SET content = ? WHERE id = ?")
-        .bind(content)
+        .bind(seal(content)?)
         .bind(id)
-        .execute(pool)
+        .execute(&mut *tx)
+        .await?;
+    // The stored formats hold what was copied, pasting them would undo the edit
+    sqlx::query("DELETE FROM clip_formats WHERE clip_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
         .await?;
+    tx.commit().await?;
     Ok(())
 }
 
-pub async fn get_clips(pool: &Pool<Sqlite>, limit: i64, offset: i64, search: Option<String>, type_filter: Option<String>, favorites_only: bool) -> Result<Vec<Clip>, sqlx::Error> {
+/// Column list for `Clip` rows, qualified with the `c` alias used by search joins
+const CLIP_COLUMNS: &str = "c.id, c.content, c.type, c.hash, c.created_at, c.pinned, c.favorite, c.tags, c.sender_app, c.sensitive, c.position, c.origin,
+    c.sensitive_detector, c.sensitive_confidence, c.redacted_original IS NOT NULL AS has_original, c.ocr_text, (SELECT b.thumb_path FROM blobs b WHERE b.hash = c.blob_hash) AS thumbnail";
+
+pub async fn get_clip(pool: &Pool<Sqlite>, id: i64) -> Result<Option<Clip>, sqlx::Error> {
+    sqlx::query_as::<_, Clip>(&format!("SELECT {} FROM clips c WHERE c.id = ?", CLIP_COLUMNS))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .map(unseal_clip)
+        .transpose()
+}
+
+pub async fn get_clips(pool: &Pool<Sqlite>, limit: i64, offset: i64, query: Option<&ClipQuery>, type_filter: Option<String>, favorites_only: bool, include_primary: bool) -> Result<Vec<Clip>, sqlx::Error> {
+    if crate::vault::is_enabled() && (query.is_some() || type_filter.is_some()) {
+        let clips = get_matching_clips(pool, query, type_filter.as_deref()).await?
+            .into_iter()
+            .filter(|c| (!favorites_only || c.favorite) && (include_primary || c.origin != "primary"))
+            .skip(offset.max(0) as usize);
+        return Ok(if limit < 0 { clips.collect() } else { clips.take(limit as usize).collect() });
+    }
+
     let mut conditions = Vec::new();
     let mut bind_values: Vec<String> = Vec::new();
     
-    if let Some(ref term) = search {
-        conditions.push(format!("(content LIKE '%{}%' OR tags LIKE '%{}%')", term, term));
+    let match_expr = query.and_then(|q| q.text.clone());
+    if let Some(ref expr) = match_expr {
+        conditions.push("clips_fts MATCH ?".to_string());
+        bind_values.push(expr.clone());
+    }
+
+    if let Some(q) = query {
+        let (filter_conditions, filter_binds) = q.sql_conditions();
+        conditions.extend(filter_conditions);
+        bind_values.extend(filter_binds);
     }
     
     if let Some(ref tf) = type_filter {
-        if tf == "links" {
-            conditions.push("type = 'text' AND (content LIKE 'http://%' OR content LIKE 'https://%')".to_string());
-        } else if tf == "colors" {
-            conditions.push("type = 'text' AND (content LIKE '#%' OR content LIKE 'rgb(%' OR content LIKE 'hsl(%')".to_string());
-        } else {
-            conditions.push("type = ?".to_string());
-            bind_values.push(tf.clone());
-        }
+        let (condition, bind) = crate::search::type_condition(tf);
+        conditions.push(condition);
+        bind_values.extend(bind);
     }
     
     if favorites_only {
-        conditions.push("favorite = 1".to_string());
+        conditions.push("c.favorite = 1".to_string());
+    }
+
+    if !include_primary {
+        conditions.push("c.origin != 'primary'".to_string());
     }
     
     let where_clause = if conditions.is_empty() {
@@ -429,10 +626,18 @@ pub async fn get_clips(pool: &Pool<Sqlite>, limit: i64, offset: i64, search: Opt
         format!("WHERE {}", conditions.join(" AND "))
     };
     
-    let query_str = format!(
-        "SELECT id, content, type, hash, created_at, pinned, favorite, tags, sender_app, sensitive, position FROM clips {} ORDER BY favorite DESC, pinned DESC, COALESCE(position, 0) DESC, created_at DESC LIMIT ? OFFSET ?",
-        where_clause
-    );
+    let query_str = if match_expr.is_some() {
+        // Ranked search: BM25 with content weighted above recognized text and tags
+        format!(
+            "SELECT {}, snippet(clips_fts, -1, '<mark>', '</mark>', '…', 16) AS highlight FROM clips_fts JOIN clips c ON c.id = clips_fts.rowid {} ORDER BY bm25(clips_fts, 10.0, 4.0, 6.0), c.created_at DESC LIMIT ? OFFSET ?",
+            CLIP_COLUMNS, where_clause
+        )
+    } else {
+        format!(
+            "SELECT {} FROM clips c {} ORDER BY c.favorite DESC, c.pinned DESC, COALESCE(c.position, 0) DESC, c.created_at DESC LIMIT ? OFFSET ?",
+            CLIP_COLUMNS, where_clause
+        )
+    };
 
     let mut query = sqlx::query_as::<_, Clip>(&query_str);
     
@@ -442,8 +647,27 @@ pub async fn get_clips(pool: &Pool<Sqlite>, limit: i64, offset: i64, search: Opt
     
     query = query.bind(limit).bind(offset);
     
-    let clips = query.fetch_all(pool).await?;
-    Ok(clips)
+    query.fetch_all(pool).await?.into_iter().map(unseal_clip).collect()
+}
+
+/// Vault mode: neither SQL nor the search index can see encrypted text, so every clip is
+/// decrypted and matched here. Results come in the default order, not ranked.
+async fn get_matching_clips(pool: &Pool<Sqlite>, query: Option<&ClipQuery>, type_filter: Option<&str>) -> Result<Vec<Clip>, sqlx::Error> {
+    let clips = sqlx::query_as::<_, Clip>(&format!(
+        "SELECT {} FROM clips c ORDER BY c.favorite DESC, c.pinned DESC, COALESCE(c.position, 0) DESC, c.created_at DESC",
+        CLIP_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut matching = Vec::new();
+    for clip in clips {
+        let clip = unseal_clip(clip)?;
+        if query.is_none_or(|q| q.matches(&clip)) && type_filter.is_none_or(|t| crate::search::type_matches(t, &clip)) {
+            matching.push(clip);
+        }
+    }
+    Ok(matching)
 }
 
 #[derive(Debug, serde::Serialize, sqlx::FromRow)]
@@ -467,79 +691,124 @@ pub struct GlobalSearchResult {
     pub module: String,
     pub title: String,
     pub preview: String,
+    /// Match excerpt with `<mark>` around hits
+    pub highlight: String,
+    /// Rank within its module, 0 for the best match to 1 for the weakest
+    pub score: f64,
     pub created_at: String,
 }
 
+/// BM25 scores of different FTS tables aren't comparable, replace a module's by their rank so they merge evenly
+fn rank_scores(results: &mut [GlobalSearchResult]) {
+    let last = results.len().saturating_sub(1).max(1) as f64;
+    for (i, result) in results.iter_mut().enumerate() {
+        result.score = i as f64 / last;
+    }
+}
+
 pub async fn global_search(pool: &Pool<Sqlite>, term: &str, limit: i64) -> Result<Vec<GlobalSearchResult>, sqlx::Error> {
     let mut results = Vec::new();
-    let search_pattern = format!("%{}%", term);
+    let match_expr = match crate::search::fts_match_expression(term) {
+        Some(expr) => expr,
+        None => return Ok(results),
+    };
     
     // Search clips
-    let clip_rows = sqlx::query(
-        "SELECT id, content, type, created_at FROM clips WHERE content LIKE ?1 OR tags LIKE ?1 ORDER BY created_at DESC LIMIT ?2"
-    )
-    .bind(&search_pattern)
-    .bind(limit)
-    .fetch_all(pool)
-    .await?;
+    if crate::vault::is_enabled() {
+        let query = crate::search::parse_clip_query(term).unwrap_or_default();
+        for clip in get_matching_clips(pool, Some(&query), None).await?.into_iter().take(limit.max(0) as usize) {
+            let preview = crate::search::preview_text(&clip.content, 100);
+            results.push(GlobalSearchResult {
+                id: clip.id,
+                module: "clip".to_string(),
+                title: format!("{} clip", clip.type_),
+                highlight: preview.clone(),
+                preview,
+                score: 0.0,
+                created_at: clip.created_at,
+            });
+        }
+    } else {
+        let clip_rows = sqlx::query(
+            "SELECT c.id, c.content, c.type, c.created_at, snippet(clips_fts, -1, '<mark>', '</mark>', '…', 12) AS highlight, bm25(clips_fts, 10.0, 4.0, 6.0) AS score
+             FROM clips_fts JOIN clips c ON c.id = clips_fts.rowid WHERE clips_fts MATCH ?1 ORDER BY score LIMIT ?2"
+        )
+        .bind(&match_expr)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
     
-    for row in clip_rows {
-        let content: String = row.get("content");
-        let clip_type: String = row.get("type");
-        let preview = if content.len() > 100 { format!("{}…", &content[..97]) } else { content.clone() };
-        results.push(GlobalSearchResult {
-            id: row.get("id"),
-            module: "clip".to_string(),
-            title: format!("{} clip", clip_type),
-            preview,
-            created_at: row.get("created_at"),
-        });
+        for row in clip_rows {
+            let content = unseal(row.get("content"))?;
+            let clip_type: String = row.get("type");
+            results.push(GlobalSearchResult {
+                id: row.get("id"),
+                module: "clip".to_string(),
+                title: format!("{} clip", clip_type),
+                preview: crate::search::preview_text(&content, 100),
+                highlight: row.get("highlight"),
+                score: row.get("score"),
+                created_at: row.get("created_at"),
+            });
+        }
     }
     
+    rank_scores(&mut results);
+    
     // Search snippets
+    let module_start = results.len();
     let snippet_rows = sqlx::query(
-        "SELECT id, title, content, created_at FROM snippets WHERE title LIKE ?1 OR content LIKE ?1 ORDER BY created_at DESC LIMIT ?2"
+        "SELECT s.id, s.title, s.content, s.created_at, snippet(snippets_fts, -1, '<mark>', '</mark>', '…', 12) AS highlight, bm25(snippets_fts, 10.0, 5.0, 3.0, 2.0) AS score
+         FROM snippets_fts JOIN snippets s ON s.id = snippets_fts.rowid WHERE snippets_fts MATCH ?1 ORDER BY score LIMIT ?2"
     )
-    .bind(&search_pattern)
+    .bind(&match_expr)
     .bind(limit)
     .fetch_all(pool)
     .await?;
     
     for row in snippet_rows {
-        let content: String = row.get("content");
-        let preview = if content.len() > 100 { format!("{}…", &content[..97]) } else { content.clone() };
+        let content = unseal(row.get("content"))?;
         results.push(GlobalSearchResult {
             id: row.get("id"),
             module: "snippet".to_string(),
             title: row.get("title"),
-            preview,
+            preview: crate::search::preview_text(&content, 100),
+            highlight: row.get("highlight"),
+            score: row.get("score"),
             created_at: row.get("created_at"),
         });
     }
     
+    rank_scores(&mut results[module_start..]);
+    
     // Search notes
+    let module_start = results.len();
     let note_rows = sqlx::query(
-        "SELECT id, title, content, created_at FROM notes WHERE title LIKE ?1 OR content LIKE ?1 ORDER BY created_at DESC LIMIT ?2"
+        "SELECT n.id, COALESCE(n.title, '') AS title, n.content, n.created_at, snippet(notes_fts, -1, '<mark>', '</mark>', '…', 12) AS highlight, bm25(notes_fts, 10.0, 5.0, 3.0) AS score
+         FROM notes_fts JOIN notes n ON n.id = notes_fts.rowid WHERE notes_fts MATCH ?1 ORDER BY score LIMIT ?2"
     )
-    .bind(&search_pattern)
+    .bind(&match_expr)
     .bind(limit)
     .fetch_all(pool)
     .await?;
     
     for row in note_rows {
         let content: String = row.get("content");
-        let preview = if content.len() > 100 { format!("{}…", &content[..97]) } else { content.clone() };
         results.push(GlobalSearchResult {
             id: row.get("id"),
             module: "note".to_string(),
             title: row.get("title"),
-            preview,
+            preview: crate::search::preview_text(&content, 100),
+            highlight: row.get("highlight"),
+            score: row.get("score"),
             created_at: row.get("created_at"),
         });
     }
     
-    // Sort by created_at descending
-    results.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    rank_scores(&mut results[module_start..]);
+    
+    // Best matches first across all modules
+    results.sort_by(|a, b| a.score.total_cmp(&b.score));
     results.truncate(limit as usize);
     
     Ok(results)
@@ -552,31 +821,48 @@ pub struct ClipStats {
     pub newest_date: Option<String>,
 }
 
-pub async fn get_clip_stats(pool: &Pool<Sqlite>, search: Option<String>) -> Result<ClipStats, sqlx::Error> {
-    let (count_query, date_query) = if let Some(ref term) = search {
-        (
-            format!("SELECT COUNT(*) as count FROM clips WHERE content LIKE '%{}%' OR tags LIKE '%{}%'", term, term),
-            format!("SELECT MIN(created_at) as oldest, MAX(created_at) as newest FROM clips WHERE content LIKE '%{}%' OR tags LIKE '%{}%'", term, term)
-        )
+pub async fn get_clip_stats(pool: &Pool<Sqlite>, query: Option<&ClipQuery>) -> Result<ClipStats, sqlx::Error> {
+    if crate::vault::is_enabled() && query.is_some() {
+        let clips = get_matching_clips(pool, query, None).await?;
+        return Ok(ClipStats {
+            total_count: clips.len() as i64,
+            oldest_date: clips.iter().map(|c| c.created_at.clone()).min(),
+            newest_date: clips.iter().map(|c| c.created_at.clone()).max(),
+        });
+    }
+
+    let mut conditions = Vec::new();
+    let mut bind_values: Vec<String> = Vec::new();
+    let mut from_clause = "clips c";
+
+    if let Some(q) = query {
+        if let Some(ref expr) = q.text {
+            from_clause = "clips_fts JOIN clips c ON c.id = clips_fts.rowid";
+            conditions.push("clips_fts MATCH ?".to_string());
+            bind_values.push(expr.clone());
+        }
+        let (filter_conditions, filter_binds) = q.sql_conditions();
+        conditions.extend(filter_conditions);
+        bind_values.extend(filter_binds);
+    }
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
     } else {
-        (
-            "SELECT COUNT(*) as count FROM clips".to_string(),
-            "SELECT MIN(created_at) as oldest, MAX(created_at) as newest FROM clips".to_string()
-        )
+        format!("WHERE {}", conditions.join(" AND "))
     };
+    let query_str = format!("SELECT COUNT(*), MIN(c.created_at), MAX(c.created_at) FROM {} {}", from_clause, where_clause);
 
-    let count: i64 = sqlx::query_scalar(&count_query)
-        .fetch_one(pool)
-        .await?;
-
-    let dates: (Option<String>, Option<String>) = sqlx::query_as(&date_query)
-        .fetch_one(pool)
-        .await?;
+    let mut stats_query = sqlx::query_as::<_, (i64, Option<String>, Option<String>)>(&query_str);
+    for val in &bind_values {
+        stats_query = stats_query.bind(val);
+    }
+    let (count, oldest, newest) = stats_query.fetch_one(pool).await?;
 
     Ok(ClipStats {
         total_count: count,
-        oldest_date: dates.0,
-        newest_date: dates.1,
+        oldest_date: oldest,
+        newest_date: newest,
     })
 }
 
@@ -664,21 +950,28 @@ pub async fn delete_clip(pool: &Pool<Sqlite>, id: i64) -> Result<(), sqlx::Error
     Ok(())
 }
 
-pub async fn prune_clips(pool: &Pool<Sqlite>, days: i64, max_clips: i64) -> Result<(), sqlx::Error> {
+/// Returns the ids of the deleted clips
+pub async fn prune_clips(pool: &Pool<Sqlite>, days: i64, max_clips: i64) -> Result<Vec<i64>, sqlx::Error> {
+    // Retention is per device, pruned clips must not turn into sync tombstones
+    let mut tx = pool.begin().await?;
+    sqlx::query("INSERT INTO sync_paused (reason) VALUES ('prune')").execute(&mut *tx).await?;
+
     // 1. Delete clips older than X days, excluding pinned and favorites
     // Note: SQLite uses 'now', '-X days' syntax
-    let date_query = format!("DELETE FROM clips WHERE created_at < date('now', '-{} days') AND pinned = 0 AND favorite = 0", days);
-    sqlx::query(&date_query)
-        .execute(pool)
+    let date_query = format!("DELETE FROM clips WHERE created_at < date('now', '-{} days') AND pinned = 0 AND favorite = 0 RETURNING id", days);
+    let mut deleted: Vec<i64> = sqlx::query_scalar(&date_query)
+        .fetch_all(&mut *tx)
         .await?;
 
     // 2. Delete excess clips, keeping the newest 'max_clips' (excluding pinned/favs)
-    let count_query = format!("DELETE FROM clips WHERE id NOT IN (SELECT id FROM clips ORDER BY created_at DESC LIMIT {}) AND pinned = 0 AND favorite = 0", max_clips);
-    sqlx::query(&count_query)
-         .execute(pool)
-         .await?;
-         
-    Ok(())
+    let count_query = format!("DELETE FROM clips WHERE id NOT IN (SELECT id FROM clips ORDER BY created_at DESC LIMIT {}) AND pinned = 0 AND favorite = 0 RETURNING id", max_clips);
+    deleted.extend(sqlx::query_scalar::<_, i64>(&count_query)
+         .fetch_all(&mut *tx)
+         .await?);
+
+    sqlx::query("DELETE FROM sync_paused").execute(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(deleted)
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
@@ -726,7 +1019,7 @@ pub async fn update_template(pool: &Pool<Sqlite>, id: i64, name: &str, content:
 
 pub async fn update_clip_tags(pool: &Pool<Sqlite>, id: i64, tags: String) -> Result<(), sqlx::Error> {
     sqlx::query("UPDATE clips SET tags = ? WHERE id = ?")
-        .bind(tags)
+        .bind(seal(tags)?)
         .bind(id)
         .execute(pool)
         .await?;
@@ -748,6 +1041,24 @@ pub async fn toggle_pin(pool: &Pool<Sqlite>, id: i64) -> Result<bool, sqlx::Erro
     Ok(result.get::<bool, _>(0))
 }
 
+pub async fn set_clip_pinned(pool: &Pool<Sqlite>, id: i64, pinned: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE clips SET pinned = ? WHERE id = ?")
+        .bind(pinned)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_clip_favorite(pool: &Pool<Sqlite>, id: i64, favorite: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE clips SET favorite = ? WHERE id = ?")
+        .bind(favorite)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 pub async fn toggle_favorite(pool: &Pool<Sqlite>, id: i64) -> Result<bool, sqlx::Error> {
     // Toggle favorite status and return new value
     sqlx::query("UPDATE clips SET favorite = NOT favorite WHERE id = ?")
@@ -765,18 +1076,52 @@ pub async fn toggle_favorite(pool: &Pool<Sqlite>, id: i64) -> Result<bool, sqlx:
 
 
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
 pub struct PrivacyRule {
     pub id: i64,
-    pub rule_type: String,
+    pub rule_type: String, // "APP_IGNORE", "REGEX_MASK", "REGEX_DROP"
     pub value: String,
     pub is_active: bool,
+    /// Replacement for REGEX_MASK matches, `[REDACTED]` when unset
+    pub placeholder: Option<String>,
+    /// Keep the unmasked text encrypted so it can be revealed
+    pub keep_original: bool,
+    /// How APP_IGNORE and APP_POLICY rules match the focused window, see `app_policy`
+    pub match_kind: String,
+    /// APP_POLICY: "all", "no_images", "text_only" or "none"
+    pub capture: String,
+    pub mark_sensitive: bool,
+    /// Seconds until sensitive clips from the app are deleted
+    pub delete_after: Option<i64>,
+    /// JSON array added to the tags of every clip from the app
+    pub tags: Option<String>,
+    pub strip_formatting: bool,
 }
 
-pub async fn add_privacy_rule(pool: &Pool<Sqlite>, rule_type: String, value: String) -> Result<i64, sqlx::Error> {
-    let id = sqlx::query("INSERT INTO privacy_rules (rule_type, value) VALUES (?, ?) RETURNING id")
+pub async fn add_privacy_rule(pool: &Pool<Sqlite>, rule_type: String, value: String, placeholder: Option<String>, keep_original: bool) -> Result<i64, sqlx::Error> {
+    let id = sqlx::query("INSERT INTO privacy_rules (rule_type, value, placeholder, keep_original) VALUES (?, ?, ?, ?) RETURNING id")
         .bind(rule_type)
         .bind(value)
+        .bind(placeholder)
+        .bind(keep_original)
+        .fetch_one(pool)
+        .await?
+        .get::<i64, _>(0);
+    Ok(id)
+}
+
+pub async fn add_app_policy(pool: &Pool<Sqlite>, rule: &AppPolicyRule) -> Result<i64, sqlx::Error> {
+    let tags = (!rule.tags.is_empty()).then(|| serde_json::to_string(&rule.tags).unwrap_or_default());
+    let id = sqlx::query("INSERT INTO privacy_rules (rule_type, value, match_kind, capture, mark_sensitive, delete_after, tags, strip_formatting)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id")
+        .bind(crate::app_policy::APP_POLICY)
+        .bind(&rule.value)
+        .bind(&rule.match_kind)
+        .bind(rule.capture.as_str())
+        .bind(rule.mark_sensitive)
+        .bind(rule.delete_after)
+        .bind(tags)
+        .bind(rule.strip_formatting)
         .fetch_one(pool)
         .await?
         .get::<i64, _>(0);
@@ -798,6 +1143,50 @@ pub async fn get_privacy_rules(pool: &Pool<Sqlite>) -> Result<Vec<PrivacyRule>,
     Ok(rules)
 }
 
+/// A user-defined sensitive content detector, checked next to the built-in ones
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
+pub struct Detector {
+    pub id: i64,
+    pub name: String,
+    pub pattern: String,
+    pub confidence: f64,
+    pub enabled: bool,
+}
+
+pub async fn get_detectors(pool: &Pool<Sqlite>) -> Result<Vec<Detector>, sqlx::Error> {
+    sqlx::query_as::<_, Detector>("SELECT id, name, pattern, confidence, enabled FROM detectors ORDER BY id")
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn add_detector(pool: &Pool<Sqlite>, name: String, pattern: String, confidence: f64) -> Result<i64, sqlx::Error> {
+    let id = sqlx::query("INSERT INTO detectors (name, pattern, confidence) VALUES (?, ?, ?) RETURNING id")
+        .bind(name)
+        .bind(pattern)
+        .bind(confidence)
+        .fetch_one(pool)
+        .await?
+        .get::<i64, _>(0);
+    Ok(id)
+}
+
+pub async fn set_detector_enabled(pool: &Pool<Sqlite>, id: i64, enabled: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE detectors SET enabled = ? WHERE id = ?")
+        .bind(enabled)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_detector(pool: &Pool<Sqlite>, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM detectors WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 pub async fn get_setting(pool: &Pool<Sqlite>, key: &str) -> Option<String> {
     sqlx::query_scalar("SELECT value FROM settings WHERE key = ?")
         .bind(key)
@@ -816,10 +1205,10 @@ pub async fn set_setting(pool: &Pool<Sqlite>, key: &str, value: &str) -> Result<
     Ok(())
 }
 
-pub async fn delete_all_clips(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
-    sqlx::query("DELETE FROM clips").execute(pool).await?;
-    sqlx::query("DELETE FROM clip_fts").execute(pool).await?;
-    Ok(())
+/// Returns the ids of the deleted clips
+pub async fn delete_all_clips(pool: &Pool<Sqlite>) -> Result<Vec<i64>, sqlx::Error> {
+    // The search index is cleared by the clips_fts_ad trigger
+    sqlx::query_scalar("DELETE FROM clips RETURNING id").fetch_all(pool).await
 }
 
 pub async fn delete_all_snippets(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
@@ -827,15 +1216,14 @@ pub async fn delete_all_snippets(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error>
     Ok(())
 }
 
-/// Delete sensitive clips older than specified seconds
-pub async fn cleanup_sensitive_clips(pool: &Pool<Sqlite>, max_age_seconds: i64) -> Result<u64, sqlx::Error> {
-    let result = sqlx::query(
-        "DELETE FROM clips WHERE sensitive = 1 AND created_at < datetime('now', '-' || ? || ' seconds')"
+/// Delete sensitive clips older than specified seconds, returns their ids
+pub async fn cleanup_sensitive_clips(pool: &Pool<Sqlite>, max_age_seconds: i64) -> Result<Vec<i64>, sqlx::Error> {
+    sqlx::query_scalar(
+        "DELETE FROM clips WHERE sensitive = 1 AND created_at < datetime('now', '-' || ? || ' seconds') RETURNING id"
     )
         .bind(max_age_seconds)
-        .execute(pool)
-        .await?;
-    Ok(result.rows_affected())
+        .fetch_all(pool)
+        .await
 }
 
 /// Update clip position for drag-drop reordering
@@ -903,13 +1291,35 @@ pub struct Snippet {
 pub async fn get_snippets(pool: &Pool<Sqlite>) -> Result<Vec<Snippet>, sqlx::Error> {
     sqlx::query_as::<_, Snippet>("SELECT id, title, content, language, tags, COALESCE(favorite, 0) as favorite, COALESCE(folder, '') as folder, COALESCE(description, '') as description, COALESCE(version_history, '[]') as version_history, created_at, updated_at FROM snippets ORDER BY favorite DESC, updated_at DESC")
         .fetch_all(pool)
-        .await
+        .await?
+        .into_iter()
+        .map(unseal_snippet)
+        .collect()
+}
+
+pub async fn get_snippet(pool: &Pool<Sqlite>, id: i64) -> Result<Option<Snippet>, sqlx::Error> {
+    sqlx::query_as::<_, Snippet>("SELECT id, title, content, language, tags, COALESCE(favorite, 0) as favorite, COALESCE(folder, '') as folder, COALESCE(description, '') as description, COALESCE(version_history, '[]') as version_history, created_at, updated_at FROM snippets WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .map(unseal_snippet)
+        .transpose()
+}
+
+/// Case-insensitive lookup by title, for the CLI
+pub async fn get_snippet_by_title(pool: &Pool<Sqlite>, title: &str) -> Result<Option<Snippet>, sqlx::Error> {
+    sqlx::query_as::<_, Snippet>("SELECT id, title, content, language, tags, COALESCE(favorite, 0) as favorite, COALESCE(folder, '') as folder, COALESCE(description, '') as description, COALESCE(version_history, '[]') as version_history, created_at, updated_at FROM snippets WHERE title = ? COLLATE NOCASE ORDER BY updated_at DESC LIMIT 1")
+        .bind(title)
+        .fetch_optional(pool)
+        .await?
+        .map(unseal_snippet)
+        .transpose()
 }
 
 pub async fn add_snippet(pool: &Pool<Sqlite>, title: String, content: String, language: String, tags: String, description: String, folder: String) -> Result<i64, sqlx::Error> {
     let id = sqlx::query("INSERT INTO snippets (title, content, language, tags, description, folder, favorite, version_history, updated_at) VALUES (?, ?, ?, ?, ?, ?, 0, '[]', CURRENT_TIMESTAMP) RETURNING id")
         .bind(title)
-        .bind(content)
+        .bind(seal(content)?)
         .bind(language)
         .bind(tags)
         .bind(description)
@@ -928,6 +1338,7 @@ pub async fn update_snippet(pool: &Pool<Sqlite>, id: i64, title: String, content
         .await?;
     
     let new_history = if let Some((old_content, old_history)) = old {
+        let (old_content, old_history) = (unseal(old_content)?, unseal(old_history)?);
         if old_content != content {
             // Append old content to version history
             let mut history: Vec<serde_json::Value> = serde_json::from_str(&old_history).unwrap_or_default();
@@ -950,12 +1361,12 @@ pub async fn update_snippet(pool: &Pool<Sqlite>, id: i64, title: String, content
 
     sqlx::query("UPDATE snippets SET title = ?, content = ?, language = ?, tags = ?, description = ?, folder = ?, version_history = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
         .bind(title)
-        .bind(content)
+        .bind(seal(content)?)
         .bind(language)
         .bind(tags)
         .bind(description)
         .bind(folder)
-        .bind(new_history)
+        .bind(seal(new_history)?)
         .bind(id)
         .execute(pool)
         .await?;
@@ -977,10 +1388,7 @@ pub async fn toggle_snippet_favorite(pool: &Pool<Sqlite>, id: i64) -> Result<boo
 }
 
 pub async fn duplicate_snippet(pool: &Pool<Sqlite>, id: i64) -> Result<i64, sqlx::Error> {
-    let snippet: Snippet = sqlx::query_as("SELECT id, title, content, language, tags, COALESCE(favorite, 0) as favorite, COALESCE(folder, '') as folder, COALESCE(description, '') as description, COALESCE(version_history, '[]') as version_history, created_at, updated_at FROM snippets WHERE id = ?")
-        .bind(id)
-        .fetch_one(pool)
-        .await?;
+    let snippet = get_snippet(pool, id).await?.ok_or(sqlx::Error::RowNotFound)?;
     
     let new_title = format!("{} (Copy)", snippet.title);
     add_snippet(pool, new_title, snippet.content, snippet.language, snippet.tags, snippet.description, snippet.folder).await
@@ -994,3 +1402,52 @@ pub async fn delete_snippet(pool: &Pool<Sqlite>, id: i64) -> Result<(), sqlx::Er
     Ok(())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn editing_a_clip_drops_its_copied_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = open_db(dir.path()).await.unwrap();
+        let id = insert_clip(&pool, "<b>old</b>".to_string(), "html".to_string(), "h".to_string(), None, None).await.unwrap();
+        insert_clip_format(&pool, id, "text/html", Some(b"<b>old</b>".to_vec()), None, 10).await.unwrap();
+
+        update_clip_content(&pool, id, "new".to_string()).await.unwrap();
+        assert_eq!(get_clip(&pool, id).await.unwrap().unwrap().content, "new");
+        assert!(get_clip_formats(&pool, id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn global_search_ranks_each_module_on_its_own() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = open_db(dir.path()).await.unwrap();
+        insert_clip(&pool, "rust rust rust".to_string(), "text".to_string(), "a".to_string(), None, None).await.unwrap();
+        insert_clip(&pool, "a long clip that mentions rust once among many other words".to_string(), "text".to_string(), "b".to_string(), None, None).await.unwrap();
+        add_note(&pool, "Notes".to_string(), "learning rust".to_string(), None, None).await.unwrap();
+
+        let results = global_search(&pool, "rust", 10).await.unwrap();
+        let ranked: Vec<(&str, f64)> = results.iter().map(|r| (r.module.as_str(), r.score)).collect();
+        assert_eq!(ranked, vec![("clip", 0.0), ("note", 0.0), ("clip", 1.0)]);
+        assert_eq!(results[0].highlight, "<mark>rust</mark> <mark>rust</mark> <mark>rust</mark>");
+    }
+
+    #[tokio::test]
+    async fn ocr_text_is_searchable_but_not_a_paste_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = open_db(dir.path()).await.unwrap();
+        let id = insert_clip(&pool, "/blobs/ab/capture.png".to_string(), "image".to_string(), "c".to_string(), None, None).await.unwrap();
+        set_clip_ocr_text(&pool, id, "Quarterly invoice total".to_string()).await.unwrap();
+
+        let query = crate::search::parse_clip_query("invoice").unwrap();
+        let clips = get_clips(&pool, 10, 0, Some(&query), None, false, true).await.unwrap();
+        assert_eq!(clips.iter().map(|c| c.id).collect::<Vec<_>>(), vec![id]);
+        assert_eq!(clips[0].ocr_text.as_deref(), Some("Quarterly invoice total"));
+        assert!(get_clip_formats(&pool, id).await.unwrap().is_empty());
+
+        let results = global_search(&pool, "quarterly", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].highlight.contains("<mark>Quarterly</mark>"));
+    }
+}
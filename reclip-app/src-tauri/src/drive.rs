@@ -8,7 +8,8 @@ use oauth2::{
 };
 use std::collections::HashMap;
 use crate::db::{DbState, set_setting, get_setting};
-use crate::crypto;
+use crate::secrets;
+use crate::sync::{Object, SyncBackend};
 use reqwest::Client;
 
 
@@ -70,9 +71,9 @@ async fn get_valid_token(
     }
 
     // 2. Need to refresh or load.
-    let refresh_token = get_setting(&db_state.pool, "drive_refresh_token").await;
+    let refresh_token = secrets::get(&db_state.pool, secrets::DRIVE_REFRESH_TOKEN).await?;
     let client_id = get_setting(&db_state.pool, "drive_client_id").await;
-    let client_secret = get_setting(&db_state.pool, "drive_client_secret").await;
+    let client_secret = secrets::get(&db_state.pool, secrets::DRIVE_CLIENT_SECRET).await?;
 
     if let (Some(rt), Some(cid), Some(csec)) = (refresh_token, client_id, client_secret) {
         if rt.is_empty() { return Err("No refresh token".into()); }
@@ -107,9 +108,16 @@ pub async fn start_google_auth(
     client_id: String,
     client_secret: String
 ) -> Result<String, String> {
+    // An empty secret reuses the stored one, so it doesn't have to be kept in the UI
+    let client_secret = if client_secret.is_empty() {
+        secrets::get(&db_state.pool, secrets::DRIVE_CLIENT_SECRET).await?.ok_or("Enter the client secret")?
+    } else {
+        client_secret
+    };
+
     // Save credentials for future refreshes
     set_setting(&db_state.pool, "drive_client_id", &client_id).await.map_err(|e| e.to_string())?;
-    set_setting(&db_state.pool, "drive_client_secret", &client_secret).await.map_err(|e| e.to_string())?;
+    secrets::set(&db_state.pool, secrets::DRIVE_CLIENT_SECRET, &client_secret).await?;
 
     let client = create_client(client_id.clone(), client_secret.clone())?;
     
@@ -212,9 +220,9 @@ pub async fn finish_google_auth(
     let access_token = token_result.access_token().secret();
     let refresh_token = token_result.refresh_token().map(|t| t.secret());
     
-    // Store Refresh Token in DB
+    // Store Refresh Token in the secret store
     if let Some(rt) = refresh_token {
-        set_setting(&db_state.pool, "drive_refresh_token", rt).await.map_err(|e| e.to_string())?;
+        secrets::set(&db_state.pool, secrets::DRIVE_REFRESH_TOKEN, rt).await?;
     }
     
     // Store Access Token in Memory
@@ -267,11 +275,11 @@ pub async fn get_drive_status(db_state: State<'_, DbState>) -> Result<DriveInfo,
 #[tauri::command]
 pub async fn disconnect_google_drive(state: State<'_, DriveState>, db_state: State<'_, DbState>) -> Result<(), String> {
     set_setting(&db_state.pool, "drive_connected", "false").await.map_err(|e| e.to_string())?;
-    set_setting(&db_state.pool, "drive_refresh_token", "").await.map_err(|e| e.to_string())?;
+    secrets::delete(&db_state.pool, secrets::DRIVE_REFRESH_TOKEN).await?;
     set_setting(&db_state.pool, "drive_user_name", "").await.map_err(|e| e.to_string())?;
     set_setting(&db_state.pool, "drive_user_email", "").await.map_err(|e| e.to_string())?;
     set_setting(&db_state.pool, "drive_client_id", "").await.map_err(|e| e.to_string())?;
-    set_setting(&db_state.pool, "drive_client_secret", "").await.map_err(|e| e.to_string())?;
+    secrets::delete(&db_state.pool, secrets::DRIVE_CLIENT_SECRET).await?;
     
     {
         let mut at_lock = state.access_token.lock().map_err(|e| e.to_string())?;
@@ -328,182 +336,182 @@ async fn ensure_reclip_folder(token: &str, db_state: &State<'_, DbState>) -> Res
     Ok(id)
 }
 
-async fn list_drive_files(token: &str, folder_id: &str) -> Result<HashMap<String, String>, String> {
+/// Files of the folder: name -> (id, version)
+async fn list_drive_files(token: &str, folder_id: &str) -> Result<HashMap<String, (String, String)>, String> {
     let client = Client::new();
     let query = format!("'{}' in parents and trashed=false", folder_id);
     let url = "https://www.googleapis.com/drive/v3/files";
-    
-    let resp: serde_json::Value = client.get(url)
-        .bearer_auth(token)
-        .query(&[("q", query.as_str()), ("fields", "files(id, name, modifiedTime)")])
-        .send().await.map_err(|e| e.to_string())?
-        .json().await.map_err(|e| e.to_string())?;
-        
+
     let mut file_map = HashMap::new();
-    if let Some(files) = resp["files"].as_array() {
-        for file in files {
-            if let (Some(name), Some(id)) = (file["name"].as_str(), file["id"].as_str()) {
-                file_map.insert(name.to_string(), id.to_string());
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut params = vec![("q", query.clone()), ("fields", "nextPageToken, files(id, name, version)".to_string()), ("pageSize", "1000".to_string())];
+        if let Some(t) = &page_token {
+            params.push(("pageToken", t.clone()));
+        }
+        let resp: serde_json::Value = client.get(url)
+            .bearer_auth(token)
+            .query(&params)
+            .send().await.map_err(|e| e.to_string())?
+            .error_for_status().map_err(|e| e.to_string())?
+            .json().await.map_err(|e| e.to_string())?;
+
+        if let Some(files) = resp["files"].as_array() {
+            for file in files {
+                if let (Some(name), Some(id)) = (file["name"].as_str(), file["id"].as_str()) {
+                    let version = file["version"].as_str().unwrap_or_default();
+                    file_map.insert(name.to_string(), (id.to_string(), version.to_string()));
+                }
             }
         }
+        match resp["nextPageToken"].as_str() {
+            Some(t) => page_token = Some(t.to_string()),
+            None => break,
+        }
     }
     Ok(file_map)
 }
 
-async fn upload_file_content(token: &str, folder_id: &str, filename: &str, content: &str) -> Result<(), String> {
+/// Create a file in the folder, returns its id
+async fn upload_file_content(token: &str, folder_id: &str, filename: &str, content: Vec<u8>) -> Result<String, String> {
+    use reqwest::multipart;
+
     let client = Client::new();
-    
-    // Simple metadata-only create check? No, we need multipart for metadata + content, 
-    // or just upload content if we don't care about metadata details except name/parent.
-    
     let metadata = serde_json::json!({
         "name": filename,
         "parents": [folder_id]
     });
-    
-    // Multipart upload is complex with reqwest serde json alone. 
-    // We'll use the 'multipart' upload type with a proper body construction if possible,
-    // or just create file with metadata then update media.
-    // Easier: Create metadata to get ID, then PATCH content? No, can do distinct calls.
-    
-    // Let's use the 'upload' endpoint with multipart/related for single request
-    // Or simple: 
-    // 1. Create file metadata (if not exists)
-    // 2. Upload media
-    
-    // For MVP, lets try strictly creating new files (we check existence in sync logic).
-    // If it exists, we should probably update it (PATCH).
-    
-    // Construct valid multipart body manually or use reqwest::multipart
-    use reqwest::multipart;
-    
+
     let part_metadata = multipart::Part::text(metadata.to_string())
         .mime_str("application/json").map_err(|e| e.to_string())?;
-        
-    let part_content = multipart::Part::text(content.to_string())
-        .mime_str("application/json").map_err(|e| e.to_string())?;
-        
+    let part_content = multipart::Part::bytes(content)
+        .mime_str("application/octet-stream").map_err(|e| e.to_string())?;
     let form = multipart::Form::new()
         .part("metadata", part_metadata)
         .part("media", part_content);
-        
-    let _ = client.post("https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart")
+
+    let resp: serde_json::Value = client.post("https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart")
         .bearer_auth(token)
         .multipart(form)
-        .send().await.map_err(|e| e.to_string())?;
-        
+        .send().await.map_err(|e| e.to_string())?
+        .error_for_status().map_err(|e| e.to_string())?
+        .json().await.map_err(|e| e.to_string())?;
+
+    resp["id"].as_str().map(|id| id.to_string()).ok_or_else(|| "Upload returned no file id".to_string())
+}
+
+/// Replace the content of an existing file
+async fn update_file_content(token: &str, file_id: &str, content: Vec<u8>) -> Result<(), String> {
+    Client::new().patch(format!("https://www.googleapis.com/upload/drive/v3/files/{}?uploadType=media", file_id))
+        .bearer_auth(token)
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .body(content)
+        .send().await.map_err(|e| e.to_string())?
+        .error_for_status().map_err(|e| e.to_string())?;
     Ok(())
 }
 
-async fn get_file_content(token: &str, file_id: &str) -> Result<String, String> {
+async fn get_file_content(token: &str, file_id: &str) -> Result<Vec<u8>, String> {
     let client = Client::new();
     let content = client.get(format!("https://www.googleapis.com/drive/v3/files/{}?alt=media", file_id))
         .bearer_auth(token)
         .send().await.map_err(|e| e.to_string())?
-        .text().await.map_err(|e| e.to_string())?;
-    Ok(content)
+        .error_for_status().map_err(|e| e.to_string())?
+        .bytes().await.map_err(|e| e.to_string())?;
+    Ok(content.to_vec())
 }
 
-#[tauri::command]
-pub async fn sync_clips(
-    _app: AppHandle,
-    state: State<'_, DriveState>,
-    db_state: State<'_, DbState>
-) -> Result<String, String> {
-    // 1. Authenticate
-    let token = get_valid_token(&state, &db_state).await?;
-    
-    // 2. Folder
-    let folder_id = ensure_reclip_folder(&token, &db_state).await?;
-    
-    // 3. List Drive Files
-    let drive_files = list_drive_files(&token, &folder_id).await?;
-    
-    // 4. List Local Clips
-    let sync_password = get_setting(&db_state.pool, "sync_encryption_password").await;
-    
-    // Use offline query function instead of macro for missing env
-    let clips = sqlx::query_as::<_, (i64, String, String)>("SELECT id, content, created_at FROM clips WHERE is_text = 1")
-        .fetch_all(&db_state.pool).await.map_err(|e| e.to_string())?;
-        
-    let mut uploaded_count = 0;
-    
-    // 5. Upload missing
-    let mut local_ids = std::collections::HashSet::new();
-    for (id, content, created_at) in &clips {
-        local_ids.insert(*id);
-        
-        let filename = format!("clip_{}.json", id);
-        if !drive_files.contains_key(&filename) {
-            // Prepare data
-            let clip_data = serde_json::json!({
-                "id": id,
-                "content": content,
-                "created_at": created_at
-            });
-            
-            let mut final_content = clip_data.to_string();
-            
-            // Encrypt if password set
-            if let Some(ref pwd) = sync_password {
-                if !pwd.is_empty() {
-                    if let Ok(encrypted) = crypto::encrypt(final_content.as_bytes(), pwd) {
-                        final_content = serde_json::to_string(&encrypted).unwrap_or(final_content);
-                    }
-                }
-            }
-            
-            upload_file_content(&token, &folder_id, &filename, &final_content).await?;
-            uploaded_count += 1;
+async fn delete_file(token: &str, file_id: &str) -> Result<(), String> {
+    let response = Client::new().delete(format!("https://www.googleapis.com/drive/v3/files/{}", file_id))
+        .bearer_auth(token)
+        .send().await.map_err(|e| e.to_string())?;
+    if response.status().is_success() || response.status() == reqwest::StatusCode::NOT_FOUND {
+        Ok(())
+    } else {
+        Err(format!("Failed to delete Drive file: {}", response.status()))
+    }
+}
+
+/// The ReClip folder as sync storage. Drive folders are flat, keys are used as file names as they are.
+pub struct DriveBackend {
+    token: String,
+    folder_id: String,
+    // File name -> (id, version), filled by `list`
+    files: tokio::sync::Mutex<Option<HashMap<String, (String, String)>>>,
+}
+
+impl DriveBackend {
+    async fn file(&self, key: &str, refresh: bool) -> Result<Option<(String, String)>, String> {
+        let mut files = self.files.lock().await;
+        if files.is_none() || refresh {
+            *files = Some(list_drive_files(&self.token, &self.folder_id).await?);
         }
+        Ok(files.as_ref().and_then(|f| f.get(key).cloned()))
     }
-    
-    // 6. Download missing
-    let mut downloaded_count = 0;
-    for (filename, file_id) in drive_files {
-        if filename.starts_with("clip_") && filename.ends_with(".json") {
-            // Extract ID
-            let id_part = &filename[5..filename.len()-5];
-            if let Ok(id) = id_part.parse::<i64>() {
-                if !local_ids.contains(&id) {
-                    // Download
-                    if let Ok(content_str) = get_file_content(&token, &file_id).await {
-                        let mut final_data_str = content_str.clone();
-                        
-                        // Decrypt if password set
-                        if let Some(ref pwd) = sync_password {
-                            if !pwd.is_empty() {
-                                if let Ok(encrypted) = serde_json::from_str::<crypto::EncryptedData>(&content_str) {
-                                    if let Ok(decrypted) = crypto::decrypt(&encrypted, pwd) {
-                                        final_data_str = String::from_utf8_lossy(&decrypted).to_string();
-                                    }
-                                }
-                            }
-                        }
 
-                        if let Ok(data) = serde_json::from_str::<serde_json::Value>(&final_data_str) {
-                             let content = data["content"].as_str().unwrap_or_default();
-                             let created_at = data["created_at"].as_str().unwrap_or("");
-                             
-                             // Insert into DB
-                             let _ = sqlx::query("INSERT OR IGNORE INTO clips (id, content, created_at, is_text) VALUES (?, ?, ?, 1)")
-                             .bind(id)
-                             .bind(content)
-                             .bind(created_at)
-                             .execute(&db_state.pool)
-                             .await;
-                             
-                             downloaded_count += 1;
-                        }
-                    }
+    async fn forget(&self, key: &str) {
+        if let Some(files) = self.files.lock().await.as_mut() {
+            files.remove(key);
+        }
+    }
+}
+
+/// Drive sync storage of the connected account
+pub(crate) async fn backend(state: &State<'_, DriveState>, db_state: &State<'_, DbState>) -> Result<DriveBackend, String> {
+    let token = get_valid_token(state, db_state).await?;
+    let folder_id = ensure_reclip_folder(&token, db_state).await?;
+    Ok(DriveBackend { token, folder_id, files: tokio::sync::Mutex::new(None) })
+}
+
+#[async_trait::async_trait]
+impl SyncBackend for DriveBackend {
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let listing = list_drive_files(&self.token, &self.folder_id).await?;
+        let keys = listing.keys().filter(|name| name.starts_with(prefix)).cloned().collect();
+        *self.files.lock().await = Some(listing);
+        Ok(keys)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Object>, String> {
+        match self.file(key, false).await? {
+            Some((id, version)) => Ok(Some(Object { data: get_file_content(&self.token, &id).await?, version })),
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), String> {
+        match self.file(key, false).await? {
+            Some((id, _)) => {
+                update_file_content(&self.token, &id, data).await?;
+                // The version changed, the next lookup lists again
+                *self.files.lock().await = None;
+                Ok(())
+            }
+            None => {
+                let id = upload_file_content(&self.token, &self.folder_id, key, data).await?;
+                if let Some(files) = self.files.lock().await.as_mut() {
+                    files.insert(key.to_string(), (id, String::new()));
                 }
+                Ok(())
             }
         }
     }
-    
-    // Update last sync time
-    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(); // Format for display
-    set_setting(&db_state.pool, "drive_last_sync", &now).await.map_err(|e| e.to_string())?;
-    
-    Ok(format!("Synced: Uploaded {}, Downloaded {}", uploaded_count, downloaded_count))
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        if let Some((id, _)) = self.file(key, false).await? {
+            delete_file(&self.token, &id).await?;
+            self.forget(key).await;
+        }
+        Ok(())
+    }
+
+    // Drive has no conditional uploads, this checks a fresh listing right before writing
+    async fn put_if(&self, key: &str, data: Vec<u8>, version: Option<&str>) -> Result<bool, String> {
+        let current = self.file(key, true).await?;
+        match (current, version) {
+            (None, None) => self.put(key, data).await.map(|_| true),
+            (Some((_, current)), Some(version)) if current == version => self.put(key, data).await.map(|_| true),
+            _ => Ok(false),
+        }
+    }
 }
@@ -0,0 +1,45 @@
+use std::sync::LazyLock;
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::sync::broadcast;
+
+/// A change to a clip. Published to the webview and to everything subscribed here (API event stream).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipEvent {
+    Created(i64),
+    Updated(i64),
+    Deleted(i64),
+}
+
+impl ClipEvent {
+    /// Event name, the same for the webview and the API stream
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClipEvent::Created(_) => "clip-created",
+            ClipEvent::Updated(_) => "clip-updated",
+            ClipEvent::Deleted(_) => "clip-deleted",
+        }
+    }
+
+    /// The payload: id of the clip
+    pub fn clip_id(&self) -> i64 {
+        match self {
+            ClipEvent::Created(id) | ClipEvent::Updated(id) | ClipEvent::Deleted(id) => *id,
+        }
+    }
+}
+
+// Subscribers that fall this far behind skip events instead of holding up publishers
+const CHANNEL_CAPACITY: usize = 256;
+
+static EVENTS: LazyLock<broadcast::Sender<ClipEvent>> = LazyLock::new(|| broadcast::channel(CHANNEL_CAPACITY).0);
+
+pub fn subscribe() -> broadcast::Receiver<ClipEvent> {
+    EVENTS.subscribe()
+}
+
+/// Emit a clip change to the webview and every subscriber
+pub fn publish<R: Runtime>(app: &AppHandle<R>, event: ClipEvent) {
+    let _ = app.emit(event.name(), event.clip_id());
+    // Only fails when nobody is subscribed
+    let _ = EVENTS.send(event);
+}
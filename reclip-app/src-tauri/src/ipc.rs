@@ -0,0 +1,585 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+use std::path::PathBuf;
+use std::time::Duration;
+use log::{info, warn};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use crate::capture::{self, PendingClip};
+use crate::db::{self, DbState};
+use crate::events::{self, ClipEvent};
+use crate::search;
+
+/// Must match `identifier` in tauri.conf.json, the CLI has no app to ask for its data dir
+const APP_IDENTIFIER: &str = "com.reclip.app";
+
+/// How long the CLI waits for the running app to answer, generous for exports and imports with images
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const APP_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    /// Absent for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RpcResponse {
+    jsonrpc: String,
+    id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+fn app_error(e: impl ToString) -> RpcError {
+    RpcError::new(APP_ERROR, e.to_string())
+}
+
+fn params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    // Methods without required params may be called with no params at all
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+#[derive(Deserialize)]
+struct HistoryParams {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Filters in search bar syntax, e.g. `tag:#url is:pinned`
+    query: Option<String>,
+    #[serde(rename = "type")]
+    type_: Option<String>,
+    favorites: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct IdParams {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    /// Same syntax as the search bar
+    query: String,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct PinParams {
+    id: i64,
+    /// Toggles when unset
+    pinned: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct AddParams {
+    /// Text of the clip, unused for images
+    #[serde(default)]
+    content: String,
+    #[serde(rename = "type")]
+    type_: Option<String>,
+    /// Base64 image data for image clips
+    data: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    sender_app: Option<String>,
+    /// Set by `reclip --copy`: hashes the clipboard listener will see for what the CLI copies next
+    #[serde(default)]
+    clipboard_hashes: Vec<String>,
+}
+
+/// A clip as written by `export`, images carry their bytes
+#[derive(Serialize, Deserialize)]
+struct ExportedClip {
+    #[serde(flatten)]
+    clip: db::Clip,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Export {
+    version: u32,
+    clips: Vec<ExportedClip>,
+    snippets: Vec<db::Snippet>,
+}
+
+#[derive(Deserialize)]
+struct SnippetParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct NewSnippetParams {
+    title: String,
+    content: String,
+    #[serde(default)]
+    language: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    folder: String,
+}
+
+/// Methods that change the database, the CLI opens it writable for these
+const WRITE_METHODS: &[&str] = &["add", "delete", "pin", "import", "snippets.add"];
+
+/// Where a request is answered: the running app, or the CLI on its own when ReClip isn't running
+struct Context<'a> {
+    pool: &'a Pool<Sqlite>,
+    app: Option<&'a AppHandle>,
+    app_dir: PathBuf,
+}
+
+impl Context<'_> {
+    /// Tell the webview, the API stream and the tray, when there is an app to tell
+    async fn publish(&self, events: &[ClipEvent]) {
+        let Some(app) = self.app else { return };
+        for event in events {
+            events::publish(app, *event);
+        }
+        if !events.is_empty() {
+            let _ = crate::tray::update_tray_history(app).await;
+        }
+    }
+}
+
+/// Tags as stored by the clipboard listener: lowercase with a leading `#`
+fn clip_tags(tags: &[String]) -> Vec<String> {
+    tags.iter()
+        .map(|t| t.trim().trim_start_matches('#').to_lowercase())
+        .filter(|t| !t.is_empty())
+        .map(|t| format!("#{}", t))
+        .collect()
+}
+
+/// Store an image in the blob store and record it as a clip
+async fn insert_image(ctx: &Context<'_>, data: &[u8], clip: Option<&db::Clip>, tags: Option<String>, sender_app: Option<String>) -> Result<i64, RpcError> {
+    let format = image::guess_format(data).map_err(|_| RpcError::new(INVALID_PARAMS, "Image data is not in a known format"))?;
+    let blob = crate::blob_store::put(ctx.pool, &ctx.app_dir, data, format.to_mime_type()).await.map_err(app_error)?;
+    let id = match clip {
+        Some(clip) => {
+            let clip = db::Clip { content: blob.path.clone(), hash: blob.hash.clone(), ..clip.clone() };
+            db::import_clip(ctx.pool, &clip).await.map_err(app_error)?
+        }
+        None => db::insert_clip(ctx.pool, blob.path.clone(), "image".to_string(), blob.hash.clone(), tags, sender_app)
+            .await
+            .map_err(app_error)?,
+    };
+    db::set_clip_blob(ctx.pool, id, &blob.hash).await.map_err(app_error)?;
+    Ok(id)
+}
+
+async fn find_clip(pool: &Pool<Sqlite>, id: i64) -> Result<db::Clip, RpcError> {
+    db::get_clip(pool, id)
+        .await
+        .map_err(app_error)?
+        .ok_or_else(|| app_error(format!("Clip {} not found", id)))
+}
+
+/// Answer one request, from the app or from the CLI itself
+async fn dispatch(ctx: &Context<'_>, method: &str, raw: Value) -> Result<Value, RpcError> {
+    let pool = ctx.pool;
+    match method {
+        "ping" => Ok(json!("pong")),
+        "history" => {
+            let p: HistoryParams = params(raw)?;
+            let query = p.query.as_deref()
+                .filter(|q| !q.trim().is_empty())
+                .map(search::parse_clip_query)
+                .transpose()
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e.message))?;
+            let limit = p.limit.unwrap_or(20).clamp(1, 500);
+            let offset = p.offset.unwrap_or(0).max(0);
+            let clips = db::get_clips(pool, limit, offset, query.as_ref(), p.type_, p.favorites.unwrap_or(false), true)
+                .await
+                .map_err(app_error)?;
+            Ok(json!(clips))
+        }
+        "get" => {
+            let p: IdParams = params(raw)?;
+            Ok(json!(find_clip(pool, p.id).await?))
+        }
+        "search" => {
+            let p: SearchParams = params(raw)?;
+            let query = search::parse_clip_query(&p.query).map_err(|e| RpcError::new(INVALID_PARAMS, e.message))?;
+            let limit = p.limit.unwrap_or(20).clamp(1, 500);
+            let clips = db::get_clips(pool, limit, 0, Some(&query), None, false, true).await.map_err(app_error)?;
+            Ok(json!(clips))
+        }
+        "add" => {
+            let p: AddParams = params(raw)?;
+            if ctx.app.is_some() {
+                crate::clipboard::expect_cli_copy(&p.clipboard_hashes);
+            }
+            let type_ = p.type_.unwrap_or_else(|| "text".to_string());
+            let tags = clip_tags(&p.tags);
+            let id = match type_.as_str() {
+                "image" => {
+                    let data = p.data.ok_or_else(|| RpcError::new(INVALID_PARAMS, "Image clips need data"))?;
+                    let data = BASE64.decode(data).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+                    let tags = (!tags.is_empty()).then(|| serde_json::to_string(&tags).unwrap_or_default());
+                    let id = insert_image(ctx, &data, None, tags, p.sender_app).await?;
+                    ctx.publish(&[ClipEvent::Created(id)]).await;
+                    id
+                }
+                "text" | "html" => {
+                    if p.content.is_empty() {
+                        return Err(RpcError::new(INVALID_PARAMS, "Clip content is empty"));
+                    }
+                    let mut clip = PendingClip::submitted(p.content, type_ == "html", tags, p.sender_app);
+                    // The same rules as copied clips, for the sender app
+                    let rules = capture::Rules::load(pool).await.map_err(app_error)?;
+                    capture::privacy(&mut clip, &rules).map_err(|skip| app_error(format!("Clip not stored: {}", skip)))?;
+                    capture::detect(pool, &mut clip).await;
+                    // `insert` tells the app itself
+                    match ctx.app {
+                        Some(app) => capture::insert(app, pool, clip).await.map_err(app_error)?,
+                        None => capture::store(pool, &ctx.app_dir, clip).await.map_err(app_error)?,
+                    }
+                }
+                other => return Err(RpcError::new(INVALID_PARAMS, format!("Clips of type '{}' can't be added", other))),
+            };
+            Ok(json!(find_clip(pool, id).await?))
+        }
+        "delete" => {
+            let p: IdParams = params(raw)?;
+            find_clip(pool, p.id).await?;
+            db::delete_clip(pool, p.id).await.map_err(app_error)?;
+            ctx.publish(&[ClipEvent::Deleted(p.id)]).await;
+            Ok(json!({ "id": p.id }))
+        }
+        "pin" => {
+            let p: PinParams = params(raw)?;
+            find_clip(pool, p.id).await?;
+            let pinned = match p.pinned {
+                Some(pinned) => {
+                    db::set_clip_pinned(pool, p.id, pinned).await.map_err(app_error)?;
+                    pinned
+                }
+                None => db::toggle_pin(pool, p.id).await.map_err(app_error)?,
+            };
+            ctx.publish(&[ClipEvent::Updated(p.id)]).await;
+            Ok(json!({ "id": p.id, "pinned": pinned }))
+        }
+        "export" => {
+            // LIMIT -1 is unlimited in SQLite
+            let clips = db::get_clips(pool, -1, 0, None, None, false, true).await.map_err(app_error)?;
+            let mut exported = Vec::new();
+            // Sensitive clips are about to be wiped, they don't belong in a file
+            for clip in clips.into_iter().filter(|c| !c.sensitive) {
+                let data = if clip.type_ == "image" {
+                    match std::fs::read(&clip.content) {
+                        Ok(bytes) => Some(BASE64.encode(bytes)),
+                        Err(e) => {
+                            warn!("Skipping image clip {} in export: {}", clip.id, e);
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+                exported.push(ExportedClip { clip, data });
+            }
+            let snippets = db::get_snippets(pool).await.map_err(app_error)?;
+            Ok(json!(Export { version: 1, clips: exported, snippets }))
+        }
+        "import" => {
+            let export: Export = params(raw)?;
+            let (mut clips, mut skipped_clips, mut snippets, mut skipped_snippets) = (0, 0, 0, 0);
+            let mut created = Vec::new();
+            for ExportedClip { clip, data } in export.clips {
+                // Clips are unique by hash, the same content is already there
+                if db::get_clip_id_by_hash(pool, &clip.hash).await.map_err(app_error)?.is_some() {
+                    skipped_clips += 1;
+                    continue;
+                }
+                let origin = if clip.origin.is_empty() { "clipboard".to_string() } else { clip.origin.clone() };
+                let clip = db::Clip { origin, ..clip };
+                let id = match (clip.type_.as_str(), data) {
+                    ("image", Some(data)) => {
+                        let data = BASE64.decode(data).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+                        insert_image(ctx, &data, Some(&clip), None, None).await?
+                    }
+                    ("image", None) => {
+                        skipped_clips += 1;
+                        continue;
+                    }
+                    _ => db::import_clip(pool, &clip).await.map_err(app_error)?,
+                };
+                created.push(ClipEvent::Created(id));
+                clips += 1;
+            }
+            for snippet in export.snippets {
+                let existing = db::get_snippet_by_title(pool, &snippet.title).await.map_err(app_error)?;
+                if existing.is_some_and(|s| s.content == snippet.content) {
+                    skipped_snippets += 1;
+                    continue;
+                }
+                db::add_snippet(pool, snippet.title, snippet.content, snippet.language, snippet.tags, snippet.description, snippet.folder)
+                    .await
+                    .map_err(app_error)?;
+                snippets += 1;
+            }
+            ctx.publish(&created).await;
+            Ok(json!({
+                "clips": clips,
+                "skipped_clips": skipped_clips,
+                "snippets": snippets,
+                "skipped_snippets": skipped_snippets,
+            }))
+        }
+        "snippets.list" => {
+            let snippets = db::get_snippets(pool).await.map_err(app_error)?;
+            Ok(json!(snippets))
+        }
+        "snippets.add" => {
+            let p: NewSnippetParams = params(raw)?;
+            if p.title.trim().is_empty() {
+                return Err(RpcError::new(INVALID_PARAMS, "Snippet title is empty"));
+            }
+            let tags = serde_json::to_string(&p.tags).unwrap_or_else(|_| "[]".to_string());
+            let id = db::add_snippet(pool, p.title, p.content, p.language, tags, p.description, p.folder)
+                .await
+                .map_err(app_error)?;
+            Ok(json!({ "id": id }))
+        }
+        "snippet.get" => {
+            let p: SnippetParams = params(raw)?;
+            match db::get_snippet_by_title(pool, &p.name).await.map_err(app_error)? {
+                Some(snippet) => Ok(json!(snippet)),
+                None => Err(app_error(format!("No snippet named '{}'", p.name))),
+            }
+        }
+        "stats" => {
+            let stats = db::get_usage_stats(pool).await.map_err(app_error)?;
+            Ok(json!(stats))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+    }
+}
+
+/// Read newline-delimited requests and write one response line for each
+async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(stream: S, state: DbState, app: AppHandle) {
+    let Ok(app_dir) = app.path().app_data_dir() else { return };
+    let ctx = Context { pool: &state.pool, app: Some(&app), app_dir };
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<RpcRequest>(&line) {
+            Ok(request) => {
+                let outcome = dispatch(&ctx, &request.method, request.params).await;
+                let Some(id) = request.id else { continue };
+                match outcome {
+                    Ok(result) => RpcResponse { jsonrpc: "2.0".into(), id, result: Some(result), error: None },
+                    Err(error) => RpcResponse { jsonrpc: "2.0".into(), id, result: None, error: Some(error) },
+                }
+            }
+            Err(e) => RpcResponse {
+                jsonrpc: "2.0".into(),
+                id: Value::Null,
+                result: None,
+                error: Some(RpcError::new(PARSE_ERROR, e.to_string())),
+            },
+        };
+        let Ok(mut out) = serde_json::to_vec(&response) else { break };
+        out.push(b'\n');
+        if writer.write_all(&out).await.is_err() {
+            break;
+        }
+    }
+}
+
+fn app_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+/// Per-user socket: in the runtime dir when there is one, else next to the database
+#[cfg(unix)]
+pub fn socket_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .or_else(app_data_dir)
+        .map(|dir| dir.join("reclip.sock"))
+}
+
+/// Pipes are machine-wide, the user name keeps sessions apart
+#[cfg(windows)]
+pub fn pipe_name() -> String {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    format!(r"\\.\pipe\reclip-{}", user)
+}
+
+#[cfg(unix)]
+pub async fn start(app: AppHandle, state: DbState) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = socket_path().ok_or("No directory for the IPC socket")?;
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            return Err(format!("Another instance is listening on {}", path.display()));
+        }
+        // Left behind by an instance that didn't exit cleanly
+        let _ = std::fs::remove_file(&path);
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let listener = UnixListener::bind(&path).map_err(|e| format!("Failed to bind {}: {}", path.display(), e))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+    info!("IPC listening on {}", path.display());
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tauri::async_runtime::spawn(serve_connection(stream, state.clone(), app.clone()));
+                }
+                Err(e) => {
+                    // e.g. out of file descriptors, give connections time to close
+                    warn!("IPC accept failed: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+pub async fn start(app: AppHandle, state: DbState) -> Result<(), String> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = pipe_name();
+    // Fails when another instance already owns the pipe
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&name)
+        .map_err(|e| format!("Failed to create {}: {}", name, e))?;
+    info!("IPC listening on {}", name);
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let connected = server.connect().await;
+            // Keep an instance waiting for the next client before serving this one
+            let client = server;
+            server = match ServerOptions::new().create(&name) {
+                Ok(server) => server,
+                Err(e) => {
+                    warn!("Failed to create {}, IPC stopped: {}", name, e);
+                    break;
+                }
+            };
+            match connected {
+                Ok(()) => {
+                    tauri::async_runtime::spawn(serve_connection(client, state.clone(), app.clone()));
+                }
+                Err(e) => warn!("IPC connect failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// One request over an open connection to the running app
+async fn call_remote<S: AsyncRead + AsyncWrite + Unpin>(stream: S, method: &str, params: Value) -> Result<Value, String> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut request = serde_json::to_vec(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+        .map_err(|e| e.to_string())?;
+    request.push(b'\n');
+    writer.write_all(&request).await.map_err(|e| e.to_string())?;
+
+    let line = BufReader::new(reader).lines().next_line().await
+        .map_err(|e| e.to_string())?
+        .ok_or("ReClip closed the connection")?;
+    let response: RpcResponse = serde_json::from_str(&line).map_err(|e| e.to_string())?;
+    match (response.result, response.error) {
+        (_, Some(error)) => Err(error.to_string()),
+        (Some(result), None) => Ok(result),
+        (None, None) => Ok(Value::Null),
+    }
+}
+
+/// Connection to the running app, unset when it isn't running
+#[cfg(unix)]
+async fn connect() -> Option<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(socket_path()?).await.ok()
+}
+
+#[cfg(windows)]
+async fn connect() -> Option<tokio::net::windows::named_pipe::NamedPipeClient> {
+    tokio::net::windows::named_pipe::ClientOptions::new().open(pipe_name()).ok()
+}
+
+/// Answer from the database file itself. Reads open it read-only so the running app or a later start isn't disturbed,
+/// writes open (and migrate) it like the app does.
+async fn call_local(method: &str, params: Value) -> Result<Value, String> {
+    let app_dir = app_data_dir().ok_or("No data directory")?;
+    let db_path = app_dir.join("reclip.db");
+    let pool = if WRITE_METHODS.contains(&method) {
+        db::open_db(&app_dir).await.map_err(|e| format!("Failed to open {}: {}", db_path.display(), e))?
+    } else {
+        if !db_path.exists() {
+            return Err(format!("ReClip is not running and there is no database at {}", db_path.display()));
+        }
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(&format!("sqlite://{}?mode=ro", db_path.to_string_lossy()))
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", db_path.display(), e))?
+    };
+    let ctx = Context { pool: &pool, app: None, app_dir };
+    let result = dispatch(&ctx, method, params).await.map_err(|e| e.to_string());
+    pool.close().await;
+    result
+}
+
+/// Call `method` on the running app, falling back to reading the database when it isn't running
+pub fn call(method: &str, params: Value) -> Result<Value, String> {
+    tauri::async_runtime::block_on(async {
+        match connect().await {
+            Some(stream) => tokio::time::timeout(CLIENT_TIMEOUT, call_remote(stream, method, params))
+                .await
+                .map_err(|_| "ReClip did not answer in time".to_string())?,
+            None => call_local(method, params).await,
+        }
+    })
+}
@@ -1,8 +1,28 @@
 mod db;
+mod schema;
+mod events;
+mod search;
 mod clipboard;
+mod clipboard_source;
+mod detectors;
+mod redaction;
+mod app_policy;
+mod capture;
+#[cfg(target_os = "linux")]
+mod linux_clipboard;
 mod tray;
 mod ocr;
 mod drive;
+mod sync;
+mod sync_backends;
+mod sync_cmds;
+mod sync_vault;
+mod vault;
+mod vault_cmds;
+mod secrets;
+mod secrets_cmds;
+#[cfg(target_os = "linux")]
+mod secret_service;
 mod crypto;
 mod api;
 mod clip_cmds;
@@ -13,6 +33,9 @@ mod system_cmds;
 mod window_cmds;
 mod maintenance_cmds;
 mod screen_cmds;
+mod blob_store;
+pub mod ipc;
+pub mod cli;
 
 use db::{DbState, init_db};
 use tauri::{Manager, Emitter};
@@ -36,19 +59,39 @@ pub fn run() {
                 init_db(&handle).await
             })?;
             
+            // Credentials go to the system keyring when there is one
+            let secrets_pool = pool.clone();
+            tauri::async_runtime::block_on(async move {
+                secrets::init(&secrets_pool).await;
+            });
+
             // Manage States
             app.manage(DbState { pool: pool.clone() });
             app.manage(ShortcutStateMap(Mutex::new(HashMap::new())));
             app.manage(drive::DriveState::new());
             
-            // Start IDE Plugin API Server (Axum) on port 14201
+            // Start IDE Plugin API Server (Axum), port and bind address come from settings
             let api_pool = pool.clone();
+            let api_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                // A failed start is kept in the API status, the app runs fine without it
+                let _ = api::start(api_handle, DbState { pool: api_pool }).await;
+            });
+
+            // Local socket (named pipe on Windows) the CLI talks to
+            let ipc_pool = pool.clone();
+            let ipc_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                api::start_api_server(DbState { pool: api_pool }).await;
+                if let Err(e) = ipc::start(ipc_handle, DbState { pool: ipc_pool }).await {
+                    log::warn!("IPC not available: {}", e);
+                }
             });
             
             // Start Clipboard Listener
             clipboard::start_clipboard_listener(app.handle(), pool.clone());
+
+            // Lock vault mode after the configured idle time
+            vault::start_auto_lock(app.handle().clone());
             
             // Start Sensitive Clip Cleanup Task (runs every 30 seconds)
             {
@@ -60,7 +103,11 @@ pub fn run() {
                         
                         tauri::async_runtime::block_on(async {
                             // 1. Cleanup Sensitive Clips
-                            let _ = db::cleanup_sensitive_clips(&pool_for_cleanup, 60).await;
+                            if let Ok(deleted) = db::cleanup_sensitive_clips(&pool_for_cleanup, 60).await {
+                                for id in deleted {
+                                    events::publish(&app_handle, events::ClipEvent::Deleted(id));
+                                }
+                            }
 
                             // 2. Check Alarms & Reminders
                             if let Ok(reminders) = db::get_due_reminders(&pool_for_cleanup).await {
@@ -160,9 +207,10 @@ pub fn run() {
                                             let state = app.state::<DbState>();
                                             let pool = state.pool.clone();
                                             tauri::async_runtime::spawn(async move {
-                                                if let Ok(clips) = db::get_clips(&pool, 20, 0, None, None, false).await {
+                                                if let Ok(clips) = db::get_clips(&pool, 20, 0, None, None, false, true).await {
                                                     if let Some(clip) = clips.get(num - 1) {
-                                                        let _ = system_cmds::paste_clip_to_system(app_clone, clip.content.clone(), clip.type_.clone()).await;
+                                                        let state = app_clone.state::<DbState>();
+                                                        let _ = system_cmds::paste_clip_to_system(app_clone.clone(), state, clip.content.clone(), clip.type_.clone(), Some(clip.id)).await;
                                                     }
                                                 }
                                             });
@@ -186,7 +234,7 @@ pub fn run() {
                 let pool_for_tray = pool.clone();
                 let app_for_tray = app.handle().clone();
                 tauri::async_runtime::spawn(async move {
-                    if let Ok(clips) = db::get_clips(&pool_for_tray, 10, 0, None, None, false).await {
+                    if let Ok(clips) = db::get_clips(&pool_for_tray, 10, 0, None, None, false, true).await {
                         let tray_clips: Vec<(i64, String, String)> = clips.iter().map(|c| (c.id, c.content.clone(), c.type_.clone())).collect();
                         tray::update_tray_clips(&app_for_tray, tray_clips);
                     }
@@ -208,7 +256,7 @@ pub fn run() {
         .plugin(tauri_plugin_autostart::init(tauri_plugin_autostart::MacosLauncher::LaunchAgent, Some(vec!["--minimized"])))
         .invoke_handler(tauri::generate_handler![
              greet,
-             clip_cmds::get_recent_clips, clip_cmds::get_clip_stats, clip_cmds::get_clip_dates, clip_cmds::get_clip_type_counts, clip_cmds::global_search, clip_cmds::get_usage_stats,
+             clip_cmds::get_recent_clips, clip_cmds::get_clip_stats, clip_cmds::get_clip_dates, clip_cmds::get_clip_type_counts, clip_cmds::get_clip_formats, clip_cmds::reveal_redacted, clip_cmds::global_search, clip_cmds::get_usage_stats,
              clip_cmds::delete_clip, clip_cmds::clear_clips, clip_cmds::reorder_clip, clip_cmds::update_clip_tags, clip_cmds::toggle_clip_pin, clip_cmds::update_clip_content, clip_cmds::toggle_clip_favorite,
              snippet_cmds::get_snippets, snippet_cmds::add_snippet, snippet_cmds::update_snippet, snippet_cmds::delete_snippet, snippet_cmds::toggle_snippet_favorite, snippet_cmds::duplicate_snippet, snippet_cmds::clear_snippets,
              snippet_cmds::get_notes, snippet_cmds::add_note, snippet_cmds::update_note, snippet_cmds::delete_note,
@@ -217,16 +265,27 @@ pub fn run() {
              settings_cmds::update_shortcut, settings_cmds::get_shortcuts,
              settings_cmds::get_sensitive_settings, settings_cmds::set_sensitive_settings, settings_cmds::get_maintenance_settings, settings_cmds::set_maintenance_settings,
              settings_cmds::get_autostart, settings_cmds::set_autostart, settings_cmds::get_incognito_mode, settings_cmds::set_incognito_mode,
-             settings_cmds::get_privacy_rules, settings_cmds::add_privacy_rule, settings_cmds::delete_privacy_rule,
+             settings_cmds::get_privacy_rules, settings_cmds::add_privacy_rule, settings_cmds::add_app_policy, settings_cmds::test_app_policy, settings_cmds::delete_privacy_rule,
+             settings_cmds::get_detectors, settings_cmds::get_builtin_detectors, settings_cmds::add_detector, settings_cmds::set_detector_enabled, settings_cmds::delete_detector,
              settings_cmds::get_listen_to_self, settings_cmds::set_listen_to_self,
+             settings_cmds::get_primary_selection_settings, settings_cmds::set_primary_selection_settings,
+             settings_cmds::get_api_token, settings_cmds::regenerate_api_token,
+             settings_cmds::get_api_settings, settings_cmds::set_api_settings, settings_cmds::get_api_status,
+             settings_cmds::start_api_server, settings_cmds::stop_api_server, settings_cmds::restart_api_server,
              workflow_cmds::get_workflows, workflow_cmds::add_workflow, workflow_cmds::delete_workflow,
              workflow_cmds::get_regex_rules, workflow_cmds::add_regex_rule, workflow_cmds::update_regex_rule, workflow_cmds::delete_regex_rule,
              workflow_cmds::get_reminders, workflow_cmds::add_reminder, workflow_cmds::update_reminder_content, workflow_cmds::toggle_reminder, workflow_cmds::delete_reminder,
              workflow_cmds::get_alarms, workflow_cmds::add_alarm, workflow_cmds::update_alarm, workflow_cmds::toggle_alarm, workflow_cmds::delete_alarm,
              system_cmds::copy_to_system, system_cmds::copy_image_to_system, system_cmds::paste_clip_to_system, system_cmds::validate_paths, system_cmds::get_app_data_path, system_cmds::get_system_accent_color, system_cmds::get_file_size, system_cmds::export_image, system_cmds::get_url_metadata, system_cmds::run_ocr,
+             screen_cmds::list_monitors, screen_cmds::capture_full_screen, screen_cmds::capture_region,
              window_cmds::save_window_position, window_cmds::load_window_position, window_cmds::is_minimized_launch,
-             maintenance_cmds::run_maintenance, maintenance_cmds::export_clips, maintenance_cmds::import_clips, maintenance_cmds::update_tray_item_state, maintenance_cmds::refresh_tray_clips,
-             drive::start_google_auth, drive::finish_google_auth, drive::get_drive_status, drive::disconnect_google_drive, drive::sync_clips
+             maintenance_cmds::run_maintenance, maintenance_cmds::check_schema, maintenance_cmds::export_clips, maintenance_cmds::import_clips, maintenance_cmds::update_tray_item_state, maintenance_cmds::refresh_tray_clips,
+             drive::start_google_auth, drive::finish_google_auth, drive::get_drive_status, drive::disconnect_google_drive,
+             sync_cmds::get_sync_config, sync_cmds::set_sync_config, sync_cmds::test_sync_backend, sync_cmds::sync_now,
+             sync_cmds::get_sync_vault_status, sync_cmds::unlock_sync_vault, sync_cmds::lock_sync_vault, sync_cmds::change_sync_password, sync_cmds::rotate_sync_key,
+             vault_cmds::get_vault_status, vault_cmds::unlock_vault, vault_cmds::lock_vault, vault_cmds::vault_activity, vault_cmds::set_vault_auto_lock,
+             vault_cmds::enable_vault, vault_cmds::disable_vault,
+             secrets_cmds::get_secret_store_status, secrets_cmds::unlock_secret_store
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
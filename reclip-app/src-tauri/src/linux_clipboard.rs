@@ -0,0 +1,494 @@
+// Raw MIME access to the Linux clipboard.
+// arboard only knows text, HTML, images and text/uri-list. File managers and office suites
+// use more targets (x-special/gnome-copied-files, text/rtf...), so those go through
+// wl-clipboard-rs on Wayland and x11rb on X11.
+
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{info, warn};
+
+use crate::clipboard_source::{ClipOrigin, FormatBudget};
+
+pub const MIME_URI_LIST: &str = "text/uri-list";
+pub const MIME_GNOME_FILES: &str = "x-special/gnome-copied-files";
+
+fn use_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// MIME types offered by the current owner of the selection
+pub fn mime_types(origin: ClipOrigin) -> Vec<String> {
+    let result = if use_wayland() {
+        wayland::mime_types(origin)
+    } else {
+        x11::mime_types(origin)
+    };
+    result.unwrap_or_default()
+}
+
+/// Read one MIME type from the selection, `None` if it is not offered
+pub fn read(origin: ClipOrigin, mime: &str) -> Option<Vec<u8>> {
+    let result = if use_wayland() {
+        wayland::read(origin, mime)
+    } else {
+        x11::read(origin, mime)
+    };
+    match result {
+        Ok(data) => data.filter(|d| !d.is_empty()),
+        Err(e) => {
+            warn!("Failed to read '{}' from clipboard: {}", mime, e);
+            None
+        }
+    }
+}
+
+/// The offered types worth keeping: MIME types only, which skips X11 meta targets (TARGETS, TIMESTAMP...)
+/// and legacy atom names, one plain text variant and one image encoding. Apps convert the image they hold
+/// to every encoding they offer, keeping them all would store the same picture several times.
+fn formats_to_read(types: Vec<String>) -> Vec<String> {
+    let offered = |mime: &str| types.iter().any(|t| t == mime);
+    let plain = ["text/plain;charset=utf-8", "text/plain"].into_iter().find(|m| offered(m));
+    let image = ["image/png", "image/jpeg", "image/bmp", "image/tiff"].into_iter().find(|m| offered(m))
+        .or_else(|| types.iter().map(String::as_str).find(|t| t.starts_with("image/")));
+    types.iter()
+        .filter(|mime| mime.contains('/'))
+        .filter(|mime| !mime.starts_with("text/plain") || Some(mime.as_str()) == plain)
+        .filter(|mime| !mime.starts_with("image/") || Some(mime.as_str()) == image)
+        .cloned()
+        .collect()
+}
+
+/// Every representation worth keeping, within the size limits of a clip
+pub fn read_all(origin: ClipOrigin) -> Vec<(String, Vec<u8>)> {
+    let mut budget = FormatBudget::default();
+    let result = if use_wayland() {
+        wayland::mime_types(origin).map(|types| {
+            for mime in formats_to_read(types) {
+                if budget.is_full() {
+                    break;
+                }
+                if let Some(data) = read(origin, &mime) {
+                    budget.add(mime, data);
+                }
+            }
+        })
+    } else {
+        x11::read_all(origin, &mut budget)
+    };
+    if let Err(e) = result {
+        warn!("Failed to read clipboard formats: {}", e);
+    }
+    budget.into_formats()
+}
+
+/// Read a text MIME type. Firefox offers `text/html` as UTF-16 with a BOM, everything else is UTF-8.
+pub fn read_text(origin: ClipOrigin, mime: &str) -> Option<String> {
+    let data = read(origin, mime)?;
+    if data.starts_with(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = data[2..].chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect();
+        return Some(String::from_utf16_lossy(&units));
+    }
+    Some(String::from_utf8_lossy(&data).trim_end_matches('\0').to_string())
+}
+
+/// Put several representations on the clipboard at once.
+/// They are served from a background thread until another application takes the clipboard.
+pub fn write(formats: Vec<(String, Vec<u8>)>) -> Result<(), String> {
+    if formats.is_empty() {
+        return Err("Nothing to copy".to_string());
+    }
+    if use_wayland() {
+        wayland::write(formats)
+    } else {
+        x11::write(formats)
+    }
+}
+
+/// Local paths from a `text/uri-list` or `x-special/gnome-copied-files` payload.
+/// Comments, the gnome `copy`/`cut` header and non-file URIs are skipped.
+pub fn paths_from_uri_list(data: &str) -> Vec<String> {
+    data.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| url::Url::parse(line).ok())
+        .filter_map(|uri| uri.to_file_path().ok())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+/// `text/uri-list` payload (CRLF separated, as the RFC wants) for local paths
+pub fn uri_list(paths: &[String]) -> Result<String, String> {
+    let uris = paths.iter()
+        .map(|p| url::Url::from_file_path(p).map(|u| u.to_string()).map_err(|_| format!("Not an absolute path: {}", p)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(uris.iter().map(|u| format!("{}\r\n", u)).collect())
+}
+
+/// Clipboard formats for an HTML clip. Apps pick the richest one they understand.
+pub fn rich_text_formats(html: Option<&str>, plain: &str) -> Vec<(String, Vec<u8>)> {
+    let mut formats = Vec::new();
+    if let Some(html) = html {
+        formats.push(("text/html".to_string(), html.as_bytes().to_vec()));
+    }
+    for mime in ["text/plain;charset=utf-8", "text/plain", "UTF8_STRING", "STRING"] {
+        formats.push((mime.to_string(), plain.as_bytes().to_vec()));
+    }
+    formats
+}
+
+/// Make sure plain-text consumers (and X11 apps asking for UTF8_STRING) get `text`
+pub fn with_plain_text(mut formats: Vec<(String, Vec<u8>)>, text: &str) -> Vec<(String, Vec<u8>)> {
+    let offered: Vec<String> = formats.iter().map(|(mime, _)| mime.clone()).collect();
+    for (mime, data) in rich_text_formats(None, text) {
+        if !offered.contains(&mime) {
+            formats.push((mime, data));
+        }
+    }
+    formats
+}
+
+/// Clipboard formats for a file list: uri-list for most apps, gnome-copied-files for Nautilus
+/// and friends, and the paths as plain text for text fields
+pub fn file_list_formats(paths: &[String]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let uris = uri_list(paths)?;
+    let gnome = format!("copy\n{}", uris.trim_end().replace("\r\n", "\n"));
+    let mut formats = vec![
+        (MIME_GNOME_FILES.to_string(), gnome.into_bytes()),
+        (MIME_URI_LIST.to_string(), uris.into_bytes()),
+    ];
+    formats.extend(rich_text_formats(None, &paths.join("\n")));
+    Ok(formats)
+}
+
+mod wayland {
+    use std::io::Read;
+    use super::ClipOrigin;
+    use wl_clipboard_rs::{copy, paste};
+
+    fn clipboard_type(origin: ClipOrigin) -> paste::ClipboardType {
+        match origin {
+            ClipOrigin::Clipboard => paste::ClipboardType::Regular,
+            ClipOrigin::Primary => paste::ClipboardType::Primary,
+        }
+    }
+
+    pub fn mime_types(origin: ClipOrigin) -> Result<Vec<String>, String> {
+        match paste::get_mime_types(clipboard_type(origin), paste::Seat::Unspecified) {
+            Ok(types) => Ok(types.into_iter().collect()),
+            Err(paste::Error::ClipboardEmpty) | Err(paste::Error::NoSeats) => Ok(Vec::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn read(origin: ClipOrigin, mime: &str) -> Result<Option<Vec<u8>>, String> {
+        match paste::get_contents(clipboard_type(origin), paste::Seat::Unspecified, paste::MimeType::Specific(mime)) {
+            Ok((mut pipe, _)) => {
+                let mut data = Vec::new();
+                pipe.read_to_end(&mut data).map_err(|e| e.to_string())?;
+                Ok(Some(data))
+            }
+            Err(paste::Error::ClipboardEmpty) | Err(paste::Error::NoMimeType) | Err(paste::Error::NoSeats) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn write(formats: Vec<(String, Vec<u8>)>) -> Result<(), String> {
+        let sources = formats.into_iter()
+            .map(|(mime, data)| copy::MimeSource {
+                source: copy::Source::Bytes(data.into_boxed_slice()),
+                mime_type: copy::MimeType::Specific(mime),
+            })
+            .collect();
+        copy::Options::new().copy_multi(sources).map_err(|e| e.to_string())
+    }
+}
+
+mod x11 {
+    use super::*;
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property,
+        SelectionNotifyEvent, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+    use x11rb::{CURRENT_TIME, NONE};
+
+    const READ_TIMEOUT: Duration = Duration::from_millis(1000);
+
+    fn selection_name(origin: ClipOrigin) -> &'static [u8] {
+        match origin {
+            ClipOrigin::Clipboard => b"CLIPBOARD",
+            ClipOrigin::Primary => b"PRIMARY",
+        }
+    }
+
+    fn intern(conn: &RustConnection, name: &[u8]) -> Result<Atom, String> {
+        Ok(conn.intern_atom(false, name)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?
+            .atom)
+    }
+
+    /// Connection with a hidden window to receive selection data on
+    fn connect() -> Result<(RustConnection, Window), String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let screen = &conn.setup().roots[screen_num];
+        let window = conn.generate_id().map_err(|e| e.to_string())?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            screen.root,
+            0, 0, 1, 1, 0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        ).map_err(|e| e.to_string())?;
+        Ok((conn, window))
+    }
+
+    fn wait_for<T>(conn: &RustConnection, mut matches: impl FnMut(&Event) -> Option<T>) -> Result<Option<T>, String> {
+        let deadline = Instant::now() + READ_TIMEOUT;
+        while Instant::now() < deadline {
+            match conn.poll_for_event().map_err(|e| e.to_string())? {
+                Some(event) => {
+                    if let Some(result) = matches(&event) {
+                        return Ok(Some(result));
+                    }
+                }
+                None => thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Convert the selection to `target` and return the property data (INCR transfers included)
+    fn convert(conn: &RustConnection, window: Window, origin: ClipOrigin, target: Atom) -> Result<Option<Vec<u8>>, String> {
+        let selection = intern(conn, selection_name(origin))?;
+        let property = intern(conn, b"RECLIP_SELECTION")?;
+        let incr = intern(conn, b"INCR")?;
+
+        conn.convert_selection(window, selection, target, property, CURRENT_TIME).map_err(|e| e.to_string())?;
+        conn.flush().map_err(|e| e.to_string())?;
+
+        let notify = wait_for(conn, |event| match event {
+            Event::SelectionNotify(ev) if ev.requestor == window => Some(ev.property),
+            _ => None,
+        })?;
+        match notify {
+            Some(p) if p != NONE => {}
+            _ => return Ok(None),
+        }
+
+        let reply = conn.get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        conn.flush().map_err(|e| e.to_string())?;
+        if reply.type_ != incr {
+            return Ok(Some(reply.value));
+        }
+
+        // Large transfer: the owner writes chunks, each one after we delete the previous
+        let mut data = Vec::new();
+        loop {
+            let chunk_ready = wait_for(conn, |event| match event {
+                Event::PropertyNotify(ev) if ev.window == window && ev.atom == property && ev.state == Property::NEW_VALUE => Some(()),
+                _ => None,
+            })?;
+            if chunk_ready.is_none() {
+                return Err("INCR transfer timed out".to_string());
+            }
+            let chunk = conn.get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX)
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map_err(|e| e.to_string())?;
+            conn.flush().map_err(|e| e.to_string())?;
+            if chunk.value.is_empty() {
+                return Ok(Some(data));
+            }
+            data.extend_from_slice(&chunk.value);
+        }
+    }
+
+    fn target_names(conn: &RustConnection, window: Window, origin: ClipOrigin) -> Result<Vec<String>, String> {
+        let targets = intern(conn, b"TARGETS")?;
+        let Some(data) = convert(conn, window, origin, targets)? else {
+            return Ok(Vec::new());
+        };
+        let mut names = Vec::new();
+        for atom in data.chunks_exact(4).map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]])) {
+            if let Ok(reply) = conn.get_atom_name(atom).map_err(|e| e.to_string())?.reply() {
+                names.push(String::from_utf8_lossy(&reply.name).to_string());
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn mime_types(origin: ClipOrigin) -> Result<Vec<String>, String> {
+        let (conn, window) = connect()?;
+        target_names(&conn, window, origin)
+    }
+
+    pub fn read(origin: ClipOrigin, mime: &str) -> Result<Option<Vec<u8>>, String> {
+        let (conn, window) = connect()?;
+        let target = intern(&conn, mime.as_bytes())?;
+        convert(&conn, window, origin, target)
+    }
+
+    /// TARGETS and the conversions worth keeping over one connection
+    pub fn read_all(origin: ClipOrigin, budget: &mut FormatBudget) -> Result<(), String> {
+        let (conn, window) = connect()?;
+        for mime in formats_to_read(target_names(&conn, window, origin)?) {
+            if budget.is_full() {
+                break;
+            }
+            let target = intern(&conn, mime.as_bytes())?;
+            match convert(&conn, window, origin, target) {
+                Ok(Some(data)) => budget.add(mime, data),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to read '{}' from clipboard: {}", mime, e),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write(formats: Vec<(String, Vec<u8>)>) -> Result<(), String> {
+        let (conn, window) = connect()?;
+        let clipboard = intern(&conn, b"CLIPBOARD")?;
+        let targets = intern(&conn, b"TARGETS")?;
+        let incr = intern(&conn, b"INCR")?;
+        let mut offered = Vec::new();
+        for (mime, data) in formats {
+            offered.push((intern(&conn, mime.as_bytes())?, data));
+        }
+
+        conn.set_selection_owner(window, clipboard, CURRENT_TIME).map_err(|e| e.to_string())?;
+        let owner = conn.get_selection_owner(clipboard)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?
+            .owner;
+        if owner != window {
+            return Err("Could not take ownership of the clipboard".to_string());
+        }
+
+        thread::spawn(move || {
+            if let Err(e) = serve(&conn, window, Atoms { clipboard, targets, incr }, &offered) {
+                warn!("Stopped serving clipboard: {}", e);
+            }
+        });
+        Ok(())
+    }
+
+    struct Atoms {
+        clipboard: Atom,
+        targets: Atom,
+        incr: Atom,
+    }
+
+    /// A format too large for one request, sent in chunks as the requestor deletes each one
+    struct Incr {
+        requestor: Window,
+        property: Atom,
+        target: Atom,
+        format: usize,
+        offset: usize,
+    }
+
+    /// Answer selection requests until another client takes the clipboard
+    fn serve(conn: &RustConnection, window: Window, atoms: Atoms, offered: &[(Atom, Vec<u8>)]) -> Result<(), String> {
+        let max_len = conn.maximum_request_bytes().saturating_sub(64);
+        let mut transfers: Vec<Incr> = Vec::new();
+        loop {
+            match conn.wait_for_event().map_err(|e| e.to_string())? {
+                Event::SelectionRequest(req) if req.selection == atoms.clipboard && req.owner == window => {
+                    let property = if req.property == NONE { req.target } else { req.property };
+                    let mut reply_property = property;
+
+                    if req.target == atoms.targets {
+                        let mut atoms_offered: Vec<Atom> = offered.iter().map(|(atom, _)| *atom).collect();
+                        atoms_offered.push(atoms.targets);
+                        conn.change_property32(PropMode::REPLACE, req.requestor, property, AtomEnum::ATOM, &atoms_offered)
+                            .map_err(|e| e.to_string())?;
+                    } else if let Some(format) = offered.iter().position(|(atom, _)| *atom == req.target) {
+                        let data = &offered[format].1;
+                        if data.len() <= max_len {
+                            conn.change_property8(PropMode::REPLACE, req.requestor, property, req.target, data)
+                                .map_err(|e| e.to_string())?;
+                        } else {
+                            // The size goes first as an INCR property, the requestor deleting it asks for the next chunk
+                            info!("Sending {} bytes of clipboard data in chunks", data.len());
+                            conn.change_window_attributes(req.requestor, &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE))
+                                .map_err(|e| e.to_string())?;
+                            conn.change_property32(PropMode::REPLACE, req.requestor, property, atoms.incr, &[data.len() as u32])
+                                .map_err(|e| e.to_string())?;
+                            transfers.retain(|t| t.requestor != req.requestor || t.property != property);
+                            transfers.push(Incr { requestor: req.requestor, property, target: req.target, format, offset: 0 });
+                        }
+                    } else {
+                        reply_property = NONE;
+                    }
+
+                    let notify = SelectionNotifyEvent {
+                        response_type: SELECTION_NOTIFY_EVENT,
+                        sequence: 0,
+                        time: req.time,
+                        requestor: req.requestor,
+                        selection: req.selection,
+                        target: req.target,
+                        property: reply_property,
+                    };
+                    conn.send_event(false, req.requestor, EventMask::NO_EVENT, notify).map_err(|e| e.to_string())?;
+                    conn.flush().map_err(|e| e.to_string())?;
+                }
+                Event::PropertyNotify(ev) if ev.state == Property::DELETE => {
+                    let Some(i) = transfers.iter().position(|t| t.requestor == ev.window && t.property == ev.atom) else { continue };
+                    let transfer = &mut transfers[i];
+                    let data = &offered[transfer.format].1;
+                    let end = (transfer.offset + max_len).min(data.len());
+                    // The empty chunk after the last one ends the transfer
+                    conn.change_property8(PropMode::REPLACE, transfer.requestor, transfer.property, transfer.target, &data[transfer.offset..end])
+                        .map_err(|e| e.to_string())?;
+                    conn.flush().map_err(|e| e.to_string())?;
+                    if transfer.offset == end {
+                        transfers.remove(i);
+                    } else {
+                        transfer.offset = end;
+                    }
+                }
+                Event::SelectionClear(ev) if ev.selection == atoms.clipboard && ev.owner == window => {
+                    if !transfers.is_empty() {
+                        warn!("Clipboard taken over with {} chunked transfers unfinished", transfers.len());
+                    }
+                    info!("Clipboard taken over by another application");
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn one_image_encoding_and_plain_text_variant_are_read() {
+        let offered = types(&["TARGETS", "TIMESTAMP", "image/bmp", "image/png", "image/jpeg", "text/plain", "text/plain;charset=utf-8", "UTF8_STRING", "text/html"]);
+        assert_eq!(formats_to_read(offered), types(&["image/png", "text/plain;charset=utf-8", "text/html"]));
+    }
+
+    #[test]
+    fn unusual_image_encodings_are_kept_when_alone() {
+        assert_eq!(formats_to_read(types(&["image/webp", "image/avif", "text/plain"])), types(&["image/webp", "text/plain"]));
+        assert_eq!(formats_to_read(types(&["text/rtf", "x-special/gnome-copied-files"])), types(&["text/rtf", "x-special/gnome-copied-files"]));
+    }
+}
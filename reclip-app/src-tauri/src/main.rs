@@ -3,14 +3,23 @@
 
 use arboard::Clipboard;
 use clap::Parser;
+use reclip_app_lib::cli;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// Copy content to clipboard
+    /// Copy content to clipboard, `-` reads it from stdin
     #[arg(long)]
     copy: Option<String>,
 
+    /// MIME type of the --copy content, e.g. text/html; PNG and JPEG images are detected
+    #[arg(long, requires = "copy")]
+    mime: Option<String>,
+
+    /// Tag for the copied clip in history, can be repeated
+    #[arg(long = "tag", requires = "copy")]
+    tags: Vec<String>,
+
     /// Print current clipboard content
     #[arg(long)]
     paste: bool,
@@ -18,28 +27,35 @@ struct Cli {
     /// Start application minimized to the system tray
     #[arg(long)]
     minimized: bool,
+
+    /// Print subcommand output as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Option<cli::Command>,
 }
 
 fn main() {
     let args = Cli::parse();
 
+    if let Some(command) = args.command {
+        if let Err(e) = cli::run(command, args.json) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
     // Handle CLI commands
     if let Some(content) = args.copy {
-        match Clipboard::new() {
-            Ok(mut clipboard) => {
-                if let Err(e) = clipboard.set_text(content) {
-                    eprintln!("Error copying to clipboard: {}", e);
-                    std::process::exit(1);
-                }
-                // We exit successfully after copying.
-                // The running ReClip instance (if any) will detect the change via its listener.
-                std::process::exit(0);
-            }
-            Err(e) => {
-                eprintln!("Failed to initialize clipboard: {}", e);
-                std::process::exit(1);
-            }
+        if let Err(e) = cli::copy(&content, args.mime.as_deref(), &args.tags) {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
+        // We exit successfully after copying.
+        // The copy is already in history; a running ReClip instance skips it when its listener sees the change.
+        std::process::exit(0);
     }
 
     if args.paste {
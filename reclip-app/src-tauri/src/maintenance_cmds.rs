@@ -1,10 +1,24 @@
 use tauri::{State, Manager};
 use crate::db::{self, DbState};
+use crate::blob_store::{self, GcReport};
+use crate::events::{publish, ClipEvent};
 use crate::tray;
 
 #[tauri::command]
-pub async fn run_maintenance(state: State<'_, DbState>, days: i64, max_clips: i64) -> Result<(), String> {
-    db::prune_clips(&state.pool, days, max_clips).await.map_err(|e| e.to_string())
+pub async fn run_maintenance(app: tauri::AppHandle, state: State<'_, DbState>, days: i64, max_clips: i64) -> Result<GcReport, String> {
+    let deleted = db::prune_clips(&state.pool, days, max_clips).await.map_err(|e| e.to_string())?;
+    for id in deleted {
+        publish(&app, ClipEvent::Deleted(id));
+    }
+    // Pruned clips leave their images behind, collect them
+    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    blob_store::gc(&state.pool, &app_dir).await
+}
+
+/// Differences between the database schema and the migrations, empty when there is no drift
+#[tauri::command]
+pub async fn check_schema(state: State<'_, DbState>) -> Result<Vec<String>, String> {
+    crate::schema::check_drift(&state.pool).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -18,6 +32,7 @@ pub async fn export_clips(app: tauri::AppHandle, export_path: String) -> Result<
     let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
     let db_path = app_dir.join("clips.db");
     let images_dir = app_dir.join("images");
+    let blobs_dir = blob_store::blobs_dir(&app_dir);
     
     let file = File::create(&export_path).map_err(|e| format!("Failed to create export file: {}", e))?;
     let mut zip = ZipWriter::new(file);
@@ -31,8 +46,11 @@ pub async fn export_clips(app: tauri::AppHandle, export_path: String) -> Result<
         zip.write_all(&db_contents).map_err(|e| e.to_string())?;
     }
     
-    if images_dir.exists() {
-        for entry in WalkDir::new(&images_dir).into_iter().filter_map(|e| e.ok()) {
+    for dir in [&images_dir, &blobs_dir] {
+        if !dir.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
             let path = entry.path();
             if path.is_file() {
                 let relative_path = path.strip_prefix(&app_dir).unwrap();
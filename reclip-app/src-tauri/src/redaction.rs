@@ -0,0 +1,201 @@
+use std::sync::{Arc, RwLock};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use log::warn;
+use regex::{NoExpand, Regex};
+use sqlx::{Pool, Sqlite};
+use crate::crypto::{self, KEY_LEN};
+use crate::db::{get_privacy_rules, PrivacyRule};
+use crate::secrets;
+
+pub const REGEX_MASK: &str = "REGEX_MASK";
+pub const REGEX_DROP: &str = "REGEX_DROP";
+pub const DEFAULT_PLACEHOLDER: &str = "[REDACTED]";
+
+/// What the regex privacy rules do with a clip
+#[derive(Debug, PartialEq)]
+pub enum Filtered {
+    /// No rule matched
+    Unchanged,
+    /// A `REGEX_DROP` rule matched, the clip must not be stored
+    Dropped { pattern: String },
+    /// `REGEX_MASK` rules replaced what they matched with their placeholder
+    Masked { text: String, keep_original: bool },
+}
+
+struct Mask {
+    pattern: Regex,
+    placeholder: String,
+    keep_original: bool,
+}
+
+/// The `REGEX_DROP` and `REGEX_MASK` rules, compiled
+#[derive(Default)]
+pub struct Redaction {
+    /// Source pattern and compiled regex
+    drops: Vec<(String, Regex)>,
+    masks: Vec<Mask>,
+}
+
+impl Redaction {
+    /// Compile the regex rules among `rules`, skipping patterns that don't compile
+    pub fn new(rules: &[PrivacyRule]) -> Self {
+        let mut redaction = Redaction::default();
+        for rule in rules.iter().filter(|r| r.rule_type == REGEX_DROP || r.rule_type == REGEX_MASK) {
+            let pattern = match Regex::new(&rule.value) {
+                Ok(pattern) => pattern,
+                Err(e) => {
+                    warn!("Skipping privacy rule '{}': {}", rule.value, e);
+                    continue;
+                }
+            };
+            if rule.rule_type == REGEX_DROP {
+                redaction.drops.push((rule.value.clone(), pattern));
+            } else {
+                redaction.masks.push(Mask {
+                    pattern,
+                    placeholder: rule.placeholder.clone().unwrap_or_else(|| DEFAULT_PLACEHOLDER.to_string()),
+                    keep_original: rule.keep_original,
+                });
+            }
+        }
+        redaction
+    }
+
+    /// Run the rules over a text clip, drop rules win over masks
+    pub fn filter(&self, text: &str) -> Filtered {
+        if let Some((pattern, _)) = self.drops.iter().find(|(_, re)| re.is_match(text)) {
+            return Filtered::Dropped { pattern: pattern.clone() };
+        }
+
+        let mut masked = text.to_string();
+        let mut matched = false;
+        let mut keep_original = false;
+        for mask in &self.masks {
+            if !mask.pattern.is_match(&masked) {
+                continue;
+            }
+            masked = mask.pattern.replace_all(&masked, NoExpand(&mask.placeholder)).into_owned();
+            matched = true;
+            keep_original |= mask.keep_original;
+        }
+
+        if matched {
+            Filtered::Masked { text: masked, keep_original }
+        } else {
+            Filtered::Unchanged
+        }
+    }
+}
+
+// Compiled rules, cleared when the `privacy_rules` table changes
+static COMPILED: RwLock<Option<Arc<Redaction>>> = RwLock::new(None);
+
+/// Call after changing the `privacy_rules` table
+pub fn invalidate() {
+    *COMPILED.write().unwrap() = None;
+}
+
+/// The compiled rules, read from the database when they changed since the last clip.
+/// A failed read is an error rather than "no rules", so clips aren't stored unmasked.
+pub async fn rules(pool: &Pool<Sqlite>) -> Result<Arc<Redaction>, String> {
+    if let Some(compiled) = COMPILED.read().unwrap().clone() {
+        return Ok(compiled);
+    }
+    let rules = get_privacy_rules(pool).await.map_err(|e| format!("Failed to load privacy rules: {}", e))?;
+    let compiled = Arc::new(Redaction::new(&rules));
+    *COMPILED.write().unwrap() = Some(compiled.clone());
+    Ok(compiled)
+}
+
+/// Key for kept originals, held in the secret store
+async fn stored_key(pool: &Pool<Sqlite>) -> Result<Option<[u8; KEY_LEN]>, String> {
+    let Some(encoded) = secrets::get(pool, secrets::REDACTION_KEY).await? else { return Ok(None) };
+    BASE64.decode(encoded).ok()
+        .and_then(|k| k.try_into().ok())
+        .map(Some)
+        .ok_or_else(|| "Invalid redaction key".to_string())
+}
+
+/// The stored key, created on first use
+async fn key(pool: &Pool<Sqlite>) -> Result<[u8; KEY_LEN], String> {
+    if let Some(key) = stored_key(pool).await? {
+        return Ok(key);
+    }
+    if !secrets::is_persistent() {
+        warn!("Credentials are only kept in memory, kept originals can't be revealed after a restart");
+    }
+    let key = crypto::random_bytes::<KEY_LEN>()?;
+    secrets::set(pool, secrets::REDACTION_KEY, &BASE64.encode(key)).await?;
+    Ok(key)
+}
+
+/// Encrypt the unmasked text of a clip for `reveal`
+pub async fn seal_original(pool: &Pool<Sqlite>, original: &str) -> Result<String, String> {
+    let key = key(pool).await?;
+    Ok(BASE64.encode(crypto::encrypt(original.as_bytes(), &key)?))
+}
+
+pub async fn reveal(pool: &Pool<Sqlite>, sealed: &str) -> Result<String, String> {
+    let key = stored_key(pool).await?.ok_or("The redaction key is not available, unlock credentials first")?;
+    let sealed = BASE64.decode(sealed).map_err(|e| e.to_string())?;
+    let plain = crypto::decrypt(&sealed, &key)
+        .map_err(|_| "The original can't be decrypted, the redaction key has changed".to_string())?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rule_type: &str, value: &str, placeholder: Option<&str>, keep_original: bool) -> PrivacyRule {
+        PrivacyRule {
+            id: 0,
+            rule_type: rule_type.to_string(),
+            value: value.to_string(),
+            is_active: true,
+            placeholder: placeholder.map(str::to_string),
+            keep_original,
+            match_kind: "substring".to_string(),
+            capture: "all".to_string(),
+            mark_sensitive: false,
+            delete_after: None,
+            tags: None,
+            strip_formatting: false,
+        }
+    }
+
+    #[test]
+    fn drop_beats_mask() {
+        let redaction = Redaction::new(&[
+            rule(REGEX_MASK, r"\d{4}", None, false),
+            rule(REGEX_DROP, "secret", None, false),
+        ]);
+        assert_eq!(redaction.filter("secret pin 1234"), Filtered::Dropped { pattern: "secret".to_string() });
+        assert_eq!(redaction.filter("pin 1234"), Filtered::Masked { text: "pin [REDACTED]".to_string(), keep_original: false });
+        assert_eq!(redaction.filter("nothing here"), Filtered::Unchanged);
+    }
+
+    #[test]
+    fn placeholders_are_not_expanded() {
+        let redaction = Redaction::new(&[rule(REGEX_MASK, r"(?P<user>\w+)@example\.com", Some("$user at $1"), false)]);
+        assert_eq!(
+            redaction.filter("mail bob@example.com"),
+            Filtered::Masked { text: "mail $user at $1".to_string(), keep_original: false },
+        );
+    }
+
+    #[test]
+    fn any_matching_mask_keeps_the_original() {
+        let redaction = Redaction::new(&[
+            rule(REGEX_MASK, r"\d{4}", Some("####"), false),
+            rule(REGEX_MASK, "hunter2", Some("***"), true),
+            // Doesn't compile, skipped
+            rule(REGEX_MASK, "(", None, true),
+        ]);
+        assert_eq!(redaction.filter("pin 1234"), Filtered::Masked { text: "pin ####".to_string(), keep_original: false });
+        assert_eq!(
+            redaction.filter("pin 1234 password hunter2"),
+            Filtered::Masked { text: "pin #### password ***".to_string(), keep_original: true },
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, BTreeSet};
+use sqlx::migrate::Migrator;
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite, Row};
+use log::{info, warn};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Migration that took over the tables init_db used to create by hand
+const LEGACY_TABLES_MIGRATION: i64 = 20260119000000;
+
+/// Columns init_db used to add with `ALTER TABLE`, ignoring the error when they already existed
+const LEGACY_COLUMNS: &[(&str, &str, &str)] = &[
+    ("notes", "title", "TEXT DEFAULT ''"),
+    ("notes", "is_pinned", "BOOLEAN NOT NULL DEFAULT 0"),
+    ("notes", "color", "TEXT DEFAULT NULL"),
+    ("notes", "is_archived", "BOOLEAN NOT NULL DEFAULT 0"),
+    ("notes", "tags", "TEXT DEFAULT ''"),
+    ("notes", "position", "INTEGER DEFAULT 0"),
+    ("reminders", "position", "INTEGER DEFAULT 0"),
+    ("alarms", "position", "INTEGER DEFAULT 0"),
+];
+
+/// Column name -> declared type, per table
+type TableColumns = BTreeMap<String, BTreeMap<String, String>>;
+
+async fn table_columns(pool: &Pool<Sqlite>) -> Result<TableColumns, sqlx::Error> {
+    let tables: Vec<String> = sqlx::query("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+
+    let mut schema = TableColumns::new();
+    for table in tables {
+        let columns = sqlx::query("SELECT name, type FROM pragma_table_info(?)")
+            .bind(&table)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|r| (r.get::<String, _>(0), r.get::<String, _>(1).to_uppercase()))
+            .collect();
+        schema.insert(table, columns);
+    }
+    Ok(schema)
+}
+
+/// Bring tables created by older versions up to the shape the migrations expect.
+/// Runs once, before the migration that now creates these tables is recorded.
+pub async fn upgrade_legacy_tables(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let has_migrations_table: bool = sqlx::query_scalar("SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'")
+        .fetch_one(pool)
+        .await?;
+    if has_migrations_table {
+        let applied: bool = sqlx::query_scalar("SELECT COUNT(*) > 0 FROM _sqlx_migrations WHERE version = ?")
+            .bind(LEGACY_TABLES_MIGRATION)
+            .fetch_one(pool)
+            .await?;
+        if applied {
+            return Ok(());
+        }
+    }
+
+    let schema = table_columns(pool).await?;
+    for (table, column, definition) in LEGACY_COLUMNS {
+        // Fresh installs get the whole table from the migration
+        let Some(columns) = schema.get(*table) else { continue };
+        if !columns.contains_key(*column) {
+            info!("Adding missing column {}.{}", table, column);
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Compare the live schema with what the migrations produce on an empty database.
+/// Returns one line per difference, empty when they match.
+pub async fn check_drift(pool: &Pool<Sqlite>) -> Result<Vec<String>, sqlx::Error> {
+    let reference = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    MIGRATOR.run(&reference).await?;
+    let expected = table_columns(&reference).await?;
+    reference.close().await;
+
+    let actual = table_columns(pool).await?;
+    let mut drift = Vec::new();
+    for (table, columns) in &expected {
+        let Some(live) = actual.get(table) else {
+            drift.push(format!("missing table {}", table));
+            continue;
+        };
+        for (column, type_) in columns {
+            match live.get(column) {
+                None => drift.push(format!("missing column {}.{}", table, column)),
+                Some(live_type) if live_type != type_ => {
+                    drift.push(format!("column {}.{} is {}, expected {}", table, column, live_type, type_))
+                }
+                _ => {}
+            }
+        }
+        let known: BTreeSet<&String> = columns.keys().collect();
+        for column in live.keys().filter(|c| !known.contains(c)) {
+            drift.push(format!("unexpected column {}.{}", table, column));
+        }
+    }
+    for table in actual.keys().filter(|t| !expected.contains_key(*t)) {
+        drift.push(format!("unexpected table {}", table));
+    }
+
+    for line in &drift {
+        warn!("Schema drift: {}", line);
+    }
+    Ok(drift)
+}
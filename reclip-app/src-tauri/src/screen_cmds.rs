@@ -1,73 +1,120 @@
 use screenshots::Screen;
-use tauri::{AppHandle, Manager, Emitter};
-use base64::Engine;
+use tauri::{AppHandle, Manager};
 use std::io::Cursor;
-use crate::db::{DbState, insert_clip};
+use log::warn;
+use crate::db::{self, DbState, insert_clip};
+use crate::blob_store;
+use crate::events::{self, ClipEvent};
+use crate::ocr;
+
+#[derive(Debug, serde::Serialize)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub is_primary: bool,
+}
 
 #[tauri::command]
-pub async fn capture_full_screen(app: AppHandle) -> Result<String, String> {
+pub fn list_monitors() -> Result<Vec<MonitorInfo>, String> {
     let screens = Screen::all().map_err(|e| format!("Failed to get screens: {}", e))?;
-    
-    if let Some(screen) = screens.first() {
-        let image = screen.capture().map_err(|e| format!("Failed to capture screen: {}", e))?;
-        
-        // Encode to PNG
-        let mut buff = Cursor::new(Vec::new());
-        image.write_to(&mut buff, screenshots::image::ImageFormat::Png)
-            .map_err(|e| format!("Failed to encode image: {}", e))?;
-        let buffer = buff.into_inner();
-        
-        let base64_image = base64::engine::general_purpose::STANDARD.encode(&buffer);
-        let data_uri = format!("data:image/png;base64,{}", base64_image);
-        
-        // Generate a hash for the clip
-        let hash = blake3::hash(data_uri.as_bytes()).to_string();
-        
-        // Save to DB
-        let state = app.state::<DbState>();
-        let pool = &state.pool;
-        
-        insert_clip(pool, data_uri.clone(), "image".to_string(), hash, None, None).await
-            .map_err(|e: sqlx::Error| format!("Failed to save clip: {}", e))?;
-        
-        // Emit event to refresh UI
-        app.emit("clip-created", ()).map_err(|e: tauri::Error| e.to_string())?;
-        
-        Ok("Success".to_string())
-    } else {
-        Err("No screens found".to_string())
-    }
+    Ok(screens.iter().enumerate().map(|(index, screen)| {
+        let info = screen.display_info;
+        MonitorInfo {
+            index,
+            id: info.id,
+            x: info.x,
+            y: info.y,
+            width: info.width,
+            height: info.height,
+            scale_factor: info.scale_factor,
+            is_primary: info.is_primary,
+        }
+    }).collect())
 }
 
-#[tauri::command]
-pub async fn capture_region(app: AppHandle, x: i32, y: i32, width: u32, height: u32) -> Result<String, String> {
+/// Monitor by index into `list_monitors`, else the one containing the point, else the primary one
+fn select_screen(monitor: Option<usize>, point: Option<(i32, i32)>) -> Result<Screen, String> {
+    if let Some((x, y)) = point.filter(|_| monitor.is_none()) {
+        return Screen::from_point(x, y).map_err(|e| format!("No screen at {},{}: {}", x, y, e));
+    }
     let screens = Screen::all().map_err(|e| format!("Failed to get screens: {}", e))?;
-    
-    if let Some(screen) = screens.first() {
-        let image = screen.capture_area(x, y, width, height).map_err(|e| format!("Failed to capture area: {}", e))?;
-        
-        // Encode to PNG
-        let mut buff = Cursor::new(Vec::new());
-        image.write_to(&mut buff, screenshots::image::ImageFormat::Png)
-            .map_err(|e| format!("Failed to encode image: {}", e))?;
-        let buffer = buff.into_inner();
-        
-        let base64_image = base64::engine::general_purpose::STANDARD.encode(&buffer);
-        let data_uri = format!("data:image/png;base64,{}", base64_image);
-        
-        let hash = blake3::hash(data_uri.as_bytes()).to_string();
-        
-        // Save to DB
-        let state = app.state::<DbState>();
-        let pool = &state.pool;
-        
-        insert_clip(pool, data_uri.clone(), "image".to_string(), hash, None, None).await
-            .map_err(|e: sqlx::Error| format!("Failed to save clip: {}", e))?;
-        
-        app.emit("clip-created", ()).map_err(|e: tauri::Error| e.to_string())?;
-        
-        Ok("Success".to_string())
-    } else {
-        Err("No screens found".to_string())
+    let screen = match monitor {
+        Some(index) => screens.get(index).copied().ok_or_else(|| format!("No screen with index {}", index))?,
+        None => screens.iter().find(|s| s.display_info.is_primary).or(screens.first()).copied()
+            .ok_or_else(|| "No screens found".to_string())?,
+    };
+    Ok(screen)
+}
+
+async fn wait(delay_ms: Option<u64>) {
+    if let Some(ms) = delay_ms.filter(|ms| *ms > 0) {
+        tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
     }
 }
+
+/// Store a capture like a clipboard image, optionally with its recognized text for search
+async fn save_capture(app: &AppHandle, image: screenshots::image::RgbaImage, run_ocr: bool) -> Result<i64, String> {
+    // Encode to PNG
+    let mut buff = Cursor::new(Vec::new());
+    image.write_to(&mut buff, screenshots::image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    let buffer = buff.into_inner();
+
+    // Save to the blob store and DB
+    let state = app.state::<DbState>();
+    let pool = &state.pool;
+    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let blob = blob_store::put(pool, &app_dir, &buffer, "image/png").await?;
+
+    let id = insert_clip(pool, blob.path.clone(), "image".to_string(), blob.hash.clone(), None, None).await
+        .map_err(|e: sqlx::Error| format!("Failed to save clip: {}", e))?;
+    db::set_clip_blob(pool, id, &blob.hash).await
+        .map_err(|e: sqlx::Error| format!("Failed to save clip: {}", e))?;
+
+    if run_ocr {
+        // The capture is saved either way, a failed OCR pass only loses the text
+        match ocr::extract_text_from_image(&blob.path).await {
+            Ok(text) if !text.is_empty() => {
+                if let Err(e) = db::set_clip_ocr_text(pool, id, text).await {
+                    warn!("Failed to store OCR text of clip {}: {}", id, e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("OCR of screen capture failed: {}", e),
+        }
+    }
+
+    // Emit event to refresh UI
+    events::publish(app, ClipEvent::Created(id));
+    let _ = crate::tray::update_tray_history(app).await;
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn capture_full_screen(app: AppHandle, monitor: Option<usize>, x: Option<i32>, y: Option<i32>, delay_ms: Option<u64>, ocr: Option<bool>) -> Result<i64, String> {
+    let screen = select_screen(monitor, x.zip(y))?;
+    wait(delay_ms).await;
+
+    let image = screen.capture().map_err(|e| format!("Failed to capture screen: {}", e))?;
+    save_capture(&app, image, ocr.unwrap_or(false)).await
+}
+
+/// `x`/`y` are relative to `monitor` when given, otherwise desktop coordinates and the monitor is the one containing them
+#[tauri::command]
+pub async fn capture_region(app: AppHandle, x: i32, y: i32, width: u32, height: u32, monitor: Option<usize>, delay_ms: Option<u64>, ocr: Option<bool>) -> Result<i64, String> {
+    let screen = select_screen(monitor, Some((x, y)))?;
+    let (x, y) = match monitor {
+        Some(_) => (x, y),
+        None => (x - screen.display_info.x, y - screen.display_info.y),
+    };
+    wait(delay_ms).await;
+
+    let image = screen.capture_area(x, y, width, height).map_err(|e| format!("Failed to capture area: {}", e))?;
+    save_capture(&app, image, ocr.unwrap_or(false)).await
+}
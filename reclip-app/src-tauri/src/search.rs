@@ -0,0 +1,582 @@
+use crate::db::Clip;
+
+/// A single piece of a user search after tokenizing
+#[derive(Debug, Clone, PartialEq)]
+enum FtsToken {
+    Term(String),
+    Phrase(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+/// Quote a term for FTS5 so punctuation (`c++`, `foo.bar`, `-x`) is treated as text, not syntax
+fn quote_fts(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn tokenize_fts(input: &str) -> Vec<FtsToken> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            // Phrase: everything up to the closing quote (or end of input)
+            chars.next();
+            let mut phrase = String::new();
+            for ch in chars.by_ref() {
+                if ch == '"' {
+                    break;
+                }
+                phrase.push(ch);
+            }
+            if !phrase.trim().is_empty() {
+                tokens.push(FtsToken::Phrase(phrase.trim().to_string()));
+            }
+        } else if c == '(' {
+            chars.next();
+            tokens.push(FtsToken::Open);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(FtsToken::Close);
+        } else {
+            let mut word = String::new();
+            while let Some(&ch) = chars.peek() {
+                if ch.is_whitespace() || ch == '"' || ch == '(' || ch == ')' {
+                    break;
+                }
+                word.push(ch);
+                chars.next();
+            }
+            match word.as_str() {
+                "AND" => tokens.push(FtsToken::Op("AND")),
+                "OR" => tokens.push(FtsToken::Op("OR")),
+                "NOT" => tokens.push(FtsToken::Op("NOT")),
+                _ => {
+                    let text = word.trim_end_matches('*').to_string();
+                    if !text.is_empty() {
+                        tokens.push(FtsToken::Term(text));
+                    }
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Drop operators and parentheses that would make the expression invalid FTS5 syntax
+fn normalize_fts(tokens: Vec<FtsToken>) -> Vec<FtsToken> {
+    let is_operand_end = |t: &FtsToken| matches!(t, FtsToken::Term(_) | FtsToken::Phrase(_) | FtsToken::Close);
+    let is_operand_start = |t: &FtsToken| matches!(t, FtsToken::Term(_) | FtsToken::Phrase(_) | FtsToken::Open);
+
+    // Parentheses are only kept when they balance
+    let mut depth = 0i32;
+    let mut balanced = true;
+    for t in &tokens {
+        match t {
+            FtsToken::Open => depth += 1,
+            FtsToken::Close => {
+                depth -= 1;
+                if depth < 0 {
+                    balanced = false;
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut tokens: Vec<FtsToken> = if balanced && depth == 0 {
+        tokens
+    } else {
+        tokens.into_iter().filter(|t| !matches!(t, FtsToken::Open | FtsToken::Close)).collect()
+    };
+
+    // Repeat until stable: removing one token can expose another dangling operator or empty group
+    loop {
+        let mut out: Vec<FtsToken> = Vec::with_capacity(tokens.len());
+        for (i, t) in tokens.iter().enumerate() {
+            let next = tokens.get(i + 1);
+            match t {
+                FtsToken::Op(_) => {
+                    let prev_ok = out.last().map(is_operand_end).unwrap_or(false);
+                    let next_ok = next.map(is_operand_start).unwrap_or(false);
+                    if prev_ok && next_ok {
+                        out.push(t.clone());
+                    }
+                }
+                FtsToken::Open if next == Some(&FtsToken::Close) => {}
+                // The empty group's `(` was skipped above
+                FtsToken::Close if i > 0 && tokens[i - 1] == FtsToken::Open => {}
+                FtsToken::Close if out.last() == Some(&FtsToken::Open) => {
+                    out.pop();
+                }
+                _ => {
+                    // Adjacent operands get an explicit AND
+                    if is_operand_start(t) && out.last().map(is_operand_end).unwrap_or(false) {
+                        out.push(FtsToken::Op("AND"));
+                    }
+                    out.push(t.clone());
+                }
+            }
+        }
+        if out == tokens {
+            return out;
+        }
+        tokens = out;
+    }
+}
+
+/// Convert free-text user input into a safe FTS5 MATCH expression.
+///
+/// Supported syntax:
+/// - bare words match as prefixes (`conf` finds `config`), a trailing `*` is accepted too
+/// - words containing punctuation are matched as exact token sequences
+/// - `"quoted text"` matches the exact phrase
+/// - `AND`, `OR`, `NOT` (uppercase) and parentheses combine terms
+///
+/// Returns `None` if the input contains nothing searchable.
+pub fn fts_match_expression(input: &str) -> Option<String> {
+    let tokens = normalize_fts(tokenize_fts(input));
+    if tokens.is_empty() {
+        return None;
+    }
+
+    let parts: Vec<String> = tokens
+        .iter()
+        .map(|t| match t {
+            // Plain words match as prefixes so search-as-you-type keeps working,
+            // words with punctuation (`c++`, `foo.bar`) are tokenized by FTS5 and must match exactly
+            FtsToken::Term(text) if text.chars().all(|c| c.is_alphanumeric() || c == '_') => format!("{}*", quote_fts(text)),
+            FtsToken::Term(text) => quote_fts(text),
+            FtsToken::Phrase(p) => quote_fts(p),
+            FtsToken::Op(op) => op.to_string(),
+            FtsToken::Open => "(".to_string(),
+            FtsToken::Close => ")".to_string(),
+        })
+        .collect();
+
+    Some(parts.join(" "))
+}
+
+/// Shorten text for result previews without splitting a UTF-8 character
+pub fn preview_text(content: &str, max_chars: usize) -> String {
+    if content.chars().count() > max_chars {
+        let cut: String = content.chars().take(max_chars.saturating_sub(3)).collect();
+        format!("{}…", cut)
+    } else {
+        content.to_string()
+    }
+}
+
+/// A problem in a structured clip query, with the offending span so the UI can underline it.
+/// `start`/`end` are UTF-16 offsets into the query string (what JS string indices use).
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct QueryError {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Error returned by the clip search commands
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchError {
+    Syntax(QueryError),
+    Database { message: String },
+}
+
+impl From<QueryError> for SearchError {
+    fn from(e: QueryError) -> Self {
+        SearchError::Syntax(e)
+    }
+}
+
+impl From<sqlx::Error> for SearchError {
+    fn from(e: sqlx::Error) -> Self {
+        SearchError::Database { message: e.to_string() }
+    }
+}
+
+/// A single `key:value` filter from a clip query
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipFilter {
+    Type(String),
+    App(String),
+    Tag(String),
+    Before(String),
+    After(String),
+    Pinned,
+    Favorite,
+    Sensitive,
+}
+
+/// Parsed clip query: free text for the FTS index plus structured filters.
+///
+/// Example: `type:image app:"Visual Studio Code" tag:#url after:2026-01-01 is:pinned -is:sensitive invoice`
+#[derive(Debug, Clone, Default)]
+pub struct ClipQuery {
+    /// Free text, matched against the FTS index
+    pub text: Option<String>,
+    /// Free text prefixed with `-`, excluded from results
+    pub excluded_text: Vec<String>,
+    /// Filters with their negation flag (`-key:value`)
+    pub filters: Vec<(bool, ClipFilter)>,
+    /// Lowercase words of the free text and of the excluded text, for matching decrypted clips in vault mode
+    pub words: Vec<String>,
+    pub excluded_words: Vec<String>,
+}
+
+/// Byte range of a raw token plus its parsed pieces
+struct QueryToken {
+    start: usize,
+    end: usize,
+    negated: bool,
+    key: Option<String>,
+    value: String,
+    /// Original text of the token, used when it is passed on as free text
+    raw: String,
+}
+
+fn utf16_offset(input: &str, byte_idx: usize) -> usize {
+    input[..byte_idx].encode_utf16().count()
+}
+
+fn query_error(input: &str, start: usize, end: usize, message: impl Into<String>) -> QueryError {
+    QueryError {
+        message: message.into(),
+        start: utf16_offset(input, start),
+        end: utf16_offset(input, end),
+    }
+}
+
+const FILTER_KEYS: [&str; 7] = ["type", "app", "tag", "before", "after", "is", "from"];
+
+/// Split a query into whitespace-separated tokens, keeping quoted sections together
+fn tokenize_query(input: &str) -> Result<Vec<QueryToken>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut iter = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = iter.peek() {
+        if c.is_whitespace() {
+            iter.next();
+            continue;
+        }
+
+        let mut end = start;
+        let mut in_quotes = false;
+        let mut quote_start = start;
+        while let Some(&(i, ch)) = iter.peek() {
+            if ch.is_whitespace() && !in_quotes {
+                break;
+            }
+            if ch == '"' {
+                if !in_quotes {
+                    quote_start = i;
+                }
+                in_quotes = !in_quotes;
+            }
+            end = i + ch.len_utf8();
+            iter.next();
+        }
+
+        let raw = &input[start..end];
+        let negated = raw.starts_with('-') && raw.len() > 1;
+        let body = if negated { &raw[1..] } else { raw };
+
+        let key = body
+            .split_once(':')
+            .map(|(k, _)| k.to_lowercase())
+            .filter(|k| FILTER_KEYS.contains(&k.as_str()));
+
+        if let Some(key) = key {
+            // Unterminated quotes are only an error inside a filter value, free text is lenient
+            if in_quotes {
+                return Err(query_error(input, quote_start, end, "Missing closing quote"));
+            }
+            let value = body[key.len() + 1..].trim_matches('"').to_string();
+            if value.trim().is_empty() {
+                return Err(query_error(input, start, end, format!("Missing value for '{}:'", key)));
+            }
+            tokens.push(QueryToken { start, end, negated, key: Some(key), value, raw: raw.to_string() });
+        } else {
+            tokens.push(QueryToken { start, end, negated, key: None, value: body.to_string(), raw: raw.to_string() });
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Accepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM[:SS]` (with `T` or space) and `today`/`yesterday`,
+/// returning the value in the `created_at` storage format
+fn parse_query_date(value: &str) -> Option<String> {
+    use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+
+    match value.to_lowercase().as_str() {
+        "today" => return Some(Utc::now().format("%Y-%m-%d").to_string()),
+        "yesterday" => return Some((Utc::now() - Duration::days(1)).format("%Y-%m-%d").to_string()),
+        _ => {}
+    }
+
+    if let Ok(d) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(d.format("%Y-%m-%d").to_string());
+    }
+    for fmt in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, fmt) {
+            return Some(dt.format("%Y-%m-%d %H:%M:%S").to_string());
+        }
+    }
+    None
+}
+
+/// Parse a clip search string with `type:`, `app:`, `tag:`, `before:`, `after:` and `is:` filters.
+/// Anything that isn't a known filter is free text for the full-text index.
+pub fn parse_clip_query(input: &str) -> Result<ClipQuery, QueryError> {
+    let mut query = ClipQuery::default();
+    let mut text_parts: Vec<String> = Vec::new();
+
+    for token in tokenize_query(input)? {
+        let key = match token.key {
+            Some(k) => k,
+            None => {
+                // `-word` excludes, everything else (including AND/OR/quotes) is passed on as-is
+                if token.negated {
+                    if let Some(expr) = fts_match_expression(&token.value) {
+                        query.excluded_text.push(expr);
+                    }
+                    query.excluded_words.extend(plain_words(&token.value));
+                } else {
+                    query.words.extend(plain_words(&token.value));
+                    text_parts.push(token.raw);
+                }
+                continue;
+            }
+        };
+
+        let value = token.value;
+        let filter = match key.as_str() {
+            "type" => ClipFilter::Type(value.to_lowercase()),
+            "app" | "from" => ClipFilter::App(value),
+            "tag" => {
+                let tag = value.trim_start_matches('#');
+                ClipFilter::Tag(format!("#{}", tag.to_lowercase()))
+            }
+            "before" | "after" => {
+                let date = parse_query_date(&value).ok_or_else(|| {
+                    query_error(input, token.start, token.end, format!("Invalid date '{}', expected YYYY-MM-DD", value))
+                })?;
+                if key == "before" { ClipFilter::Before(date) } else { ClipFilter::After(date) }
+            }
+            "is" => match value.to_lowercase().as_str() {
+                "pinned" => ClipFilter::Pinned,
+                "favorite" | "favourite" | "fav" => ClipFilter::Favorite,
+                "sensitive" => ClipFilter::Sensitive,
+                other => {
+                    return Err(query_error(
+                        input,
+                        token.start,
+                        token.end,
+                        format!("Unknown value 'is:{}', expected pinned, favorite or sensitive", other),
+                    ))
+                }
+            },
+            _ => unreachable!("filter keys are checked in tokenize_query"),
+        };
+        query.filters.push((token.negated, filter));
+    }
+
+    query.text = fts_match_expression(&text_parts.join(" "));
+    Ok(query)
+}
+
+/// Words of free text without operators, quotes and parentheses
+fn plain_words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .filter(|w| !matches!(*w, "AND" | "OR" | "NOT"))
+        .map(|w| w.trim_matches(|c| c == '"' || c == '(' || c == ')').to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// SQL condition for a `type` value, shared by `type:` filters and the UI type chips.
+/// Returns the condition over the `c` alias and an optional bind value.
+pub fn type_condition(type_name: &str) -> (String, Option<String>) {
+    match type_name {
+        "links" | "link" | "url" => (
+            "(c.type = 'text' AND (c.content LIKE 'http://%' OR c.content LIKE 'https://%'))".to_string(),
+            None,
+        ),
+        "colors" | "color" => (
+            "(c.type = 'text' AND (c.content LIKE '#%' OR c.content LIKE 'rgb(%' OR c.content LIKE 'hsl(%'))".to_string(),
+            None,
+        ),
+        other => ("c.type = ?".to_string(), Some(other.to_string())),
+    }
+}
+
+/// `type_condition` evaluated on a decrypted clip
+pub fn type_matches(type_name: &str, clip: &Clip) -> bool {
+    let content = clip.content.to_lowercase();
+    match type_name {
+        "links" | "link" | "url" => clip.type_ == "text" && (content.starts_with("http://") || content.starts_with("https://")),
+        "colors" | "color" => clip.type_ == "text" && (content.starts_with('#') || content.starts_with("rgb(") || content.starts_with("hsl(")),
+        other => clip.type_ == other,
+    }
+}
+
+impl ClipQuery {
+    /// The query evaluated on a decrypted clip, for vault mode. Free text matches when the content
+    /// or tags contain every word, `OR` and phrases are not supported.
+    pub fn matches(&self, clip: &Clip) -> bool {
+        let text = format!("{} {} {}", clip.content, clip.tags.as_deref().unwrap_or_default(), clip.ocr_text.as_deref().unwrap_or_default()).to_lowercase();
+        if !self.words.iter().all(|w| text.contains(w.as_str())) || self.excluded_words.iter().any(|w| text.contains(w.as_str())) {
+            return false;
+        }
+
+        self.filters.iter().all(|(negated, filter)| {
+            let matched = match filter {
+                ClipFilter::Type(t) => type_matches(t, clip),
+                ClipFilter::App(app) => clip.sender_app.as_ref().is_some_and(|s| s.to_lowercase().contains(&app.to_lowercase())),
+                ClipFilter::Tag(tag) => clip.tags.as_ref().is_some_and(|t| t.to_lowercase().contains(&format!("\"{}\"", tag))),
+                ClipFilter::Before(date) => clip.created_at.as_str() < date.as_str(),
+                ClipFilter::After(date) => clip.created_at.as_str() >= date.as_str(),
+                ClipFilter::Pinned => clip.pinned,
+                ClipFilter::Favorite => clip.favorite,
+                ClipFilter::Sensitive => clip.sensitive,
+            };
+            matched != *negated
+        })
+    }
+
+    /// Compile the filters into parameterized SQL conditions over the `clips` table aliased as `c`.
+    /// Free text is not included, the caller joins `clips_fts` for it.
+    pub fn sql_conditions(&self) -> (Vec<String>, Vec<String>) {
+        let mut conditions = Vec::new();
+        let mut binds = Vec::new();
+
+        for (negated, filter) in &self.filters {
+            let condition = match filter {
+                ClipFilter::Type(t) => {
+                    let (cond, bind) = type_condition(t);
+                    binds.extend(bind);
+                    cond
+                }
+                ClipFilter::App(app) => {
+                    binds.push(app.to_lowercase());
+                    "instr(lower(c.sender_app), ?) > 0".to_string()
+                }
+                ClipFilter::Tag(tag) => {
+                    // Tags are stored as a JSON array, match the quoted tag so #url doesn't match #urls
+                    binds.push(format!("\"{}\"", tag));
+                    "instr(lower(c.tags), ?) > 0".to_string()
+                }
+                ClipFilter::Before(date) => {
+                    binds.push(date.clone());
+                    "c.created_at < ?".to_string()
+                }
+                ClipFilter::After(date) => {
+                    binds.push(date.clone());
+                    "c.created_at >= ?".to_string()
+                }
+                ClipFilter::Pinned => "c.pinned = 1".to_string(),
+                ClipFilter::Favorite => "c.favorite = 1".to_string(),
+                ClipFilter::Sensitive => "c.sensitive = 1".to_string(),
+            };
+
+            if *negated {
+                // NULL columns (no sender app, no tags) count as "not matching"
+                conditions.push(format!("NOT COALESCE({}, 0)", condition));
+            } else {
+                conditions.push(condition);
+            }
+        }
+
+        for expr in &self.excluded_text {
+            conditions.push("c.id NOT IN (SELECT rowid FROM clips_fts WHERE clips_fts MATCH ?)".to_string());
+            binds.push(expr.clone());
+        }
+
+        (conditions, binds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_query_has_no_text_or_filters() {
+        for input in ["", "   "] {
+            let query = parse_clip_query(input).unwrap();
+            assert_eq!(query.text, None);
+            assert!(query.filters.is_empty());
+            assert!(query.excluded_text.is_empty());
+        }
+        assert_eq!(fts_match_expression("AND ( )"), None);
+    }
+
+    #[test]
+    fn phrases_match_exactly() {
+        let query = parse_clip_query(r#""hello world" conf"#).unwrap();
+        assert_eq!(query.text.as_deref(), Some(r#""hello world" AND "conf"*"#));
+        assert_eq!(query.words, ["hello", "world", "conf"]);
+
+        // Quoted filter values keep their spaces
+        let query = parse_clip_query(r#"app:"Visual Studio Code""#).unwrap();
+        assert_eq!(query.filters, [(false, ClipFilter::App("Visual Studio Code".to_string()))]);
+    }
+
+    #[test]
+    fn minus_negates_words_and_filters() {
+        let query = parse_clip_query("invoice -draft -is:sensitive type:image").unwrap();
+        assert_eq!(query.text.as_deref(), Some(r#""invoice"*"#));
+        assert_eq!(query.excluded_text, [r#""draft"*"#]);
+        assert_eq!(query.excluded_words, ["draft"]);
+        assert_eq!(query.filters, [(true, ClipFilter::Sensitive), (false, ClipFilter::Type("image".to_string()))]);
+
+        let (conditions, binds) = query.sql_conditions();
+        assert_eq!(conditions[0], "NOT COALESCE(c.sensitive = 1, 0)");
+        assert!(conditions[2].contains("clips_fts MATCH ?"));
+        assert_eq!(binds, ["image", r#""draft"*"#]);
+    }
+
+    #[test]
+    fn filter_values_are_normalized() {
+        let query = parse_clip_query("tag:URL from:code after:2026-01-02 before:2026-01-03T10:00").unwrap();
+        assert_eq!(query.filters, [
+            (false, ClipFilter::Tag("#url".to_string())),
+            (false, ClipFilter::App("code".to_string())),
+            (false, ClipFilter::After("2026-01-02".to_string())),
+            (false, ClipFilter::Before("2026-01-03 10:00:00".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn unknown_keys_are_free_text() {
+        let query = parse_clip_query("foo:bar").unwrap();
+        assert!(query.filters.is_empty());
+        assert_eq!(query.text.as_deref(), Some(r#""foo:bar""#));
+    }
+
+    #[test]
+    fn bad_filters_report_their_span() {
+        let err = parse_clip_query("notes is:archived").unwrap_err();
+        assert!(err.message.contains("is:archived"));
+        assert_eq!((err.start, err.end), (6, 17));
+
+        let err = parse_clip_query("type:").unwrap_err();
+        assert_eq!(err.message, "Missing value for 'type:'");
+
+        let err = parse_clip_query("after:yesterdayish").unwrap_err();
+        assert!(err.message.starts_with("Invalid date"));
+
+        let err = parse_clip_query(r#"app:"Visual Studio"#).unwrap_err();
+        assert_eq!(err.message, "Missing closing quote");
+        assert_eq!((err.start, err.end), (4, 18));
+
+        // Offsets are UTF-16 so the UI can underline after non-ASCII text
+        let err = parse_clip_query("日本 is:x").unwrap_err();
+        assert_eq!((err.start, err.end), (3, 7));
+    }
+}
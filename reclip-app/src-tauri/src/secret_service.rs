@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, Proxy};
+use crate::secrets::SecretStore;
+
+// freedesktop Secret Service, provided by GNOME Keyring, KWallet and KeePassXC
+const SERVICE: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+const APPLICATION: &str = "reclip";
+
+// (session, parameters, value, content type)
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+/// Credentials as items of the default keyring, found by their `application` and `key` attributes
+pub struct SecretServiceStore {
+    conn: Connection,
+    session: OwnedObjectPath,
+}
+
+impl SecretServiceStore {
+    pub async fn connect() -> Result<Self, String> {
+        let conn = Connection::session().await.map_err(|e| e.to_string())?;
+        let service = proxy(&conn, SERVICE_PATH, "org.freedesktop.Secret.Service").await?;
+        // The session bus is local to the user, secrets travel unencrypted over it like with secret-tool
+        let (_, session): (OwnedValue, OwnedObjectPath) = service
+            .call("OpenSession", &("plain", Value::from("")))
+            .await
+            .map_err(|e| format!("Secret Service not available: {}", e))?;
+        Ok(SecretServiceStore { conn, session })
+    }
+
+    async fn service(&self) -> Result<Proxy<'static>, String> {
+        proxy(&self.conn, SERVICE_PATH, "org.freedesktop.Secret.Service").await
+    }
+
+    async fn find(&self, key: &str) -> Result<Option<OwnedObjectPath>, String> {
+        let (unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = self
+            .service()
+            .await?
+            .call("SearchItems", &(attributes(key),))
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(item) = unlocked.into_iter().next() {
+            return Ok(Some(item));
+        }
+        match locked.into_iter().next() {
+            Some(item) => {
+                self.unlock(vec![item.as_ref()]).await?;
+                Ok(Some(item))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Unlock keyring objects, letting the keyring ask for its password when needed
+    async fn unlock(&self, objects: Vec<ObjectPath<'_>>) -> Result<(), String> {
+        let (_, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) = self
+            .service()
+            .await?
+            .call("Unlock", &(objects,))
+            .await
+            .map_err(|e| e.to_string())?;
+        self.prompt(prompt).await
+    }
+
+    async fn prompt(&self, prompt: OwnedObjectPath) -> Result<(), String> {
+        if prompt.as_str() == "/" {
+            return Ok(());
+        }
+        let prompt = proxy(&self.conn, prompt.as_str(), "org.freedesktop.Secret.Prompt").await?;
+        let mut completed = prompt.receive_signal("Completed").await.map_err(|e| e.to_string())?;
+        prompt.call_method("Prompt", &("",)).await.map_err(|e| e.to_string())?;
+        let message = completed.next().await.ok_or("The keyring prompt went away")?;
+        let (dismissed, _): (bool, OwnedValue) = message.body().deserialize().map_err(|e| e.to_string())?;
+        if dismissed {
+            return Err("The keyring was not unlocked".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SecretStore for SecretServiceStore {
+    fn kind(&self) -> &'static str {
+        "secret-service"
+    }
+
+    fn persistent(&self) -> bool {
+        true
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let Some(item) = self.find(key).await? else { return Ok(None) };
+        let item = proxy(&self.conn, item.as_str(), "org.freedesktop.Secret.Item").await?;
+        let (_, _, value, _): Secret = item.call("GetSecret", &(&self.session,)).await.map_err(|e| e.to_string())?;
+        String::from_utf8(value).map(Some).map_err(|e| e.to_string())
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        let collection_path = ObjectPath::from_static_str(DEFAULT_COLLECTION).map_err(|e| e.to_string())?;
+        self.unlock(vec![collection_path]).await?;
+        let collection = proxy(&self.conn, DEFAULT_COLLECTION, "org.freedesktop.Secret.Collection").await?;
+
+        let mut properties: HashMap<&str, Value> = HashMap::new();
+        properties.insert("org.freedesktop.Secret.Item.Label", Value::from(format!("ReClip {}", key)));
+        properties.insert("org.freedesktop.Secret.Item.Attributes", Value::from(attributes(key)));
+        let secret: Secret = (self.session.clone(), Vec::new(), value.as_bytes().to_vec(), "text/plain".to_string());
+        let (_, prompt): (OwnedObjectPath, OwnedObjectPath) = collection
+            .call("CreateItem", &(properties, secret, true))
+            .await
+            .map_err(|e| e.to_string())?;
+        self.prompt(prompt).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        let Some(item) = self.find(key).await? else { return Ok(()) };
+        let item = proxy(&self.conn, item.as_str(), "org.freedesktop.Secret.Item").await?;
+        let prompt: OwnedObjectPath = item.call("Delete", &()).await.map_err(|e| e.to_string())?;
+        self.prompt(prompt).await
+    }
+}
+
+async fn proxy(conn: &Connection, path: &str, interface: &'static str) -> Result<Proxy<'static>, String> {
+    Proxy::new(conn, SERVICE, path.to_string(), interface).await.map_err(|e| e.to_string())
+}
+
+fn attributes(key: &str) -> HashMap<String, String> {
+    HashMap::from([
+        ("application".to_string(), APPLICATION.to_string()),
+        ("key".to_string(), key.to_string()),
+    ])
+}
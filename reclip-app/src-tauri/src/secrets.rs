@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use crate::crypto::{self, KdfParams, KEY_LEN};
+use crate::db::get_setting;
+
+pub const DRIVE_CLIENT_SECRET: &str = "drive_client_secret";
+pub const DRIVE_REFRESH_TOKEN: &str = "drive_refresh_token";
+pub const SYNC_WEBDAV_PASSWORD: &str = "sync_webdav_password";
+pub const SYNC_S3_SECRET_KEY: &str = "sync_s3_secret_key";
+pub const SYNC_ENCRYPTION_PASSWORD: &str = "sync_encryption_password";
+pub const REDACTION_KEY: &str = "redaction_key";
+
+// Earlier versions kept these in the settings table, under the same keys
+const LEGACY_SETTINGS: [&str; 5] = [DRIVE_CLIENT_SECRET, DRIVE_REFRESH_TOKEN, SYNC_WEBDAV_PASSWORD, SYNC_S3_SECRET_KEY, SYNC_ENCRYPTION_PASSWORD];
+
+const FILE_NAME: &str = "secrets.json";
+const FILE_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+/// Where credentials live. Keys are the constants above.
+#[async_trait]
+pub trait SecretStore: Send + Sync {
+    /// "secret-service", "file" or "memory"
+    fn kind(&self) -> &'static str;
+    /// Whether secrets survive a restart
+    fn persistent(&self) -> bool;
+    async fn get(&self, key: &str) -> Result<Option<String>, String>;
+    async fn set(&self, key: &str, value: &str) -> Result<(), String>;
+    async fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// Keeps secrets until the process exits
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryStore {
+    fn drain(&self) -> HashMap<String, String> {
+        std::mem::take(&mut *self.entries.lock().unwrap())
+    }
+}
+
+#[async_trait]
+impl SecretStore for MemoryStore {
+    fn kind(&self) -> &'static str {
+        "memory"
+    }
+
+    fn persistent(&self) -> bool {
+        false
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        self.entries.lock().unwrap().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SecretFile {
+    version: u32,
+    kdf: KdfParams,
+    salt: String,
+    /// The JSON map of secrets, encrypted with the key derived from the master password
+    data: String,
+}
+
+/// A file in the app data folder encrypted with a master password, for systems without a keyring
+pub struct FileStore {
+    path: PathBuf,
+    kdf: KdfParams,
+    salt: [u8; SALT_LEN],
+    key: [u8; KEY_LEN],
+    entries: Mutex<HashMap<String, String>>,
+}
+
+impl FileStore {
+    /// Open the file with the master password, or create it when there is none yet
+    pub fn open(path: &Path, password: &str) -> Result<Self, String> {
+        if !path.exists() {
+            if password.is_empty() {
+                return Err("Choose a master password".to_string());
+            }
+            let kdf = KdfParams::default();
+            let salt = crypto::random_bytes::<SALT_LEN>()?;
+            let key = crypto::derive_key(password, &salt, &kdf)?;
+            let store = FileStore { path: path.to_path_buf(), kdf, salt, key, entries: Mutex::new(HashMap::new()) };
+            store.write(&HashMap::new())?;
+            return Ok(store);
+        }
+
+        let file: SecretFile = serde_json::from_slice(&fs::read(path).map_err(|e| e.to_string())?)
+            .map_err(|e| format!("Invalid secrets file: {}", e))?;
+        if file.version > FILE_VERSION {
+            return Err("The secrets file was written by a newer version of ReClip".to_string());
+        }
+        let salt: [u8; SALT_LEN] = BASE64.decode(&file.salt).ok()
+            .and_then(|s| s.try_into().ok())
+            .ok_or("Invalid secrets file: bad salt")?;
+        let key = crypto::derive_key(password, &salt, &file.kdf)?;
+        let sealed = BASE64.decode(&file.data).map_err(|e| format!("Invalid secrets file: {}", e))?;
+        let plain = crypto::decrypt(&sealed, &key).map_err(|_| "Wrong master password".to_string())?;
+        let entries = serde_json::from_slice(&plain).map_err(|e| format!("Invalid secrets file: {}", e))?;
+        Ok(FileStore { path: path.to_path_buf(), kdf: file.kdf, salt, key, entries: Mutex::new(entries) })
+    }
+
+    fn write(&self, entries: &HashMap<String, String>) -> Result<(), String> {
+        let plain = serde_json::to_vec(entries).map_err(|e| e.to_string())?;
+        let file = SecretFile {
+            version: FILE_VERSION,
+            kdf: self.kdf,
+            salt: BASE64.encode(self.salt),
+            data: BASE64.encode(crypto::encrypt(&plain, &self.key)?),
+        };
+        // Replace in one step so a crash can't leave half a file
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, &self.path).map_err(|e| e.to_string())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut HashMap<String, String>)) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut updated = entries.clone();
+        change(&mut updated);
+        self.write(&updated)?;
+        *entries = updated;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SecretStore for FileStore {
+    fn kind(&self) -> &'static str {
+        "file"
+    }
+
+    fn persistent(&self) -> bool {
+        true
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        self.update(|entries| {
+            entries.insert(key.to_string(), value.to_string());
+        })
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.update(|entries| {
+            entries.remove(key);
+        })
+    }
+}
+
+// Holds credentials saved before the secrets file is unlocked, they move into the file on unlock
+static SESSION: LazyLock<Arc<MemoryStore>> = LazyLock::new(Default::default);
+static STORE: LazyLock<RwLock<Arc<dyn SecretStore>>> = LazyLock::new(|| RwLock::new(SESSION.clone()));
+
+fn store() -> Arc<dyn SecretStore> {
+    STORE.read().unwrap().clone()
+}
+
+/// Replace the active store, also used to run against a `MemoryStore`
+pub fn install(store: Arc<dyn SecretStore>) {
+    *STORE.write().unwrap() = store;
+}
+
+/// Whether secrets saved now survive a restart
+pub fn is_persistent() -> bool {
+    store().persistent()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretStoreStatus {
+    pub kind: &'static str,
+    pub persistent: bool,
+    /// Whether a secrets file exists to unlock, otherwise unlocking creates one
+    pub file_exists: bool,
+}
+
+pub fn status(app_dir: &Path) -> SecretStoreStatus {
+    let store = store();
+    SecretStoreStatus {
+        kind: store.kind(),
+        persistent: store.persistent(),
+        file_exists: app_dir.join(FILE_NAME).exists(),
+    }
+}
+
+/// Use the system keyring when there is one, otherwise secrets stay in memory until the file is unlocked
+pub async fn init(pool: &Pool<Sqlite>) {
+    #[cfg(target_os = "linux")]
+    match crate::secret_service::SecretServiceStore::connect().await {
+        Ok(keyring) => install(Arc::new(keyring)),
+        Err(e) => info!("No keyring, credentials need the master password: {}", e),
+    }
+    if let Err(e) = migrate(pool, &*store()).await {
+        warn!("Failed to move credentials out of the settings table: {}", e);
+    }
+}
+
+/// Open the secrets file with the master password, creating it on first use
+pub async fn unlock(pool: &Pool<Sqlite>, app_dir: &Path, password: &str) -> Result<(), String> {
+    let current = store();
+    if current.persistent() {
+        return Err(match current.kind() {
+            "file" => "Credentials are already unlocked",
+            _ => "Credentials are kept in the system keyring",
+        }.to_string());
+    }
+    let file = Arc::new(FileStore::open(&app_dir.join(FILE_NAME), password)?);
+    for (key, value) in SESSION.drain() {
+        file.set(&key, &value).await?;
+    }
+    install(file.clone());
+    migrate(pool, &*file).await
+}
+
+/// Move credentials from the settings table into the store, once it keeps them across restarts
+async fn migrate(pool: &Pool<Sqlite>, store: &dyn SecretStore) -> Result<(), String> {
+    if !store.persistent() {
+        return Ok(());
+    }
+    for key in LEGACY_SETTINGS {
+        let Some(value) = get_setting(pool, key).await else { continue };
+        if !value.is_empty() && store.get(key).await?.is_none() {
+            store.set(key, &value).await?;
+            info!("Moved {} out of the settings table", key);
+        }
+        delete_setting(pool, key).await.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+async fn delete_setting(pool: &Pool<Sqlite>, key: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM settings WHERE key = ?").bind(key).execute(pool).await?;
+    Ok(())
+}
+
+/// Read a credential. Until the store keeps secrets across restarts, ones saved by earlier versions
+/// are still read from the settings table.
+pub async fn get(pool: &Pool<Sqlite>, key: &str) -> Result<Option<String>, String> {
+    let store = store();
+    if let Some(value) = store.get(key).await? {
+        return Ok(Some(value));
+    }
+    if store.persistent() {
+        return Ok(None);
+    }
+    Ok(get_setting(pool, key).await.filter(|v| !v.is_empty()))
+}
+
+/// Save a credential, an empty value removes it
+pub async fn set(pool: &Pool<Sqlite>, key: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return delete(pool, key).await;
+    }
+    store().set(key, value).await
+}
+
+pub async fn delete(pool: &Pool<Sqlite>, key: &str) -> Result<(), String> {
+    store().delete(key).await?;
+    delete_setting(pool, key).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_store_keeps_secrets_until_dropped() {
+        let store = MemoryStore::default();
+        assert!(!store.persistent());
+        store.set(DRIVE_REFRESH_TOKEN, "token").await.unwrap();
+        assert_eq!(store.get(DRIVE_REFRESH_TOKEN).await.unwrap().as_deref(), Some("token"));
+        store.delete(DRIVE_REFRESH_TOKEN).await.unwrap();
+        assert_eq!(store.get(DRIVE_REFRESH_TOKEN).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn file_store_reopens_with_the_master_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        assert!(FileStore::open(&path, "").is_err());
+
+        let store = FileStore::open(&path, "master").unwrap();
+        store.set(SYNC_S3_SECRET_KEY, "s3-secret").await.unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("s3-secret"));
+
+        assert_eq!(FileStore::open(&path, "wrong").err().as_deref(), Some("Wrong master password"));
+        let reopened = FileStore::open(&path, "master").unwrap();
+        assert_eq!(reopened.get(SYNC_S3_SECRET_KEY).await.unwrap().as_deref(), Some("s3-secret"));
+    }
+
+    #[tokio::test]
+    async fn migrate_moves_legacy_settings_into_a_persistent_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::db::open_db(dir.path()).await.unwrap();
+        crate::db::set_setting(&pool, DRIVE_REFRESH_TOKEN, "legacy").await.unwrap();
+        crate::db::set_setting(&pool, SYNC_WEBDAV_PASSWORD, "").await.unwrap();
+
+        // Nothing moves into a store that forgets it on exit
+        let memory = MemoryStore::default();
+        migrate(&pool, &memory).await.unwrap();
+        assert_eq!(memory.get(DRIVE_REFRESH_TOKEN).await.unwrap(), None);
+        assert_eq!(get_setting(&pool, DRIVE_REFRESH_TOKEN).await.as_deref(), Some("legacy"));
+
+        let file = FileStore::open(&dir.path().join(FILE_NAME), "master").unwrap();
+        file.set(SYNC_WEBDAV_PASSWORD, "kept").await.unwrap();
+        migrate(&pool, &file).await.unwrap();
+        assert_eq!(file.get(DRIVE_REFRESH_TOKEN).await.unwrap().as_deref(), Some("legacy"));
+        assert_eq!(file.get(SYNC_WEBDAV_PASSWORD).await.unwrap().as_deref(), Some("kept"));
+        assert_eq!(get_setting(&pool, DRIVE_REFRESH_TOKEN).await, None);
+        assert_eq!(get_setting(&pool, SYNC_WEBDAV_PASSWORD).await, None);
+    }
+}
@@ -0,0 +1,16 @@
+use tauri::{AppHandle, Manager, State};
+use crate::db::DbState;
+use crate::secrets::{self, SecretStoreStatus};
+
+#[tauri::command]
+pub fn get_secret_store_status(app: AppHandle) -> Result<SecretStoreStatus, String> {
+    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(secrets::status(&app_dir))
+}
+
+/// Unlock the secrets file with the master password, or create it
+#[tauri::command]
+pub async fn unlock_secret_store(app: AppHandle, state: State<'_, DbState>, password: String) -> Result<(), String> {
+    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    secrets::unlock(&state.pool, &app_dir, &password).await
+}
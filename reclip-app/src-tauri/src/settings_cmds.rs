@@ -4,6 +4,10 @@ use tauri::State;
 use tauri_plugin_global_shortcut::GlobalShortcutExt;
 use crate::db::{self, DbState};
 use crate::clipboard;
+use crate::detectors;
+use crate::app_policy::{self, AppPolicies, AppPolicy, AppPolicyRule, SourceWindow};
+use crate::redaction;
+use crate::api;
 
 /// Normalize shortcut string from plugin format to stored format
 /// Plugin: "shift+control+alt+Digit1" -> Stored: "Ctrl+Shift+Alt+1"
@@ -179,19 +183,164 @@ pub fn get_incognito_mode() -> bool {
     clipboard::is_incognito()
 }
 
+#[tauri::command]
+pub async fn get_primary_selection_settings(state: State<'_, DbState>) -> Result<(bool, u64), String> {
+    let enabled = db::get_setting(&state.pool, "capture_primary_selection").await
+        .map(|v| v == "true")
+        .unwrap_or(false); // Opt-in
+    let debounce_ms = db::get_setting(&state.pool, "primary_selection_debounce_ms").await
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(600); // Default 600 ms
+    Ok((enabled, debounce_ms))
+}
+
+/// Capture of the Linux PRIMARY selection. The debounce applies after a restart.
+#[tauri::command]
+pub async fn set_primary_selection_settings(state: State<'_, DbState>, enabled: bool, debounce_ms: u64) -> Result<(), String> {
+    db::set_setting(&state.pool, "capture_primary_selection", if enabled { "true" } else { "false" })
+        .await.map_err(|e| e.to_string())?;
+    db::set_setting(&state.pool, "primary_selection_debounce_ms", &debounce_ms.to_string())
+        .await.map_err(|e| e.to_string())?;
+    clipboard::set_capture_primary(enabled);
+    Ok(())
+}
+
+/// Bearer token for the local HTTP API
+#[tauri::command]
+pub async fn get_api_token(state: State<'_, DbState>) -> Result<String, String> {
+    api::get_or_create_token(&state.pool).await
+}
+
+#[tauri::command]
+pub async fn regenerate_api_token(state: State<'_, DbState>) -> Result<String, String> {
+    api::regenerate_token(&state.pool).await
+}
+
+#[tauri::command]
+pub async fn get_api_settings(state: State<'_, DbState>) -> Result<(bool, String, u16), String> {
+    Ok(api::get_config(&state.pool).await)
+}
+
+/// Save the API server configuration and restart the server with it
+#[tauri::command]
+pub async fn set_api_settings(app: tauri::AppHandle, state: State<'_, DbState>, enabled: bool, bind_address: String, port: u16) -> Result<(), String> {
+    api::set_config(&state.pool, enabled, &bind_address, port).await?;
+    api::restart(app, state.inner().clone()).await
+}
+
+#[tauri::command]
+pub async fn start_api_server(app: tauri::AppHandle, state: State<'_, DbState>) -> Result<(), String> {
+    api::start(app, state.inner().clone()).await
+}
+
+#[tauri::command]
+pub async fn stop_api_server() -> Result<(), String> {
+    api::stop().await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn restart_api_server(app: tauri::AppHandle, state: State<'_, DbState>) -> Result<(), String> {
+    api::restart(app, state.inner().clone()).await
+}
+
+#[tauri::command]
+pub async fn get_api_status(state: State<'_, DbState>) -> Result<api::ApiStatus, String> {
+    Ok(api::status(&state.pool).await)
+}
+
 #[tauri::command]
 pub async fn get_privacy_rules(state: State<'_, DbState>) -> Result<Vec<db::PrivacyRule>, String> {
     db::get_privacy_rules(&state.pool).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn add_privacy_rule(state: State<'_, DbState>, rule_type: String, value: String) -> Result<i64, String> {
-    db::add_privacy_rule(&state.pool, rule_type, value).await.map_err(|e| e.to_string())
+pub async fn add_privacy_rule(state: State<'_, DbState>, rule_type: String, value: String, placeholder: Option<String>, keep_original: Option<bool>) -> Result<i64, String> {
+    if rule_type == redaction::REGEX_MASK || rule_type == redaction::REGEX_DROP {
+        detectors::check_pattern(&value)?;
+    }
+    let placeholder = placeholder.filter(|p| !p.is_empty());
+    let id = db::add_privacy_rule(&state.pool, rule_type, value, placeholder, keep_original.unwrap_or(false)).await.map_err(|e| e.to_string())?;
+    redaction::invalidate();
+    app_policy::invalidate();
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn add_app_policy(state: State<'_, DbState>, rule: AppPolicyRule) -> Result<i64, String> {
+    rule.check()?;
+    let id = db::add_app_policy(&state.pool, &rule).await.map_err(|e| e.to_string())?;
+    app_policy::invalidate();
+    Ok(id)
+}
+
+#[derive(serde::Serialize)]
+pub struct AppPolicyTest {
+    /// None when the focused window can't be read
+    pub window: Option<SourceWindow>,
+    pub policy: AppPolicy,
+}
+
+/// Resolve the policy for the window focused after `delay_ms`, so the user can switch to the app first.
+/// Tests only `rule` when given, otherwise every saved app rule.
+#[tauri::command]
+pub async fn test_app_policy(state: State<'_, DbState>, rule: Option<AppPolicyRule>, delay_ms: Option<u64>) -> Result<AppPolicyTest, String> {
+    let rules = match rule {
+        Some(rule) => {
+            rule.check()?;
+            vec![rule.to_rule()]
+        }
+        None => db::get_privacy_rules(&state.pool).await.map_err(|e| e.to_string())?,
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(delay_ms.unwrap_or(0))).await;
+    let window = x_win::get_active_window().ok().map(|w| SourceWindow::from(&w));
+    let policy = AppPolicies::new(&rules).resolve(window.as_ref());
+    Ok(AppPolicyTest { window, policy })
 }
 
 #[tauri::command]
 pub async fn delete_privacy_rule(state: State<'_, DbState>, id: i64) -> Result<(), String> {
-    db::delete_privacy_rule(&state.pool, id).await.map_err(|e| e.to_string())
+    db::delete_privacy_rule(&state.pool, id).await.map_err(|e| e.to_string())?;
+    redaction::invalidate();
+    app_policy::invalidate();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_detectors(state: State<'_, DbState>) -> Result<Vec<db::Detector>, String> {
+    db::get_detectors(&state.pool).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_builtin_detectors() -> Vec<&'static str> {
+    detectors::builtin_names()
+}
+
+#[tauri::command]
+pub async fn add_detector(state: State<'_, DbState>, name: String, pattern: String, confidence: Option<f64>) -> Result<i64, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Give the detector a name".to_string());
+    }
+    detectors::check_pattern(&pattern)?;
+    let confidence = confidence.unwrap_or(0.9).clamp(0.0, 1.0);
+    let id = db::add_detector(&state.pool, name, pattern, confidence).await.map_err(|e| e.to_string())?;
+    detectors::invalidate();
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn set_detector_enabled(state: State<'_, DbState>, id: i64, enabled: bool) -> Result<(), String> {
+    db::set_detector_enabled(&state.pool, id, enabled).await.map_err(|e| e.to_string())?;
+    detectors::invalidate();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_detector(state: State<'_, DbState>, id: i64) -> Result<(), String> {
+    db::delete_detector(&state.pool, id).await.map_err(|e| e.to_string())?;
+    detectors::invalidate();
+    Ok(())
 }
 
 #[tauri::command]
@@ -0,0 +1,898 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use crate::blob_store::{self, Blob};
+use crate::db::{self, get_setting};
+use crate::events::ClipEvent;
+use crate::sync_vault::{self, Keyring};
+
+/// A stored object and its version (ETag or content hash), for `put_if`
+pub struct Object {
+    pub data: Vec<u8>,
+    pub version: String,
+}
+
+/// Storage devices exchange their change logs and blobs through. Keys are `/` separated paths.
+#[async_trait]
+pub trait SyncBackend: Send + Sync {
+    /// Keys starting with `prefix`
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, String>;
+    /// `None` when there is no such key
+    async fn get(&self, key: &str) -> Result<Option<Object>, String>;
+    /// Create or overwrite
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), String>;
+    /// Removing a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<(), String>;
+    /// Write only if the key is still at `version`, or doesn't exist for `None`. `false` when it was changed.
+    async fn put_if(&self, key: &str, data: Vec<u8>, version: Option<&str>) -> Result<bool, String>;
+}
+
+const FORMAT_VERSION: u32 = 1;
+// Remote layout: changes/<device>/<seq>.json, one file per push, and blobs/<hash> for images
+pub(crate) const CHANGES_PREFIX: &str = "changes/";
+pub(crate) const BLOBS_PREFIX: &str = "blobs/";
+const SEGMENT_SIZE: usize = 500;
+
+static SYNCING: AtomicBool = AtomicBool::new(false);
+
+/// Hybrid logical clock timestamp: wall clock millis, a counter for changes within the same milli
+/// and the device as tie breaker. Fixed width, so the encoded form sorts like the timestamp.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Hlc {
+    millis: u64,
+    counter: u32,
+    node: String,
+}
+
+impl Hlc {
+    fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.splitn(3, '-');
+        Some(Hlc {
+            millis: parts.next()?.parse().ok()?,
+            counter: parts.next()?.parse().ok()?,
+            node: parts.next()?.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Hlc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:015}-{:05}-{}", self.millis, self.counter, self.node)
+    }
+}
+
+/// Local changes are stamped by the `sync_dirty` triggers from the `sync_clock` row, this keeps it past received changes
+struct Clock {
+    last: Hlc,
+}
+
+impl Clock {
+    fn wall() -> u64 {
+        chrono::Utc::now().timestamp_millis().max(0) as u64
+    }
+
+    /// Move past a received timestamp, so local changes made later order after it even with a clock behind
+    fn observe(&mut self, remote: &Hlc) {
+        let millis = Self::wall().max(self.last.millis).max(remote.millis);
+        self.last.counter = match (millis == self.last.millis, millis == remote.millis) {
+            (true, true) => self.last.counter.max(remote.counter) + 1,
+            (true, false) => self.last.counter + 1,
+            (false, true) => remote.counter + 1,
+            (false, false) => 0,
+        };
+        self.last.millis = millis;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Entity {
+    Clip,
+    Snippet,
+    Note,
+}
+
+impl Entity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Entity::Clip => "clip",
+            Entity::Snippet => "snippet",
+            Entity::Note => "note",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "clip" => Some(Entity::Clip),
+            "snippet" => Some(Entity::Snippet),
+            "note" => Some(Entity::Note),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Op {
+    Upsert,
+    Delete,
+}
+
+/// One change to an item. Clips are keyed by content hash, snippets and notes by uid.
+#[derive(Serialize, Deserialize)]
+struct Change {
+    entity: Entity,
+    key: String,
+    op: Op,
+    hlc: String,
+    /// The whole item for upserts, the last writer wins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
+}
+
+/// The changes of one push of one device
+#[derive(Serialize, Deserialize)]
+struct Segment {
+    version: u32,
+    device: String,
+    seq: u64,
+    changes: Vec<Change>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClipData {
+    content: String,
+    #[serde(rename = "type")]
+    type_: String,
+    created_at: String,
+    pinned: bool,
+    favorite: bool,
+    tags: Option<String>,
+    sender_app: Option<String>,
+    /// Image clips: the content is a local path, the image itself travels as `blobs/<hash>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blob: Option<BlobRef>,
+    /// Representations stored in the database (plain text of rich clips), base64
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    formats: Vec<FormatData>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct BlobRef {
+    hash: String,
+    mime: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FormatData {
+    mime: String,
+    data: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct ClipRow {
+    id: i64,
+    content: String,
+    #[sqlx(rename = "type")]
+    type_: String,
+    created_at: String,
+    pinned: bool,
+    favorite: bool,
+    tags: Option<String>,
+    sender_app: Option<String>,
+    blob_hash: Option<String>,
+    blob_mime: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+struct SnippetData {
+    title: String,
+    content: String,
+    language: String,
+    tags: String,
+    favorite: bool,
+    folder: String,
+    description: String,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+struct NoteData {
+    title: Option<String>,
+    content: String,
+    is_pinned: bool,
+    color: Option<String>,
+    is_archived: bool,
+    tags: Option<String>,
+    position: Option<i64>,
+    created_at: String,
+    updated_at: String,
+}
+
+/// Outcome of a sync run
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Local changes written to the backend
+    pub sent: usize,
+    /// Remote changes read
+    pub received: usize,
+    /// Remote changes that were newer than the local state and got applied
+    pub applied: usize,
+    /// Clip changes made by applying, to publish
+    pub events: Vec<ClipEvent>,
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Synced: sent {} changes, received {} ({} applied)", self.sent, self.received, self.applied)
+    }
+}
+
+/// Send local changes, then fetch and merge the changes of every other device
+pub async fn run(pool: &Pool<Sqlite>, app_dir: &Path, backend: &dyn SyncBackend) -> Result<SyncReport, String> {
+    // Encrypted clips can be neither read for upload nor written from download
+    if crate::vault::is_locked() {
+        return Err("ReClip is locked, sync resumes after unlocking".to_string());
+    }
+    if SYNCING.swap(true, Ordering::SeqCst) {
+        return Err("A sync is already running".to_string());
+    }
+    let result = match Session::load(pool, app_dir, backend).await {
+        Ok(mut session) => session.run().await,
+        Err(e) => Err(e),
+    };
+    SYNCING.store(false, Ordering::SeqCst);
+    result
+}
+
+/// This device's id, created on first use
+pub async fn device_id(pool: &Pool<Sqlite>) -> Result<String, String> {
+    if let Some(id) = get_setting(pool, "sync_device_id").await.filter(|id| !id.is_empty()) {
+        return Ok(id);
+    }
+    let mut bytes = [0u8; 8];
+    getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+    let id: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    db::set_setting(pool, "sync_device_id", &id).await.map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+fn segment_key(device: &str, seq: u64) -> String {
+    format!("{}{}/{:010}.json", CHANGES_PREFIX, device, seq)
+}
+
+/// `(device, seq)` of a `changes/<device>/<seq>.json` key
+fn parse_segment_key(key: &str) -> Option<(String, u64)> {
+    let (device, file) = key.strip_prefix(CHANGES_PREFIX)?.split_once('/')?;
+    Some((device.to_string(), file.strip_suffix(".json")?.parse().ok()?))
+}
+
+async fn save_setting(conn: &mut SqliteConnection, key: &str, value: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO settings (key, value) VALUES (?, ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value")
+        .bind(key)
+        .bind(value)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+struct Session<'a> {
+    pool: &'a Pool<Sqlite>,
+    app_dir: &'a Path,
+    backend: &'a dyn SyncBackend,
+    device: String,
+    keyring: Option<Keyring>,
+    clock: Clock,
+    remote_blobs: Option<HashSet<String>>,
+}
+
+impl<'a> Session<'a> {
+    async fn load(pool: &'a Pool<Sqlite>, app_dir: &'a Path, backend: &'a dyn SyncBackend) -> Result<Self, String> {
+        let device = device_id(pool).await?;
+        let (millis, counter): (i64, i64) = sqlx::query_as("SELECT millis, counter FROM sync_clock WHERE id = 1")
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default();
+        Ok(Session {
+            pool,
+            app_dir,
+            backend,
+            keyring: sync_vault::keyring(pool, backend).await?,
+            clock: Clock { last: Hlc { millis: millis as u64, counter: counter as u32, node: device.clone() } },
+            device,
+            remote_blobs: None,
+        })
+    }
+
+    async fn run(&mut self) -> Result<SyncReport, String> {
+        let mut report = SyncReport { sent: self.push().await?, ..Default::default() };
+        let segments = self.pull().await?;
+        report.received = segments.iter().map(|s| s.changes.len()).sum();
+        self.apply(segments, &mut report).await?;
+        Ok(report)
+    }
+
+    fn seal(&self, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        match &self.keyring {
+            Some(keyring) => keyring.encrypt(&data),
+            None => Ok(data),
+        }
+    }
+
+    // Turning encryption on encrypts what was there, so plain data on an encrypted storage
+    // comes from a device without the keys
+    fn open(&self, key: &str, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        match (&self.keyring, sync_vault::is_encrypted(&data)) {
+            (Some(keyring), true) => keyring.decrypt(&data).map_err(|e| format!("{}: {}", key, e)),
+            (Some(_), false) => Err(format!("{} is not encrypted but the storage is, refusing it", key)),
+            (None, true) => Err(format!("{} is encrypted but the storage has no vault.json", key)),
+            (None, false) => Ok(data),
+        }
+    }
+
+    // Sending
+
+    async fn push(&mut self) -> Result<usize, String> {
+        let dirty: Vec<(i64, String, String, bool, i64, i64)> = sqlx::query_as("SELECT id, entity, key, deleted, millis, counter FROM sync_dirty ORDER BY id")
+            .fetch_all(self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut sent = 0;
+        for chunk in dirty.chunks(SEGMENT_SIZE) {
+            let (segment, seq, local_keys) = loop {
+                let mut changes = Vec::with_capacity(chunk.len());
+                let mut local_keys = Vec::with_capacity(chunk.len());
+                for (_, entity, key, deleted, millis, counter) in chunk {
+                    let Some(entity) = Entity::parse(entity) else { continue };
+                    // Items gone since they were marked go out as tombstones too
+                    let data = if *deleted { None } else { self.load_item(entity, key).await? };
+                    local_keys.push(key.clone());
+                    changes.push(Change {
+                        entity,
+                        key: self.sync_key(entity, key).await?,
+                        op: if data.is_some() { Op::Upsert } else { Op::Delete },
+                        hlc: self.local_hlc(*millis, *counter).to_string(),
+                        data,
+                    });
+                }
+
+                let seq = get_setting(self.pool, "sync_seq").await.and_then(|s| s.parse::<u64>().ok()).unwrap_or(0) + 1;
+                let segment = Segment { version: FORMAT_VERSION, device: self.device.clone(), seq, changes };
+                let body = self.seal(serde_json::to_vec(&segment).map_err(|e| e.to_string())?)?;
+                if self.backend.put_if(&segment_key(&self.device, seq), body, None).await? {
+                    break (segment, seq, local_keys);
+                }
+                // Someone else writes as this device, e.g. a copy of this database on another machine
+                self.fork_device().await?;
+            };
+
+            let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+            for (change, key) in segment.changes.iter().zip(&local_keys) {
+                record_version(&mut tx, change, key).await.map_err(|e| e.to_string())?;
+            }
+            for (id, ..) in chunk {
+                sqlx::query("DELETE FROM sync_dirty WHERE id = ?").bind(id).execute(&mut *tx).await.map_err(|e| e.to_string())?;
+            }
+            save_setting(&mut tx, "sync_seq", &seq.to_string()).await.map_err(|e| e.to_string())?;
+            tx.commit().await.map_err(|e| e.to_string())?;
+            sent += segment.changes.len();
+        }
+        Ok(sent)
+    }
+
+    /// Key other devices know an item by. Clips stored under a keyed hash (vault mode) have it in `sync_keys`.
+    async fn sync_key(&self, entity: Entity, key: &str) -> Result<String, String> {
+        if entity != Entity::Clip {
+            return Ok(key.to_string());
+        }
+        let sealed: Option<String> = sqlx::query_scalar("SELECT sync_key FROM sync_keys WHERE hash = ?")
+            .bind(key)
+            .fetch_optional(self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        match sealed {
+            Some(sealed) => db::unseal(sealed).map_err(|e| e.to_string()),
+            None => Ok(key.to_string()),
+        }
+    }
+
+    fn local_hlc(&self, millis: i64, counter: i64) -> Hlc {
+        Hlc { millis: millis as u64, counter: counter as u32, node: self.device.clone() }
+    }
+
+    /// Continue under a new device id, the segments of the old one are read like any other device's
+    async fn fork_device(&mut self) -> Result<(), String> {
+        let previous = self.device.clone();
+        db::set_setting(self.pool, "sync_device_id", "").await.map_err(|e| e.to_string())?;
+        self.device = device_id(self.pool).await?;
+        db::set_setting(self.pool, "sync_seq", "0").await.map_err(|e| e.to_string())?;
+        self.clock.last.node = self.device.clone();
+        warn!("Sync device id {} is used by another installation, continuing as {}", previous, self.device);
+        Ok(())
+    }
+
+    async fn load_item(&mut self, entity: Entity, key: &str) -> Result<Option<serde_json::Value>, String> {
+        let value = match entity {
+            Entity::Clip => match self.load_clip(key).await? {
+                Some(clip) => serde_json::to_value(clip),
+                None => return Ok(None),
+            },
+            Entity::Snippet => match sqlx::query_as::<_, SnippetData>("SELECT title, content, COALESCE(language, 'plaintext') AS language, COALESCE(tags, '[]') AS tags, COALESCE(favorite, 0) AS favorite, COALESCE(folder, '') AS folder, COALESCE(description, '') AS description, created_at, updated_at FROM snippets WHERE uid = ?")
+                .bind(key)
+                .fetch_optional(self.pool)
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                Some(snippet) => serde_json::to_value(SnippetData { content: db::unseal(snippet.content).map_err(|e| e.to_string())?, ..snippet }),
+                None => return Ok(None),
+            },
+            Entity::Note => match sqlx::query_as::<_, NoteData>("SELECT title, content, is_pinned, color, is_archived, tags, position, created_at, updated_at FROM notes WHERE uid = ?")
+                .bind(key)
+                .fetch_optional(self.pool)
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                Some(note) => serde_json::to_value(note),
+                None => return Ok(None),
+            },
+        };
+        value.map(Some).map_err(|e| e.to_string())
+    }
+
+    async fn load_clip(&mut self, hash: &str) -> Result<Option<ClipData>, String> {
+        let Some(row) = sqlx::query_as::<_, ClipRow>(
+            "SELECT c.id, c.content, c.type, c.created_at, c.pinned, c.favorite, c.tags, c.sender_app, c.blob_hash, b.mime AS blob_mime
+             FROM clips c LEFT JOIN blobs b ON b.hash = c.blob_hash WHERE c.hash = ? AND c.sensitive = 0 AND c.type != 'files'")
+            .bind(hash)
+            .fetch_optional(self.pool)
+            .await
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(None);
+        };
+
+        let formats: Vec<(String, Vec<u8>)> = sqlx::query_as("SELECT mime, data FROM clip_formats WHERE clip_id = ? AND data IS NOT NULL")
+            .bind(row.id)
+            .fetch_all(self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        let formats = formats
+            .into_iter()
+            .map(|(mime, data)| db::unseal_bytes(data).map(|data| FormatData { mime, data: BASE64.encode(data) }))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let blob = match (row.blob_hash, row.blob_mime) {
+            (Some(hash), Some(mime)) => Some(BlobRef { hash, mime }),
+            _ => None,
+        };
+        if let Some(blob) = &blob {
+            self.upload_blob(blob).await?;
+        }
+
+        Ok(Some(ClipData {
+            content: db::unseal(row.content).map_err(|e| e.to_string())?,
+            type_: row.type_,
+            created_at: row.created_at,
+            pinned: row.pinned,
+            favorite: row.favorite,
+            tags: row.tags.map(db::unseal).transpose().map_err(|e| e.to_string())?,
+            sender_app: row.sender_app,
+            blob,
+            formats,
+        }))
+    }
+
+    async fn upload_blob(&mut self, blob: &BlobRef) -> Result<(), String> {
+        if self.remote_blobs.is_none() {
+            let keys = self.backend.list(BLOBS_PREFIX).await?;
+            self.remote_blobs = Some(keys.iter().filter_map(|k| k.strip_prefix(BLOBS_PREFIX)).map(String::from).collect());
+        }
+        if self.remote_blobs.as_ref().is_some_and(|blobs| blobs.contains(&blob.hash)) {
+            return Ok(());
+        }
+
+        let Some(stored) = blob_store::get(self.pool, &blob.hash).await.map_err(|e| e.to_string())? else {
+            return Ok(());
+        };
+        let data = match std::fs::read(&stored.path) {
+            Ok(data) => data,
+            Err(e) => {
+                // Other devices skip the clip until it is synced again with its image
+                warn!("Blob {} of a synced clip is unreadable: {}", blob.hash, e);
+                return Ok(());
+            }
+        };
+        self.backend.put(&format!("{}{}", BLOBS_PREFIX, blob.hash), self.seal(data)?).await?;
+        if let Some(blobs) = self.remote_blobs.as_mut() {
+            blobs.insert(blob.hash.clone());
+        }
+        Ok(())
+    }
+
+    // Receiving
+
+    /// Segments of other devices not read yet, in order per device
+    async fn pull(&mut self) -> Result<Vec<Segment>, String> {
+        let cursors: HashMap<String, i64> = sqlx::query_as("SELECT device, seq FROM sync_cursors")
+            .fetch_all(self.pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect();
+
+        let mut pending: Vec<(String, u64, String)> = self.backend.list(CHANGES_PREFIX).await?
+            .into_iter()
+            .filter_map(|key| parse_segment_key(&key).map(|(device, seq)| (device, seq, key)))
+            .filter(|(device, seq, _)| *device != self.device && *seq as i64 > cursors.get(device).copied().unwrap_or(0))
+            .collect();
+        pending.sort();
+
+        let mut segments = Vec::with_capacity(pending.len());
+        for (device, seq, key) in pending {
+            let Some(object) = self.backend.get(&key).await? else { continue };
+            let segment: Segment = serde_json::from_slice(&self.open(&key, object.data)?)
+                .map_err(|e| format!("Invalid change segment {}: {}", key, e))?;
+            if segment.version > FORMAT_VERSION {
+                return Err(format!("Device {} syncs with a newer version of ReClip, update to sync with it", device));
+            }
+            if segment.device != device || segment.seq != seq {
+                warn!("Skipping change segment {} with mismatching header", key);
+                continue;
+            }
+            segments.push(segment);
+        }
+        Ok(segments)
+    }
+
+    async fn apply(&mut self, segments: Vec<Segment>, report: &mut SyncReport) -> Result<(), String> {
+        let mut cursors: HashMap<String, u64> = HashMap::new();
+        let mut latest: HashMap<(Entity, String), (Hlc, Change)> = HashMap::new();
+        for segment in segments {
+            let seq = cursors.entry(segment.device).or_default();
+            *seq = (*seq).max(segment.seq);
+            for change in segment.changes {
+                let Some(hlc) = Hlc::parse(&change.hlc) else { continue };
+                self.clock.observe(&hlc);
+                let key = local_key(&change)?;
+                match latest.get(&(change.entity, key.clone())) {
+                    Some((newest, _)) if *newest >= hlc => {}
+                    _ => { latest.insert((change.entity, key), (hlc, change)); }
+                }
+            }
+        }
+
+        // Items changed locally while this sync ran keep their state when that change is newer, it goes out next time
+        let dirty: HashMap<(String, String), Hlc> = sqlx::query_as::<_, (String, String, i64, i64)>("SELECT entity, key, millis, counter FROM sync_dirty")
+            .fetch_all(self.pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|(entity, key, millis, counter)| ((entity, key), self.local_hlc(millis, counter)))
+            .collect();
+
+        let mut winners: Vec<(Hlc, String, Change)> = Vec::new();
+        for ((entity, key), (hlc, change)) in latest {
+            if dirty.get(&(entity.as_str().to_string(), key.clone())).is_some_and(|local| *local >= hlc) {
+                continue;
+            }
+            let local: Option<String> = sqlx::query_scalar("SELECT hlc FROM sync_versions WHERE entity = ? AND key = ?")
+                .bind(entity.as_str())
+                .bind(&key)
+                .fetch_optional(self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            if local.and_then(|l| Hlc::parse(&l)).is_some_and(|l| l >= hlc) {
+                continue;
+            }
+            winners.push((hlc, key, change));
+        }
+        winners.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // Images go to the blob store before the transaction, it writes through the pool
+        let mut blobs: HashMap<String, Blob> = HashMap::new();
+        let mut changes = Vec::with_capacity(winners.len());
+        for (_, key, change) in winners {
+            if let (Entity::Clip, Op::Upsert) = (change.entity, change.op) {
+                let data: ClipData = match change.data.clone().map(serde_json::from_value) {
+                    Some(Ok(data)) => data,
+                    _ => {
+                        warn!("Skipping malformed clip change {}", change.key);
+                        continue;
+                    }
+                };
+                if let Some(blob) = &data.blob {
+                    if !blobs.contains_key(&blob.hash) {
+                        let blob_key = format!("{}{}", BLOBS_PREFIX, blob.hash);
+                        let Some(object) = self.backend.get(&blob_key).await? else {
+                            warn!("Image {} of synced clip {} is missing", blob.hash, change.key);
+                            continue;
+                        };
+                        let stored = blob_store::put(self.pool, self.app_dir, &self.open(&blob_key, object.data)?, &blob.mime).await?;
+                        blobs.insert(blob.hash.clone(), stored);
+                    }
+                }
+            }
+            changes.push((key, change));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        sqlx::query("INSERT INTO sync_paused (reason) VALUES ('sync')").execute(&mut *tx).await.map_err(|e| e.to_string())?;
+        for (key, change) in &changes {
+            let event = match change.entity {
+                Entity::Clip => apply_clip(&mut tx, change, key, &blobs).await,
+                Entity::Snippet => apply_snippet(&mut tx, change).await,
+                Entity::Note => apply_note(&mut tx, change).await,
+            };
+            match event {
+                Ok(event) => report.events.extend(event),
+                Err(ApplyError::Db(e)) => return Err(e.to_string()),
+                Err(ApplyError::Data(e)) => {
+                    warn!("Skipping malformed {} change {}: {}", change.entity.as_str(), change.key, e);
+                    continue;
+                }
+            }
+            record_version(&mut tx, change, key).await.map_err(|e| e.to_string())?;
+            // An older local change lost, unless it was made again since the check above
+            if let Some(hlc) = Hlc::parse(&change.hlc) {
+                sqlx::query("DELETE FROM sync_dirty WHERE entity = ? AND key = ? AND (millis, counter) < (?, ?)")
+                    .bind(change.entity.as_str())
+                    .bind(key)
+                    .bind(hlc.millis as i64)
+                    .bind(hlc.counter as i64)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            report.applied += 1;
+        }
+        sqlx::query("DELETE FROM sync_paused").execute(&mut *tx).await.map_err(|e| e.to_string())?;
+
+        for (device, seq) in &cursors {
+            sqlx::query("INSERT INTO sync_cursors (device, seq) VALUES (?, ?) ON CONFLICT(device) DO UPDATE SET seq = MAX(seq, excluded.seq)")
+                .bind(device)
+                .bind(*seq as i64)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        sqlx::query("UPDATE sync_clock SET millis = ?, counter = ? WHERE id = 1 AND (millis, counter) < (?, ?)")
+            .bind(self.clock.last.millis as i64)
+            .bind(self.clock.last.counter as i64)
+            .bind(self.clock.last.millis as i64)
+            .bind(self.clock.last.counter as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+enum ApplyError {
+    Db(sqlx::Error),
+    Data(serde_json::Error),
+}
+
+impl From<sqlx::Error> for ApplyError {
+    fn from(e: sqlx::Error) -> Self {
+        ApplyError::Db(e)
+    }
+}
+
+impl From<serde_json::Error> for ApplyError {
+    fn from(e: serde_json::Error) -> Self {
+        ApplyError::Data(e)
+    }
+}
+
+fn change_data<T: for<'de> Deserialize<'de>>(change: &Change) -> Result<T, serde_json::Error> {
+    serde_json::from_value(change.data.clone().unwrap_or_default())
+}
+
+/// Key an item is stored under here, see `Session::sync_key`
+fn local_key(change: &Change) -> Result<String, String> {
+    match change.entity {
+        Entity::Clip => db::clip_hash(&change.key).map_err(|e| e.to_string()),
+        _ => Ok(change.key.clone()),
+    }
+}
+
+async fn record_version(conn: &mut SqliteConnection, change: &Change, key: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO sync_versions (entity, key, hlc, deleted) VALUES (?, ?, ?, ?)
+        ON CONFLICT(entity, key) DO UPDATE SET hlc = excluded.hlc, deleted = excluded.deleted")
+        .bind(change.entity.as_str())
+        .bind(key)
+        .bind(&change.hlc)
+        .bind(change.op == Op::Delete)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// `key` is the hash the clip is stored under, see `local_key`
+async fn apply_clip(conn: &mut SqliteConnection, change: &Change, key: &str, blobs: &HashMap<String, Blob>) -> Result<Option<ClipEvent>, ApplyError> {
+    if change.op == Op::Delete {
+        let id: Option<i64> = sqlx::query_scalar("DELETE FROM clips WHERE hash = ? RETURNING id")
+            .bind(key)
+            .fetch_optional(&mut *conn)
+            .await?;
+        return Ok(id.map(ClipEvent::Deleted));
+    }
+
+    let data: ClipData = change_data(change)?;
+    let blob = data.blob.as_ref().and_then(|b| blobs.get(&b.hash));
+    // Image clips point at their local copy
+    let content = db::seal_clip_content(&data.type_, blob.map(|b| b.path.clone()).unwrap_or(data.content))?;
+    let tags = db::seal_opt(data.tags)?;
+
+    let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM clips WHERE hash = ?")
+        .bind(key)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let event = match existing {
+        Some(id) => {
+            sqlx::query("UPDATE clips SET content = ?, created_at = ?, pinned = ?, favorite = ?, tags = ? WHERE id = ?")
+                .bind(&content)
+                .bind(&data.created_at)
+                .bind(data.pinned)
+                .bind(data.favorite)
+                .bind(&tags)
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            ClipEvent::Updated(id)
+        }
+        None => {
+            db::remember_sync_key(&mut *conn, key, &change.key).await?;
+            let id: i64 = sqlx::query_scalar("INSERT INTO clips (content, type, hash, created_at, pinned, favorite, tags, sender_app, origin) VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'clipboard') RETURNING id")
+                .bind(&content)
+                .bind(&data.type_)
+                .bind(key)
+                .bind(&data.created_at)
+                .bind(data.pinned)
+                .bind(data.favorite)
+                .bind(&tags)
+                .bind(&data.sender_app)
+                .fetch_one(&mut *conn)
+                .await?;
+            ClipEvent::Created(id)
+        }
+    };
+    let id = event.clip_id();
+
+    if let Some(blob) = blob {
+        sqlx::query("UPDATE clips SET blob_hash = ? WHERE id = ?")
+            .bind(&blob.hash)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for format in &data.formats {
+        let Ok(bytes) = BASE64.decode(&format.data) else { continue };
+        let size = bytes.len() as i64;
+        sqlx::query("INSERT INTO clip_formats (clip_id, mime, data, size) VALUES (?, ?, ?, ?)
+            ON CONFLICT(clip_id, mime) DO UPDATE SET data = excluded.data, path = NULL, blob_hash = NULL, size = excluded.size")
+            .bind(id)
+            .bind(&format.mime)
+            .bind(db::seal_bytes(bytes)?)
+            .bind(size)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(Some(event))
+}
+
+async fn apply_snippet(conn: &mut SqliteConnection, change: &Change) -> Result<Option<ClipEvent>, ApplyError> {
+    if change.op == Op::Delete {
+        sqlx::query("DELETE FROM snippets WHERE uid = ?").bind(&change.key).execute(&mut *conn).await?;
+        return Ok(None);
+    }
+
+    let data: SnippetData = change_data(change)?;
+    sqlx::query("INSERT INTO snippets (uid, title, content, language, tags, favorite, folder, description, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(uid) DO UPDATE SET title = excluded.title, content = excluded.content, language = excluded.language, tags = excluded.tags,
+            favorite = excluded.favorite, folder = excluded.folder, description = excluded.description, updated_at = excluded.updated_at")
+        .bind(&change.key)
+        .bind(&data.title)
+        .bind(db::seal(data.content)?)
+        .bind(&data.language)
+        .bind(&data.tags)
+        .bind(data.favorite)
+        .bind(&data.folder)
+        .bind(&data.description)
+        .bind(&data.created_at)
+        .bind(&data.updated_at)
+        .execute(&mut *conn)
+        .await?;
+    Ok(None)
+}
+
+async fn apply_note(conn: &mut SqliteConnection, change: &Change) -> Result<Option<ClipEvent>, ApplyError> {
+    if change.op == Op::Delete {
+        sqlx::query("DELETE FROM notes WHERE uid = ?").bind(&change.key).execute(&mut *conn).await?;
+        return Ok(None);
+    }
+
+    let data: NoteData = change_data(change)?;
+    sqlx::query("INSERT INTO notes (uid, title, content, is_pinned, color, is_archived, tags, position, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(uid) DO UPDATE SET title = excluded.title, content = excluded.content, is_pinned = excluded.is_pinned, color = excluded.color,
+            is_archived = excluded.is_archived, tags = excluded.tags, position = excluded.position, updated_at = excluded.updated_at")
+        .bind(&change.key)
+        .bind(&data.title)
+        .bind(&data.content)
+        .bind(data.is_pinned)
+        .bind(&data.color)
+        .bind(data.is_archived)
+        .bind(&data.tags)
+        .bind(data.position)
+        .bind(&data.created_at)
+        .bind(&data.updated_at)
+        .execute(&mut *conn)
+        .await?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_backends::FolderBackend;
+
+    async fn tags(pool: &Pool<Sqlite>, hash: &str) -> Option<String> {
+        sqlx::query_scalar("SELECT tags FROM clips WHERE hash = ?").bind(hash).fetch_one(pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn changes_keep_the_time_they_were_made() {
+        let dir = tempfile::tempdir().unwrap();
+        let remote = FolderBackend::new(dir.path().join("remote"));
+        let (a_dir, b_dir) = (dir.path().join("a"), dir.path().join("b"));
+        let a = db::open_db(&a_dir).await.unwrap();
+        let b = db::open_db(&b_dir).await.unwrap();
+        db::insert_clip(&a, "hello".to_string(), "text".to_string(), "h".to_string(), None, None).await.unwrap();
+        run(&a, &a_dir, &remote).await.unwrap();
+        run(&b, &b_dir, &remote).await.unwrap();
+
+        // B edits first but only syncs after A's newer edit went out
+        sqlx::query("UPDATE clips SET tags = '[\"#b\"]' WHERE hash = 'h'").execute(&b).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        sqlx::query("UPDATE clips SET tags = '[\"#a\"]' WHERE hash = 'h'").execute(&a).await.unwrap();
+        run(&a, &a_dir, &remote).await.unwrap();
+        run(&b, &b_dir, &remote).await.unwrap();
+        run(&a, &a_dir, &remote).await.unwrap();
+
+        assert_eq!(tags(&a, "h").await.as_deref(), Some("[\"#a\"]"));
+        assert_eq!(tags(&b, "h").await.as_deref(), Some("[\"#a\"]"));
+    }
+
+    #[tokio::test]
+    async fn newer_local_changes_survive_a_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let remote = FolderBackend::new(dir.path().join("remote"));
+        let (a_dir, b_dir) = (dir.path().join("a"), dir.path().join("b"));
+        let a = db::open_db(&a_dir).await.unwrap();
+        let b = db::open_db(&b_dir).await.unwrap();
+        db::insert_clip(&a, "hello".to_string(), "text".to_string(), "h".to_string(), None, None).await.unwrap();
+        run(&a, &a_dir, &remote).await.unwrap();
+        let mut session = Session::load(&b, &b_dir, &remote).await.unwrap();
+        let segments = session.pull().await.unwrap();
+
+        // B makes its own copy while the segment is in flight, it is newer and stays marked for sending
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        db::insert_clip(&b, "hello".to_string(), "text".to_string(), "h".to_string(), Some("[\"#b\"]".to_string()), None).await.unwrap();
+        session.apply(segments, &mut SyncReport::default()).await.unwrap();
+
+        assert_eq!(tags(&b, "h").await.as_deref(), Some("[\"#b\"]"));
+        let dirty: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sync_dirty").fetch_one(&b).await.unwrap();
+        assert_eq!(dirty, 1);
+    }
+}
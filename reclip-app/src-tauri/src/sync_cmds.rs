@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use tauri::{AppHandle, Manager, State};
+use crate::db::{get_setting, set_setting, DbState};
+use crate::drive::{self, DriveState};
+use crate::events;
+use crate::secrets;
+use crate::sync::{self, SyncBackend};
+use crate::sync_backends::{FolderBackend, S3Backend, S3Config, WebDavBackend};
+use crate::sync_vault::{self, VaultStatus};
+
+/// Where sync stores its data, kept in the `sync_*` settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncConfig {
+    /// "drive", "folder", "webdav" or "s3", empty when sync is off
+    pub backend: String,
+    #[serde(default)]
+    pub folder_path: String,
+    #[serde(default)]
+    pub webdav_url: String,
+    #[serde(default)]
+    pub webdav_user: String,
+    #[serde(default)]
+    pub webdav_password: String,
+    /// Empty for AWS
+    #[serde(default)]
+    pub s3_endpoint: String,
+    #[serde(default)]
+    pub s3_region: String,
+    #[serde(default)]
+    pub s3_bucket: String,
+    #[serde(default)]
+    pub s3_prefix: String,
+    #[serde(default)]
+    pub s3_access_key: String,
+    #[serde(default)]
+    pub s3_secret_key: String,
+    #[serde(default)]
+    pub s3_path_style: bool,
+    #[serde(default, skip_deserializing)]
+    pub last_sync: Option<String>,
+    /// Secrets are never sent to the webview, these tell it one is stored
+    #[serde(default, skip_deserializing)]
+    pub webdav_password_set: bool,
+    #[serde(default, skip_deserializing)]
+    pub s3_secret_key_set: bool,
+}
+
+impl SyncConfig {
+    pub async fn load(pool: &Pool<Sqlite>) -> Result<Self, String> {
+        let setting = |key: &'static str| async move { get_setting(pool, key).await.unwrap_or_default() };
+        let mut backend = setting("sync_backend").await;
+        // Installs from before the storage choice synced with Drive
+        if backend.is_empty() && setting("drive_connected").await == "true" {
+            backend = "drive".to_string();
+        }
+        Ok(SyncConfig {
+            backend,
+            folder_path: setting("sync_folder_path").await,
+            webdav_url: setting("sync_webdav_url").await,
+            webdav_user: setting("sync_webdav_user").await,
+            webdav_password: secrets::get(pool, secrets::SYNC_WEBDAV_PASSWORD).await?.unwrap_or_default(),
+            s3_endpoint: setting("sync_s3_endpoint").await,
+            s3_region: setting("sync_s3_region").await,
+            s3_bucket: setting("sync_s3_bucket").await,
+            s3_prefix: setting("sync_s3_prefix").await,
+            s3_access_key: setting("sync_s3_access_key").await,
+            s3_secret_key: secrets::get(pool, secrets::SYNC_S3_SECRET_KEY).await?.unwrap_or_default(),
+            s3_path_style: setting("sync_s3_path_style").await == "true",
+            last_sync: get_setting(pool, "sync_last_run").await,
+            ..Default::default()
+        })
+    }
+
+    /// For the webview: secrets blanked, with flags telling which are stored
+    fn without_secrets(self) -> Self {
+        SyncConfig {
+            webdav_password_set: !self.webdav_password.is_empty(),
+            s3_secret_key_set: !self.s3_secret_key.is_empty(),
+            webdav_password: String::new(),
+            s3_secret_key: String::new(),
+            ..self
+        }
+    }
+
+    /// From the webview: blank secrets keep the stored ones
+    async fn with_stored_secrets(mut self, pool: &Pool<Sqlite>) -> Result<Self, String> {
+        if self.webdav_password.is_empty() {
+            self.webdav_password = secrets::get(pool, secrets::SYNC_WEBDAV_PASSWORD).await?.unwrap_or_default();
+        }
+        if self.s3_secret_key.is_empty() {
+            self.s3_secret_key = secrets::get(pool, secrets::SYNC_S3_SECRET_KEY).await?.unwrap_or_default();
+        }
+        Ok(self)
+    }
+
+    fn same_storage(&self, other: &SyncConfig) -> bool {
+        let location = |c: &SyncConfig| match c.backend.as_str() {
+            "folder" => vec![c.folder_path.clone()],
+            "webdav" => vec![c.webdav_url.clone()],
+            "s3" => vec![c.s3_endpoint.clone(), c.s3_bucket.clone(), c.s3_prefix.clone()],
+            _ => Vec::new(),
+        };
+        self.backend == other.backend && location(self) == location(other)
+    }
+
+    pub async fn save(&self, pool: &Pool<Sqlite>) -> Result<(), String> {
+        let path_style = if self.s3_path_style { "true" } else { "false" };
+        for (key, value) in [
+            ("sync_backend", self.backend.as_str()),
+            ("sync_folder_path", &self.folder_path),
+            ("sync_webdav_url", &self.webdav_url),
+            ("sync_webdav_user", &self.webdav_user),
+            ("sync_s3_endpoint", &self.s3_endpoint),
+            ("sync_s3_region", &self.s3_region),
+            ("sync_s3_bucket", &self.s3_bucket),
+            ("sync_s3_prefix", &self.s3_prefix),
+            ("sync_s3_access_key", &self.s3_access_key),
+            ("sync_s3_path_style", path_style),
+        ] {
+            set_setting(pool, key, value).await.map_err(|e| e.to_string())?;
+        }
+        secrets::set(pool, secrets::SYNC_WEBDAV_PASSWORD, &self.webdav_password).await?;
+        secrets::set(pool, secrets::SYNC_S3_SECRET_KEY, &self.s3_secret_key).await
+    }
+}
+
+async fn open_backend(config: &SyncConfig, drive_state: &State<'_, DriveState>, db_state: &State<'_, DbState>) -> Result<Box<dyn SyncBackend>, String> {
+    match config.backend.as_str() {
+        "drive" => Ok(Box::new(drive::backend(drive_state, db_state).await?)),
+        "folder" if config.folder_path.is_empty() => Err("No sync folder chosen".to_string()),
+        "folder" => Ok(Box::new(FolderBackend::new(&config.folder_path))),
+        "webdav" => Ok(Box::new(WebDavBackend::new(&config.webdav_url, &config.webdav_user, &config.webdav_password)?)),
+        "s3" => Ok(Box::new(S3Backend::new(S3Config {
+            endpoint: &config.s3_endpoint,
+            region: &config.s3_region,
+            bucket: &config.s3_bucket,
+            prefix: &config.s3_prefix,
+            access_key: &config.s3_access_key,
+            secret_key: &config.s3_secret_key,
+            path_style: config.s3_path_style,
+        })?)),
+        "" => Err("Sync is not set up".to_string()),
+        other => Err(format!("Unknown sync storage: {}", other)),
+    }
+}
+
+#[tauri::command]
+pub async fn get_sync_config(state: State<'_, DbState>) -> Result<SyncConfig, String> {
+    SyncConfig::load(&state.pool).await.map(SyncConfig::without_secrets)
+}
+
+#[tauri::command]
+pub async fn set_sync_config(state: State<'_, DbState>, config: SyncConfig) -> Result<(), String> {
+    let config = config.with_stored_secrets(&state.pool).await?;
+    let previous = SyncConfig { last_sync: None, ..SyncConfig::load(&state.pool).await? };
+    // The unlocked vault belongs to the old storage
+    if previous != (SyncConfig { last_sync: None, ..config.clone() }) {
+        sync_vault::lock();
+    }
+    // New credentials for the same storage keep it encrypted
+    if !previous.same_storage(&config) {
+        sync_vault::forget(&state.pool).await?;
+    }
+    config.save(&state.pool).await
+}
+
+/// Write, read back and remove a small file with the given settings
+#[tauri::command]
+pub async fn test_sync_backend(drive_state: State<'_, DriveState>, db_state: State<'_, DbState>, config: SyncConfig) -> Result<String, String> {
+    let config = config.with_stored_secrets(&db_state.pool).await?;
+    let backend = open_backend(&config, &drive_state, &db_state).await?;
+    let key = format!("probe/{}.txt", sync::device_id(&db_state.pool).await?);
+    let payload = chrono::Utc::now().to_rfc3339().into_bytes();
+
+    backend.put(&key, payload.clone()).await?;
+    let read = backend.get(&key).await?.ok_or("The test file was written but can't be read back")?;
+    if read.data != payload {
+        return Err("The test file read back differs from what was written".to_string());
+    }
+    let listed = backend.list("probe/").await?;
+    backend.delete(&key).await?;
+    if !listed.contains(&key) {
+        return Err("The test file was written but isn't listed".to_string());
+    }
+    Ok("Connection works".to_string())
+}
+
+#[tauri::command]
+pub async fn sync_now(app: AppHandle, drive_state: State<'_, DriveState>, db_state: State<'_, DbState>) -> Result<String, String> {
+    let config = SyncConfig::load(&db_state.pool).await?;
+    let backend = open_backend(&config, &drive_state, &db_state).await?;
+
+    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let report = sync::run(&db_state.pool, &app_dir, backend.as_ref()).await?;
+    for event in &report.events {
+        events::publish(&app, *event);
+    }
+    if !report.events.is_empty() {
+        let _ = crate::tray::update_tray_history(&app).await;
+    }
+
+    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    set_setting(&db_state.pool, "sync_last_run", &now).await.map_err(|e| e.to_string())?;
+    if config.backend == "drive" {
+        set_setting(&db_state.pool, "drive_last_sync", &now).await.map_err(|e| e.to_string())?;
+    }
+    Ok(report.to_string())
+}
+
+#[tauri::command]
+pub async fn get_sync_vault_status(drive_state: State<'_, DriveState>, db_state: State<'_, DbState>) -> Result<VaultStatus, String> {
+    let config = SyncConfig::load(&db_state.pool).await?;
+    sync_vault::status(open_backend(&config, &drive_state, &db_state).await?.as_ref()).await
+}
+
+/// Unlock encrypted sync data, or encrypt from now on when the storage isn't encrypted yet
+#[tauri::command]
+pub async fn unlock_sync_vault(drive_state: State<'_, DriveState>, db_state: State<'_, DbState>, password: String) -> Result<(), String> {
+    let config = SyncConfig::load(&db_state.pool).await?;
+    sync_vault::unlock(&db_state.pool, open_backend(&config, &drive_state, &db_state).await?.as_ref(), &password).await
+}
+
+#[tauri::command]
+pub fn lock_sync_vault() {
+    sync_vault::lock();
+}
+
+#[tauri::command]
+pub async fn change_sync_password(drive_state: State<'_, DriveState>, db_state: State<'_, DbState>, current: String, new: String) -> Result<(), String> {
+    let config = SyncConfig::load(&db_state.pool).await?;
+    sync_vault::change_password(open_backend(&config, &drive_state, &db_state).await?.as_ref(), &current, &new).await
+}
+
+#[tauri::command]
+pub async fn rotate_sync_key(drive_state: State<'_, DriveState>, db_state: State<'_, DbState>) -> Result<String, String> {
+    let config = SyncConfig::load(&db_state.pool).await?;
+    let keys = sync_vault::rotate_key(open_backend(&config, &drive_state, &db_state).await?.as_ref()).await?;
+    Ok(format!("New data is encrypted with a fresh key ({} keys in the vault)", keys))
+}
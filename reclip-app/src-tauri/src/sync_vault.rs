@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use crate::crypto::{self, KdfParams, KEY_LEN};
+use crate::db;
+use crate::secrets;
+use crate::sync::SyncBackend;
+
+// Lives next to changes/ and blobs/ on the sync storage, its presence means the storage is encrypted
+const VAULT_KEY: &str = "vault.json";
+const VAULT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+// Encrypted with the password key, a wrong password fails on this before any data key is touched
+const VERIFIER: &[u8] = b"reclip sync vault";
+// Encrypted objects start with this, followed by the id of the data key as 4 bytes big endian
+const MAGIC: &[u8] = b"RCV1";
+// Local setting with the id of the vault this device unlocked. Once set, the storage must stay
+// encrypted: plain data is refused and a missing vault.json stops sync instead of turning encryption off.
+const VAULT_ID_SETTING: &str = "sync_vault_id";
+
+type Key = [u8; KEY_LEN];
+
+/// The data keys, each wrapped with a key derived from the sync password. Changing the password
+/// only rewraps them; rotating adds a new key for new data and keeps the old ones for old data.
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    /// Random, kept through password changes and key rotations
+    id: String,
+    kdf: KdfParams,
+    salt: String,
+    verifier: String,
+    current: u32,
+    keys: Vec<WrappedKey>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WrappedKey {
+    id: u32,
+    key: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    pub encrypted: bool,
+    pub unlocked: bool,
+    pub keys: usize,
+}
+
+/// Data keys of an unlocked vault
+pub struct Keyring {
+    keys: HashMap<u32, Key>,
+    current: u32,
+}
+
+impl Keyring {
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let key = self.keys.get(&self.current).ok_or("The current sync key is missing from the vault")?;
+        let mut sealed = Vec::with_capacity(MAGIC.len() + 4 + data.len() + 28);
+        sealed.extend_from_slice(MAGIC);
+        sealed.extend_from_slice(&self.current.to_be_bytes());
+        sealed.extend_from_slice(&crypto::encrypt(data, key)?);
+        Ok(sealed)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let rest = data.strip_prefix(MAGIC).ok_or("Not encrypted sync data")?;
+        if rest.len() < 4 {
+            return Err("Encrypted sync data is truncated".to_string());
+        }
+        let (id, sealed) = rest.split_at(4);
+        let id = u32::from_be_bytes([id[0], id[1], id[2], id[3]]);
+        let key = self.keys.get(&id).ok_or_else(|| format!("Sync data uses key {} which is not in the vault", id))?;
+        crypto::decrypt(sealed, key)
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// The password key of the unlocked vault, in memory only. Data keys are unwrapped from the vault
+// on every sync, so keys added or a password changed on another device are picked up.
+static UNLOCKED: LazyLock<Mutex<Option<Key>>> = LazyLock::new(|| Mutex::new(None));
+
+fn unlocked() -> Option<Key> {
+    *UNLOCKED.lock().unwrap()
+}
+
+pub fn lock() {
+    *UNLOCKED.lock().unwrap() = None;
+}
+
+impl VaultFile {
+    fn create(password: &str) -> Result<(Self, Key), String> {
+        let kdf = KdfParams::default();
+        let salt = crypto::random_bytes::<SALT_LEN>()?;
+        let kek = crypto::derive_key(password, &salt, &kdf)?;
+        let data_key = crypto::random_bytes::<KEY_LEN>()?;
+        let id: String = crypto::random_bytes::<16>()?.iter().map(|b| format!("{:02x}", b)).collect();
+        let vault = VaultFile {
+            version: VAULT_VERSION,
+            id,
+            kdf,
+            salt: BASE64.encode(salt),
+            verifier: BASE64.encode(crypto::encrypt(VERIFIER, &kek)?),
+            current: 1,
+            keys: vec![WrappedKey { id: 1, key: BASE64.encode(crypto::encrypt(&data_key, &kek)?) }],
+        };
+        Ok((vault, kek))
+    }
+
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        let vault: VaultFile = serde_json::from_slice(data).map_err(|e| format!("Invalid sync vault: {}", e))?;
+        if vault.version > VAULT_VERSION {
+            return Err("The sync vault was created by a newer version of ReClip, update to sync with it".to_string());
+        }
+        Ok(vault)
+    }
+
+    fn derive(&self, password: &str) -> Result<Key, String> {
+        let salt = BASE64.decode(&self.salt).map_err(|e| format!("Invalid sync vault: {}", e))?;
+        crypto::derive_key(password, &salt, &self.kdf)
+    }
+
+    fn verify(&self, kek: &Key) -> bool {
+        BASE64.decode(&self.verifier).ok()
+            .and_then(|sealed| crypto::decrypt(&sealed, kek).ok())
+            .is_some_and(|plain| plain == VERIFIER)
+    }
+
+    fn unwrap_keys(&self, kek: &Key) -> Result<Keyring, String> {
+        let mut keys = HashMap::new();
+        for wrapped in &self.keys {
+            let sealed = BASE64.decode(&wrapped.key).map_err(|e| format!("Invalid sync vault: {}", e))?;
+            let key: Key = crypto::decrypt(&sealed, kek)?
+                .try_into()
+                .map_err(|_| format!("Sync key {} has the wrong length", wrapped.id))?;
+            keys.insert(wrapped.id, key);
+        }
+        if !keys.contains_key(&self.current) {
+            return Err("The current sync key is missing from the vault".to_string());
+        }
+        Ok(Keyring { keys, current: self.current })
+    }
+
+    fn to_vec(&self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec_pretty(self).map_err(|e| e.to_string())
+    }
+}
+
+async fn load(backend: &dyn SyncBackend) -> Result<Option<(VaultFile, String)>, String> {
+    match backend.get(VAULT_KEY).await? {
+        Some(object) => Ok(Some((VaultFile::parse(&object.data)?, object.version))),
+        None => Ok(None),
+    }
+}
+
+/// Write a changed vault, unless another device changed it since it was read
+async fn store(backend: &dyn SyncBackend, vault: &VaultFile, version: &str) -> Result<(), String> {
+    if backend.put_if(VAULT_KEY, vault.to_vec()?, Some(version)).await? {
+        Ok(())
+    } else {
+        Err("The sync vault was changed on another device, try again".to_string())
+    }
+}
+
+pub async fn status(backend: &dyn SyncBackend) -> Result<VaultStatus, String> {
+    Ok(match load(backend).await? {
+        Some((vault, _)) => VaultStatus {
+            encrypted: true,
+            unlocked: unlocked().is_some_and(|kek| vault.verify(&kek)),
+            keys: vault.keys.len(),
+        },
+        None => VaultStatus { encrypted: false, unlocked: false, keys: 0 },
+    })
+}
+
+async fn recorded_vault(pool: &Pool<Sqlite>) -> Result<Option<String>, String> {
+    let id: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = ?")
+        .bind(VAULT_ID_SETTING)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(id.filter(|id| !id.is_empty()))
+}
+
+/// Lock and forget that the storage was encrypted, for when sync moves to another storage
+pub async fn forget(pool: &Pool<Sqlite>) -> Result<(), String> {
+    lock();
+    sqlx::query("DELETE FROM settings WHERE key = ?")
+        .bind(VAULT_ID_SETTING)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Encrypt what was synced before encryption was turned on, plain objects are refused from then on
+async fn encrypt_existing(backend: &dyn SyncBackend, keyring: &Keyring) -> Result<usize, String> {
+    let mut encrypted = 0;
+    for prefix in [crate::sync::CHANGES_PREFIX, crate::sync::BLOBS_PREFIX] {
+        for key in backend.list(prefix).await? {
+            let Some(object) = backend.get(&key).await? else { continue };
+            if is_encrypted(&object.data) {
+                continue;
+            }
+            // Skipped when it changed meanwhile, the device that wrote it has to unlock first now
+            if backend.put_if(&key, keyring.encrypt(&object.data)?, Some(&object.version)).await? {
+                encrypted += 1;
+            }
+        }
+    }
+    Ok(encrypted)
+}
+
+/// Unlock with the sync password, or turn encryption on when the storage has no vault yet
+pub async fn unlock(pool: &Pool<Sqlite>, backend: &dyn SyncBackend, password: &str) -> Result<(), String> {
+    if password.is_empty() {
+        return Err("Enter the sync password".to_string());
+    }
+    let (vault, kek, created) = match load(backend).await? {
+        Some((vault, _)) => {
+            let kek = vault.derive(password)?;
+            (vault, kek, false)
+        }
+        None => {
+            let (vault, kek) = VaultFile::create(password)?;
+            if backend.put_if(VAULT_KEY, vault.to_vec()?, None).await? {
+                (vault, kek, true)
+            } else {
+                // Another device turned encryption on at the same time, its vault wins
+                let (vault, _) = load(backend).await?.ok_or("The sync vault disappeared, try again")?;
+                let kek = vault.derive(password)?;
+                (vault, kek, false)
+            }
+        }
+    };
+    if !vault.verify(&kek) {
+        return Err("Wrong sync password".to_string());
+    }
+    let keyring = vault.unwrap_keys(&kek)?;
+    db::set_setting(pool, VAULT_ID_SETTING, &vault.id).await.map_err(|e| e.to_string())?;
+    *UNLOCKED.lock().unwrap() = Some(kek);
+    if created {
+        encrypt_existing(backend, &keyring).await
+            .map_err(|e| format!("Encryption is on, but data synced before could not be encrypted: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Rewrap the data keys for a new password, data already synced stays readable
+pub async fn change_password(backend: &dyn SyncBackend, current: &str, new: &str) -> Result<(), String> {
+    if new.is_empty() {
+        return Err("The new sync password can't be empty".to_string());
+    }
+    let (vault, version) = load(backend).await?.ok_or("Sync data is not encrypted")?;
+    let old_kek = vault.derive(current)?;
+    if !vault.verify(&old_kek) {
+        return Err("Wrong sync password".to_string());
+    }
+    let keyring = vault.unwrap_keys(&old_kek)?;
+
+    let salt = crypto::random_bytes::<SALT_LEN>()?;
+    let kek = crypto::derive_key(new, &salt, &vault.kdf)?;
+    let mut keys = Vec::with_capacity(keyring.keys.len());
+    for (id, key) in &keyring.keys {
+        keys.push(WrappedKey { id: *id, key: BASE64.encode(crypto::encrypt(key, &kek)?) });
+    }
+    keys.sort_by_key(|k| k.id);
+    let changed = VaultFile {
+        salt: BASE64.encode(salt),
+        verifier: BASE64.encode(crypto::encrypt(VERIFIER, &kek)?),
+        keys,
+        ..vault
+    };
+    store(backend, &changed, &version).await?;
+    *UNLOCKED.lock().unwrap() = Some(kek);
+    Ok(())
+}
+
+/// Add a fresh data key for everything synced from now on. Returns the number of keys in the vault.
+pub async fn rotate_key(backend: &dyn SyncBackend) -> Result<usize, String> {
+    let kek = unlocked().ok_or("Unlock sync encryption first")?;
+    let (mut vault, version) = load(backend).await?.ok_or("Sync data is not encrypted")?;
+    if !vault.verify(&kek) {
+        lock();
+        return Err("The sync password was changed on another device, unlock again".to_string());
+    }
+    let id = vault.keys.iter().map(|k| k.id).max().unwrap_or(0) + 1;
+    let data_key = crypto::random_bytes::<KEY_LEN>()?;
+    vault.keys.push(WrappedKey { id, key: BASE64.encode(crypto::encrypt(&data_key, &kek)?) });
+    vault.current = id;
+    store(backend, &vault, &version).await?;
+    Ok(vault.keys.len())
+}
+
+/// Data keys for a sync, `None` when the storage isn't encrypted and never was on this device
+pub async fn keyring(pool: &Pool<Sqlite>, backend: &dyn SyncBackend) -> Result<Option<Keyring>, String> {
+    // Earlier versions kept the sync password, it only unlocks the vault once and is then forgotten
+    if let Some(password) = secrets::get(pool, secrets::SYNC_ENCRYPTION_PASSWORD).await? {
+        if unlocked().is_none() {
+            unlock(pool, backend, &password).await?;
+        }
+        secrets::delete(pool, secrets::SYNC_ENCRYPTION_PASSWORD).await?;
+    }
+
+    let recorded = recorded_vault(pool).await?;
+    let Some((vault, _)) = load(backend).await? else {
+        return match recorded {
+            // Deleting vault.json must not turn encryption off
+            Some(_) => Err("Sync data is encrypted but vault.json is missing from the storage. \
+                Sync stays off until it is restored or a new sync password is set".to_string()),
+            None => Ok(None),
+        };
+    };
+    if recorded.is_some_and(|id| id != vault.id) {
+        lock();
+        return Err("The storage has a different sync vault than the one unlocked here, unlock it with its password".to_string());
+    }
+    let kek = unlocked().ok_or("Synced data is encrypted, unlock it with the sync password")?;
+    if !vault.verify(&kek) {
+        lock();
+        return Err("The sync password was changed on another device, unlock again".to_string());
+    }
+    vault.unwrap_keys(&kek).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_backends::FolderBackend;
+
+    // The unlocked password key is shared by the whole process
+    static SERIAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    async fn setup() -> (tempfile::TempDir, Pool<Sqlite>, FolderBackend) {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::open_db(dir.path()).await.unwrap();
+        let backend = FolderBackend::new(dir.path().join("remote"));
+        (dir, pool, backend)
+    }
+
+    #[tokio::test]
+    async fn wrong_passwords_fail_the_verifier() {
+        let _serial = SERIAL.lock().await;
+        let (_dir, pool, backend) = setup().await;
+        unlock(&pool, &backend, "correct horse").await.unwrap();
+        lock();
+
+        assert_eq!(unlock(&pool, &backend, "battery staple").await.unwrap_err(), "Wrong sync password");
+        assert!(unlocked().is_none());
+        assert!(keyring(&pool, &backend).await.is_err());
+        unlock(&pool, &backend, "correct horse").await.unwrap();
+        assert!(keyring(&pool, &backend).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn changing_the_password_rewraps_the_same_keys() {
+        let _serial = SERIAL.lock().await;
+        let (_dir, pool, backend) = setup().await;
+        unlock(&pool, &backend, "old").await.unwrap();
+        let sealed = keyring(&pool, &backend).await.unwrap().unwrap().encrypt(b"segment").unwrap();
+
+        assert!(change_password(&backend, "wrong", "new").await.is_err());
+        change_password(&backend, "old", "new").await.unwrap();
+        lock();
+        assert_eq!(unlock(&pool, &backend, "old").await.unwrap_err(), "Wrong sync password");
+        unlock(&pool, &backend, "new").await.unwrap();
+        assert_eq!(keyring(&pool, &backend).await.unwrap().unwrap().decrypt(&sealed).unwrap(), b"segment");
+    }
+
+    #[tokio::test]
+    async fn rotated_keys_still_open_old_segments() {
+        let _serial = SERIAL.lock().await;
+        let (_dir, pool, backend) = setup().await;
+        unlock(&pool, &backend, "pw").await.unwrap();
+        let old = keyring(&pool, &backend).await.unwrap().unwrap().encrypt(b"old segment").unwrap();
+
+        assert_eq!(rotate_key(&backend).await.unwrap(), 2);
+        let keyring = keyring(&pool, &backend).await.unwrap().unwrap();
+        let new = keyring.encrypt(b"new segment").unwrap();
+        assert_eq!(&new[MAGIC.len()..MAGIC.len() + 4], &2u32.to_be_bytes());
+        assert_eq!(keyring.decrypt(&old).unwrap(), b"old segment");
+        assert_eq!(keyring.decrypt(&new).unwrap(), b"new segment");
+        lock();
+    }
+
+    #[tokio::test]
+    async fn encrypted_storage_never_falls_back_to_plain() {
+        let _serial = SERIAL.lock().await;
+        let (dir, pool, backend) = setup().await;
+        db::insert_clip(&pool, "before".to_string(), "text".to_string(), "h1".to_string(), None, None).await.unwrap();
+        crate::sync::run(&pool, dir.path(), &backend).await.unwrap();
+
+        // Turning encryption on encrypts what was synced before
+        unlock(&pool, &backend, "pw").await.unwrap();
+        for key in backend.list("changes/").await.unwrap() {
+            assert!(is_encrypted(&backend.get(&key).await.unwrap().unwrap().data));
+        }
+
+        // Plain data written later by another device is refused
+        backend.put("changes/intruder/0000000001.json", b"{}".to_vec()).await.unwrap();
+        let err = crate::sync::run(&pool, dir.path(), &backend).await.unwrap_err();
+        assert!(err.contains("not encrypted"), "{}", err);
+        backend.delete("changes/intruder/0000000001.json").await.unwrap();
+        crate::sync::run(&pool, dir.path(), &backend).await.unwrap();
+
+        // Removing vault.json doesn't turn encryption off
+        backend.delete(VAULT_KEY).await.unwrap();
+        assert!(keyring(&pool, &backend).await.err().unwrap().contains("vault.json is missing"));
+        assert!(crate::sync::run(&pool, dir.path(), &backend).await.is_err());
+        lock();
+    }
+}
@@ -1,4 +1,5 @@
-use tauri::Manager;
+use tauri::{Manager, State};
+use crate::db::{self, DbState};
 use crate::ocr;
 use log::{info, error};
 
@@ -76,10 +77,26 @@ pub async fn validate_paths(content: String) -> Vec<(String, bool, bool)> {
 }
 
 #[tauri::command]
-pub async fn paste_clip_to_system(app_handle: tauri::AppHandle, content: String, clip_type: String) -> Result<(), String> {
+pub async fn paste_clip_to_system(app_handle: tauri::AppHandle, state: State<'_, DbState>, content: String, clip_type: String, clip_id: Option<i64>) -> Result<(), String> {
     let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+
+    // Everything the source app offered, for clips pasted unmodified
+    let stored_formats = match clip_id {
+        Some(id) => db::get_clip_formats(&state.pool, id).await.unwrap_or_default(),
+        None => Vec::new(),
+    };
+    let formats: Vec<(String, Vec<u8>)> = stored_formats.iter()
+        .filter_map(|f| Some((f.mime.clone(), crate::clipboard::clip_format_data(f)?)))
+        .collect();
     
-    if clip_type == "files" {
+    if !formats.is_empty() {
+        let fallback_text = match clip_type.as_str() {
+            "html" => html2text::from_read(content.as_bytes(), 80).unwrap_or(content.clone()),
+            "files" => serde_json::from_str::<Vec<String>>(&content).map(|paths| paths.join("\n")).unwrap_or(content.clone()),
+            _ => content.clone(),
+        };
+        restore_clip_formats(formats, &fallback_text)?;
+    } else if clip_type == "files" {
         #[cfg(target_os = "windows")]
         {
             use clipboard_rs::{Clipboard, ClipboardContext};
@@ -90,7 +107,12 @@ pub async fn paste_clip_to_system(app_handle: tauri::AppHandle, content: String,
                  return Err("Invalid file list format".to_string());
             }
         }
-        #[cfg(not(target_os = "windows"))]
+        #[cfg(target_os = "linux")]
+        {
+            let paths = serde_json::from_str::<Vec<String>>(&content).map_err(|_| "Invalid file list format".to_string())?;
+            crate::linux_clipboard::write(crate::linux_clipboard::file_list_formats(&paths)?)?;
+        }
+        #[cfg(not(any(target_os = "windows", target_os = "linux")))]
         {
              return Err("File pasting not supported on this OS".to_string());
         }
@@ -105,18 +127,16 @@ pub async fn paste_clip_to_system(app_handle: tauri::AppHandle, content: String,
         };
         clipboard.set_image(image_data).map_err(|e| e.to_string())?;
     } else if clip_type == "html" {
-        #[cfg(target_os = "windows")]
+        let plain_text = html2text::from_read(content.as_bytes(), 80).unwrap_or(content.clone());
+        #[cfg(any(target_os = "windows", target_os = "macos"))]
         {
-            use clipboard_rs::{Clipboard, ClipboardContext};
+            use clipboard_rs::{Clipboard, ClipboardContent, ClipboardContext};
             let ctx = ClipboardContext::new().map_err(|e| e.to_string())?;
-            let plain_text = html2text::from_read(content.as_bytes(), 80).unwrap_or(content.clone()); 
-            ctx.set_text(plain_text.clone()).map_err(|e| e.to_string())?;
-            ctx.set_html(content.clone()).map_err(|e| e.to_string())?;
+            ctx.set(vec![ClipboardContent::Text(plain_text), ClipboardContent::Html(content.clone())]).map_err(|e| e.to_string())?;
         }
-        #[cfg(not(target_os = "windows"))]
+        #[cfg(target_os = "linux")]
         {
-            let plain_text = html2text::from_read(content.as_bytes(), 80).unwrap_or(content); 
-            clipboard.set_text(plain_text).map_err(|e| e.to_string())?;
+            crate::linux_clipboard::write(crate::linux_clipboard::rich_text_formats(Some(&content), &plain_text))?;
         }
     } else {
         clipboard.set_text(content.clone()).map_err(|e| e.to_string())?;
@@ -153,6 +173,31 @@ pub async fn paste_clip_to_system(app_handle: tauri::AppHandle, content: String,
     Ok(())
 }
 
+/// Put stored clipboard formats back at once so the target app picks its preferred one
+fn restore_clip_formats(formats: Vec<(String, Vec<u8>)>, fallback_text: &str) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        crate::linux_clipboard::write(crate::linux_clipboard::with_plain_text(formats, fallback_text))
+    }
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        use clipboard_rs::{Clipboard, ClipboardContent, ClipboardContext};
+        // Plain text goes through the typed API, the system converts it between its text formats
+        const TEXT_FORMATS: [&str; 5] = ["public.utf8-plain-text", "NSStringPboardType", "CF_TEXT", "CF_UNICODETEXT", "CF_OEMTEXT"];
+        let ctx = ClipboardContext::new().map_err(|e| e.to_string())?;
+        let mut contents = vec![ClipboardContent::Text(fallback_text.to_string())];
+        contents.extend(formats.into_iter()
+            .filter(|(format, _)| !TEXT_FORMATS.contains(&format.as_str()))
+            .map(|(format, data)| ClipboardContent::Other(format, data)));
+        ctx.set(contents).map_err(|e| e.to_string())
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        let _ = (formats, fallback_text);
+        Err("Restoring clipboard formats is not supported on this OS".to_string())
+    }
+}
+
 #[tauri::command]
 pub async fn get_app_data_path(app: tauri::AppHandle) -> Result<String, String> {
     app.path().app_data_dir()
@@ -174,7 +174,7 @@ pub fn create_tray<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<()> {
                             let db_state = app.state::<crate::db::DbState>();
                             let pool = db_state.pool.clone();
                             tauri::async_runtime::spawn(async move {
-                                if let Ok(clips) = crate::db::get_clips(&pool, 10, 0, None, None, false).await {
+                                if let Ok(clips) = crate::db::get_clips(&pool, 10, 0, None, None, false, true).await {
                                     if let Some(clip) = clips.get(index) {
                                         if clip.type_ != "image" {
                                             if let Ok(mut cb) = arboard::Clipboard::new() {
@@ -220,7 +220,7 @@ pub fn update_item_state<R: Runtime>(state: tauri::State<'_, TrayState<R>>, id:
 
 pub async fn update_tray_history<R: tauri::Runtime>(app: &AppHandle<R>) -> Result<(), String> {
     let state = app.state::<crate::db::DbState>();
-    if let Ok(clips) = crate::db::get_clips(&state.pool, 10, 0, None, None, false).await {
+    if let Ok(clips) = crate::db::get_clips(&state.pool, 10, 0, None, None, false, true).await {
         let tray_clips: Vec<(i64, String, String)> = clips.iter().map(|c| (c.id, c.content.clone(), c.type_.clone())).collect();
         update_tray_clips(app, tray_clips);
     }
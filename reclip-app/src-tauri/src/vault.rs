@@ -0,0 +1,550 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use log::info;
+use serde::Serialize;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use tauri::{AppHandle, Emitter, Runtime};
+use crate::crypto::{self, KdfParams, KEY_LEN};
+use crate::db::get_setting;
+
+// Vault mode encrypts clip content, tags and recognized text, stored clip formats, and snippet bodies
+// and their history. Clip hashes are keyed with the vault key. Stored in the clear: image files and
+// thumbnails in the blob store (image clips only hold their path) with their plain hashes, notes,
+// snippet titles, dates, flags and source app names.
+
+// Encrypted field values start with this, anything else was stored before vault mode was turned on.
+// The search index triggers test for the same prefix.
+const PREFIX: &str = "enc:v1:";
+// Derives the key for clip hashes from the vault key
+const HASH_CONTEXT: &str = "reclip 2026-02 vault clip hashes";
+const VERIFIER: &str = "reclip vault";
+const SALT_LEN: usize = 16;
+const DEFAULT_AUTO_LOCK_MINUTES: u64 = 15;
+
+type Key = [u8; KEY_LEN];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static KEY: LazyLock<RwLock<Option<Key>>> = LazyLock::new(|| RwLock::new(None));
+// Unix seconds of the last user activity in the app, and minutes of inactivity before locking (0 = never)
+static LAST_ACTIVITY: AtomicU64 = AtomicU64::new(0);
+static AUTO_LOCK_MINUTES: AtomicU64 = AtomicU64::new(DEFAULT_AUTO_LOCK_MINUTES);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+    pub auto_lock_minutes: u64,
+}
+
+/// Read the vault settings, after the migrations ran. Starts locked.
+pub async fn init(pool: &Pool<Sqlite>) {
+    ENABLED.store(get_setting(pool, "vault_verifier").await.is_some_and(|v| !v.is_empty()), Ordering::SeqCst);
+    if let Some(minutes) = get_setting(pool, "vault_auto_lock_minutes").await.and_then(|m| m.parse().ok()) {
+        AUTO_LOCK_MINUTES.store(minutes, Ordering::SeqCst);
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Enabled and not unlocked: nothing can be read or stored
+pub fn is_locked() -> bool {
+    is_enabled() && KEY.read().unwrap().is_none()
+}
+
+pub fn status() -> VaultStatus {
+    VaultStatus {
+        enabled: is_enabled(),
+        unlocked: !is_locked(),
+        auto_lock_minutes: AUTO_LOCK_MINUTES.load(Ordering::SeqCst),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Record user activity, postponing auto-lock
+pub fn touch() {
+    LAST_ACTIVITY.store(now(), Ordering::SeqCst);
+}
+
+fn key() -> Result<Option<Key>, String> {
+    if !is_enabled() {
+        return Ok(None);
+    }
+    match *KEY.read().unwrap() {
+        Some(key) => Ok(Some(key)),
+        None => Err("ReClip is locked, unlock it with the vault password".to_string()),
+    }
+}
+
+fn seal_text(key: &Key, plain: &str) -> Result<String, String> {
+    Ok(format!("{}{}", PREFIX, BASE64.encode(crypto::encrypt(plain.as_bytes(), key)?)))
+}
+
+fn open_text(key: &Key, stored: &str) -> Result<String, String> {
+    let Some(encoded) = stored.strip_prefix(PREFIX) else { return Ok(stored.to_string()) };
+    let sealed = BASE64.decode(encoded).map_err(|e| format!("Corrupted encrypted field: {}", e))?;
+    String::from_utf8(crypto::decrypt(&sealed, key)?).map_err(|e| e.to_string())
+}
+
+fn seal_bytes(key: &Key, plain: &[u8]) -> Result<Vec<u8>, String> {
+    let mut sealed = PREFIX.as_bytes().to_vec();
+    sealed.extend_from_slice(&crypto::encrypt(plain, key)?);
+    Ok(sealed)
+}
+
+fn open_bytes(key: &Key, stored: &[u8]) -> Result<Vec<u8>, String> {
+    match stored.strip_prefix(PREFIX.as_bytes()) {
+        Some(sealed) => crypto::decrypt(sealed, key),
+        None => Ok(stored.to_vec()),
+    }
+}
+
+fn keyed_hash(key: &Key, hash: &str) -> String {
+    blake3::keyed_hash(&blake3::derive_key(HASH_CONTEXT, key), hash.as_bytes()).to_hex().to_string()
+}
+
+/// The hash a clip is stored under: its content hash, keyed in vault mode so it can't confirm a guess
+pub fn clip_hash(hash: &str) -> Result<String, String> {
+    match key()? {
+        Some(key) => Ok(keyed_hash(&key, hash)),
+        None => Ok(hash.to_string()),
+    }
+}
+
+/// Encrypt a field for storing, unchanged when vault mode is off
+pub fn encrypt_text(plain: &str) -> Result<String, String> {
+    match key()? {
+        Some(key) => seal_text(&key, plain),
+        None => Ok(plain.to_string()),
+    }
+}
+
+/// Decrypt a stored field, plain values pass through
+pub fn decrypt_text(stored: &str) -> Result<String, String> {
+    if !stored.starts_with(PREFIX) {
+        return Ok(stored.to_string());
+    }
+    match key()? {
+        Some(key) => open_text(&key, stored),
+        None => Err("The database holds encrypted clips but vault mode is off".to_string()),
+    }
+}
+
+pub fn encrypt_bytes(plain: &[u8]) -> Result<Vec<u8>, String> {
+    match key()? {
+        Some(key) => seal_bytes(&key, plain),
+        None => Ok(plain.to_vec()),
+    }
+}
+
+pub fn decrypt_bytes(stored: &[u8]) -> Result<Vec<u8>, String> {
+    if !stored.starts_with(PREFIX.as_bytes()) {
+        return Ok(stored.to_vec());
+    }
+    match key()? {
+        Some(key) => open_bytes(&key, stored),
+        None => Err("The database holds encrypted clips but vault mode is off".to_string()),
+    }
+}
+
+/// Derive the key and check it against the stored verifier
+async fn derive(pool: &Pool<Sqlite>, password: &str) -> Result<Key, String> {
+    let salt = get_setting(pool, "vault_salt").await.ok_or("Vault mode is not set up")?;
+    let salt = BASE64.decode(salt).map_err(|e| format!("Invalid vault salt: {}", e))?;
+    let kdf: KdfParams = get_setting(pool, "vault_kdf").await
+        .and_then(|k| serde_json::from_str(&k).ok())
+        .unwrap_or_default();
+    let verifier = get_setting(pool, "vault_verifier").await.ok_or("Vault mode is not set up")?;
+
+    let key = crypto::derive_key(password, &salt, &kdf)?;
+    match open_text(&key, &verifier) {
+        Ok(plain) if plain == VERIFIER => Ok(key),
+        _ => Err("Wrong vault password".to_string()),
+    }
+}
+
+pub async fn unlock(pool: &Pool<Sqlite>, password: &str) -> Result<(), String> {
+    if !is_enabled() {
+        return Err("Vault mode is off".to_string());
+    }
+    let key = derive(pool, password).await?;
+    *KEY.write().unwrap() = Some(key);
+    touch();
+    Ok(())
+}
+
+pub fn lock() {
+    *KEY.write().unwrap() = None;
+}
+
+pub async fn set_auto_lock(pool: &Pool<Sqlite>, minutes: u64) -> Result<(), sqlx::Error> {
+    crate::db::set_setting(pool, "vault_auto_lock_minutes", &minutes.to_string()).await?;
+    AUTO_LOCK_MINUTES.store(minutes, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Encrypt every clip, format and snippet in place and turn vault mode on. Returns the number of rows changed.
+pub async fn enable(pool: &Pool<Sqlite>, password: &str) -> Result<usize, String> {
+    if is_enabled() {
+        return Err("Vault mode is already on".to_string());
+    }
+    if password.is_empty() {
+        return Err("Choose a vault password".to_string());
+    }
+    let kdf = KdfParams::default();
+    let salt = crypto::random_bytes::<SALT_LEN>()?;
+    let key = crypto::derive_key(password, &salt, &kdf)?;
+    let verifier = seal_text(&key, VERIFIER)?;
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let changed = migrate(&mut tx, &key, true).await?;
+    for (setting, value) in [
+        ("vault_salt", BASE64.encode(salt)),
+        ("vault_kdf", serde_json::to_string(&kdf).map_err(|e| e.to_string())?),
+        ("vault_verifier", verifier),
+    ] {
+        save_setting(&mut tx, setting, &value).await.map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    *KEY.write().unwrap() = Some(key);
+    ENABLED.store(true, Ordering::SeqCst);
+    touch();
+    // Clips captured while the migration held the database were stored in plain
+    let changed = changed + migrate_again(pool, &key, true).await?;
+    compact(pool).await;
+    info!("Vault mode on, encrypted {} rows", changed);
+    Ok(changed)
+}
+
+/// Decrypt everything in place and turn vault mode off
+pub async fn disable(pool: &Pool<Sqlite>, password: &str) -> Result<usize, String> {
+    if !is_enabled() {
+        return Err("Vault mode is off".to_string());
+    }
+    let key = derive(pool, password).await?;
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let changed = migrate(&mut tx, &key, false).await?;
+    sqlx::query("DELETE FROM settings WHERE key IN ('vault_salt', 'vault_kdf', 'vault_verifier')")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    ENABLED.store(false, Ordering::SeqCst);
+    lock();
+    let changed = changed + migrate_again(pool, &key, false).await?;
+    info!("Vault mode off, decrypted {} rows", changed);
+    Ok(changed)
+}
+
+async fn migrate_again(pool: &Pool<Sqlite>, key: &Key, encrypt: bool) -> Result<usize, String> {
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let changed = migrate(&mut tx, key, encrypt).await?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(changed)
+}
+
+async fn save_setting(conn: &mut SqliteConnection, key: &str, value: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO settings (key, value) VALUES (?, ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value")
+        .bind(key)
+        .bind(value)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Rewrite the encrypted fields, skipping values already in the target state
+async fn migrate(conn: &mut SqliteConnection, key: &Key, encrypt: bool) -> Result<usize, String> {
+    let text = |value: &str| -> Result<Option<String>, String> {
+        match (encrypt, value.starts_with(PREFIX)) {
+            (true, false) => seal_text(key, value).map(Some),
+            (false, true) => open_text(key, value).map(Some),
+            _ => Ok(None),
+        }
+    };
+    let db = |e: sqlx::Error| e.to_string();
+    let mut changed = 0;
+
+    // Re-encrypting changes nothing the other devices need to know
+    sqlx::query("INSERT INTO sync_paused (reason) VALUES ('vault')").execute(&mut *conn).await.map_err(db)?;
+
+    // Image clips hold the path of their blob, the files themselves are not encrypted
+    let clips: Vec<(i64, String, Option<String>)> = sqlx::query_as("SELECT id, content, tags FROM clips WHERE type != 'image'")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db)?;
+    for (id, content, tags) in clips {
+        let new_content = text(&content)?;
+        let new_tags = match &tags {
+            Some(tags) => text(tags)?,
+            None => None,
+        };
+        if new_content.is_none() && new_tags.is_none() {
+            continue;
+        }
+        sqlx::query("UPDATE clips SET content = ?, tags = ? WHERE id = ?")
+            .bind(new_content.unwrap_or(content))
+            .bind(new_tags.or(tags))
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(db)?;
+        changed += 1;
+    }
+
+    // Text recognized in images is encrypted even though the images aren't
+    let ocr_texts: Vec<(i64, String)> = sqlx::query_as("SELECT id, ocr_text FROM clips WHERE ocr_text IS NOT NULL")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db)?;
+    for (id, ocr_text) in ocr_texts {
+        if let Some(new_text) = text(&ocr_text)? {
+            sqlx::query("UPDATE clips SET ocr_text = ? WHERE id = ?").bind(new_text).bind(id).execute(&mut *conn).await.map_err(db)?;
+            changed += 1;
+        }
+    }
+
+    let formats: Vec<(i64, Vec<u8>)> = sqlx::query_as("SELECT id, data FROM clip_formats WHERE data IS NOT NULL")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db)?;
+    for (id, data) in formats {
+        let new_data = match (encrypt, data.starts_with(PREFIX.as_bytes())) {
+            (true, false) => seal_bytes(key, &data)?,
+            (false, true) => open_bytes(key, &data)?,
+            _ => continue,
+        };
+        sqlx::query("UPDATE clip_formats SET data = ? WHERE id = ?").bind(new_data).bind(id).execute(&mut *conn).await.map_err(db)?;
+        changed += 1;
+    }
+
+    let snippets: Vec<(i64, String, Option<String>)> = sqlx::query_as("SELECT id, content, version_history FROM snippets")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db)?;
+    for (id, content, history) in snippets {
+        let new_content = text(&content)?;
+        let new_history = match &history {
+            Some(history) => text(history)?,
+            None => None,
+        };
+        if new_content.is_none() && new_history.is_none() {
+            continue;
+        }
+        sqlx::query("UPDATE snippets SET content = ?, version_history = ? WHERE id = ?")
+            .bind(new_content.unwrap_or(content))
+            .bind(new_history.or(history))
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(db)?;
+        changed += 1;
+    }
+
+    changed += if encrypt { key_hashes(conn, key).await? } else { unkey_hashes(conn, key).await? };
+
+    sqlx::query("DELETE FROM sync_paused").execute(&mut *conn).await.map_err(db)?;
+    Ok(changed)
+}
+
+/// Key the clip hashes, keeping the plain ones encrypted in `sync_keys` for sync. Hashes of deleted
+/// clips that sync still tracks are keyed too.
+async fn key_hashes(conn: &mut SqliteConnection, key: &Key) -> Result<usize, String> {
+    let db = |e: sqlx::Error| e.to_string();
+    // The ones keyed already are in sync_keys
+    let hashes: Vec<String> = sqlx::query_scalar(
+        "SELECT hash FROM clips WHERE hash NOT IN (SELECT hash FROM sync_keys)
+         UNION SELECT key FROM sync_versions WHERE entity = 'clip' AND key NOT IN (SELECT hash FROM sync_keys)
+         UNION SELECT key FROM sync_dirty WHERE entity = 'clip' AND key NOT IN (SELECT hash FROM sync_keys)")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db)?;
+    for hash in &hashes {
+        let keyed = keyed_hash(key, hash);
+        sqlx::query("INSERT OR REPLACE INTO sync_keys (hash, sync_key) VALUES (?, ?)")
+            .bind(&keyed)
+            .bind(seal_text(key, hash)?)
+            .execute(&mut *conn)
+            .await
+            .map_err(db)?;
+        rename_hash(conn, hash, &keyed).await.map_err(db)?;
+    }
+    Ok(hashes.len())
+}
+
+async fn unkey_hashes(conn: &mut SqliteConnection, key: &Key) -> Result<usize, String> {
+    let db = |e: sqlx::Error| e.to_string();
+    let keys: Vec<(String, String)> = sqlx::query_as("SELECT hash, sync_key FROM sync_keys")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db)?;
+    for (keyed, sync_key) in &keys {
+        rename_hash(conn, keyed, &open_text(key, sync_key)?).await.map_err(db)?;
+    }
+    sqlx::query("DELETE FROM sync_keys").execute(&mut *conn).await.map_err(db)?;
+    Ok(keys.len())
+}
+
+async fn rename_hash(conn: &mut SqliteConnection, from: &str, to: &str) -> Result<(), sqlx::Error> {
+    for statement in [
+        "UPDATE clips SET hash = ?2 WHERE hash = ?1",
+        "UPDATE sync_versions SET key = ?2 WHERE entity = 'clip' AND key = ?1",
+        "UPDATE sync_dirty SET key = ?2 WHERE entity = 'clip' AND key = ?1",
+    ] {
+        sqlx::query(statement).bind(from).bind(to).execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Drop the plaintext left in deleted index entries and free pages
+async fn compact(pool: &Pool<Sqlite>) {
+    for statement in [
+        "INSERT INTO clips_fts (clips_fts) VALUES ('optimize')",
+        "INSERT INTO snippets_fts (snippets_fts) VALUES ('optimize')",
+        "VACUUM",
+    ] {
+        if let Err(e) = sqlx::query(statement).execute(pool).await {
+            log::warn!("Vault compaction step failed ({}): {}", statement, e);
+        }
+    }
+}
+
+/// Lock and tell the webview, which shows the unlock screen
+pub fn lock_app<R: Runtime>(app: &AppHandle<R>) {
+    lock();
+    let _ = app.emit("vault-locked", ());
+    #[cfg(desktop)]
+    crate::tray::update_tray_clips(app, Vec::new());
+}
+
+/// Lock after the configured minutes without activity
+pub fn start_auto_lock<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            let minutes = AUTO_LOCK_MINUTES.load(Ordering::SeqCst);
+            if minutes == 0 || !is_enabled() || is_locked() {
+                continue;
+            }
+            if now().saturating_sub(LAST_ACTIVITY.load(Ordering::SeqCst)) >= minutes * 60 {
+                info!("Locking after {} idle minutes", minutes);
+                lock_app(&app);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    // These call `migrate` with their own key: vault mode itself is process wide and stays off
+    async fn matches(pool: &Pool<Sqlite>, word: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM clips_fts WHERE clips_fts MATCH ?").bind(word).fetch_one(pool).await.unwrap()
+    }
+
+    async fn run_migrate(pool: &Pool<Sqlite>, key: &Key, encrypt: bool) -> usize {
+        let mut tx = pool.begin().await.unwrap();
+        let changed = migrate(&mut tx, key, encrypt).await.unwrap();
+        tx.commit().await.unwrap();
+        changed
+    }
+
+    #[tokio::test]
+    async fn enabling_and_disabling_round_trips_every_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::open_db(dir.path()).await.unwrap();
+        let key = crypto::random_bytes::<KEY_LEN>().unwrap();
+        let text = db::insert_clip(&pool, "launch code 0000".to_string(), "text".to_string(), "h-text".to_string(), Some("[\"#ops\"]".to_string()), None).await.unwrap();
+        db::insert_clip_format(&pool, text, "text/html", Some(b"<b>launch</b>".to_vec()), None, 13).await.unwrap();
+        let image = db::insert_clip(&pool, "/blobs/ab/shot.png".to_string(), "image".to_string(), "h-image".to_string(), None, None).await.unwrap();
+        db::set_clip_ocr_text(&pool, image, "quarterly figures".to_string()).await.unwrap();
+        db::add_snippet(&pool, "Deploy".to_string(), "kubectl apply".to_string(), "bash".to_string(), "[]".to_string(), String::new(), String::new()).await.unwrap();
+        let row = |id: i64| {
+            let pool = pool.clone();
+            async move {
+                sqlx::query_as::<_, (String, String, Option<String>, Option<String>)>("SELECT hash, content, tags, ocr_text FROM clips WHERE id = ?")
+                    .bind(id).fetch_one(&pool).await.unwrap()
+            }
+        };
+
+        assert!(run_migrate(&pool, &key, true).await > 0);
+        assert_eq!(run_migrate(&pool, &key, true).await, 0);
+
+        let (hash, content, tags, _) = row(text).await;
+        assert_eq!(hash, keyed_hash(&key, "h-text"));
+        assert!(content.starts_with(PREFIX) && tags.unwrap().starts_with(PREFIX));
+        assert_eq!(open_text(&key, &content).unwrap(), "launch code 0000");
+        let (hash, content, _, ocr_text) = row(image).await;
+        assert_eq!(hash, keyed_hash(&key, "h-image"));
+        assert_eq!(content, "/blobs/ab/shot.png");
+        assert!(ocr_text.unwrap().starts_with(PREFIX));
+        let format: Vec<u8> = sqlx::query_scalar("SELECT data FROM clip_formats WHERE clip_id = ?").bind(text).fetch_one(&pool).await.unwrap();
+        assert!(format.starts_with(PREFIX.as_bytes()));
+        let snippet: String = sqlx::query_scalar("SELECT content FROM snippets").fetch_one(&pool).await.unwrap();
+        assert!(snippet.starts_with(PREFIX));
+        // Sync still sends the plain hash
+        let dirty: Vec<String> = sqlx::query_scalar("SELECT key FROM sync_dirty WHERE entity = 'clip' ORDER BY key").fetch_all(&pool).await.unwrap();
+        let mut keyed = vec![keyed_hash(&key, "h-image"), keyed_hash(&key, "h-text")];
+        keyed.sort();
+        assert_eq!(dirty, keyed);
+        let sync_key: String = sqlx::query_scalar("SELECT sync_key FROM sync_keys WHERE hash = ?").bind(keyed_hash(&key, "h-text")).fetch_one(&pool).await.unwrap();
+        assert_eq!(open_text(&key, &sync_key).unwrap(), "h-text");
+        assert_eq!(matches(&pool, "launch").await, 0);
+        assert_eq!(matches(&pool, "quarterly").await, 0);
+
+        run_migrate(&pool, &key, false).await;
+        assert_eq!(row(text).await, ("h-text".to_string(), "launch code 0000".to_string(), Some("[\"#ops\"]".to_string()), None));
+        assert_eq!(row(image).await.3.as_deref(), Some("quarterly figures"));
+        let format: Vec<u8> = sqlx::query_scalar("SELECT data FROM clip_formats WHERE clip_id = ?").bind(text).fetch_one(&pool).await.unwrap();
+        assert_eq!(format, b"<b>launch</b>");
+        let snippet: String = sqlx::query_scalar("SELECT content FROM snippets").fetch_one(&pool).await.unwrap();
+        assert_eq!(snippet, "kubectl apply");
+        let sync_keys: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sync_keys").fetch_one(&pool).await.unwrap();
+        assert_eq!(sync_keys, 0);
+        assert_eq!(matches(&pool, "launch").await, 1);
+        assert_eq!(matches(&pool, "quarterly").await, 1);
+    }
+
+    #[test]
+    fn values_without_the_prefix_pass_through() {
+        let key = crypto::random_bytes::<KEY_LEN>().unwrap();
+        assert_eq!(open_text(&key, "stored before vault mode").unwrap(), "stored before vault mode");
+        assert_eq!(open_bytes(&key, b"\x89PNG").unwrap(), b"\x89PNG");
+        assert_eq!(decrypt_text("plain").unwrap(), "plain");
+        assert_eq!(decrypt_bytes(b"plain").unwrap(), b"plain");
+
+        let sealed = seal_text(&key, "secret").unwrap();
+        assert!(sealed.starts_with(PREFIX));
+        assert_eq!(open_text(&key, &sealed).unwrap(), "secret");
+        assert!(open_text(&crypto::random_bytes::<KEY_LEN>().unwrap(), &sealed).is_err());
+    }
+
+    #[tokio::test]
+    async fn encrypted_values_are_indexed_as_empty_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::open_db(dir.path()).await.unwrap();
+        sqlx::query("INSERT INTO clips (content, type, hash, tags, ocr_text) VALUES ('enc:v1:bGF1bmNo', 'text', 'h', 'enc:v1:b3Bz', 'enc:v1:cXVhcnRlcmx5')")
+            .execute(&pool).await.unwrap();
+        for word in ["enc", "v1", "bGF1bmNo", "b3Bz", "cXVhcnRlcmx5"] {
+            assert_eq!(matches(&pool, word).await, 0, "{}", word);
+        }
+
+        // Updating and deleting remove the same (empty) entries that were indexed
+        sqlx::query("UPDATE clips SET content = 'launch' WHERE hash = 'h'").execute(&pool).await.unwrap();
+        assert_eq!(matches(&pool, "launch").await, 1);
+        sqlx::query("DELETE FROM clips WHERE hash = 'h'").execute(&pool).await.unwrap();
+        assert_eq!(matches(&pool, "launch").await, 0);
+        sqlx::query("INSERT INTO clips_fts (clips_fts) VALUES ('integrity-check')").execute(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+use tauri::{AppHandle, State};
+use crate::db::DbState;
+use crate::vault::{self, VaultStatus};
+
+#[tauri::command]
+pub fn get_vault_status() -> VaultStatus {
+    vault::status()
+}
+
+#[tauri::command]
+pub async fn unlock_vault(app: AppHandle, state: State<'_, DbState>, password: String) -> Result<(), String> {
+    vault::unlock(&state.pool, &password).await?;
+    let _ = crate::tray::update_tray_history(&app).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn lock_vault(app: AppHandle) {
+    vault::lock_app(&app);
+}
+
+/// Called by the UI on user input, keeps auto-lock from firing while the app is in use
+#[tauri::command]
+pub fn vault_activity() {
+    vault::touch();
+}
+
+#[tauri::command]
+pub async fn set_vault_auto_lock(state: State<'_, DbState>, minutes: u64) -> Result<(), String> {
+    vault::set_auto_lock(&state.pool, minutes).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn enable_vault(state: State<'_, DbState>, password: String) -> Result<String, String> {
+    let changed = vault::enable(&state.pool, &password).await?;
+    Ok(format!("Encrypted {} items", changed))
+}
+
+#[tauri::command]
+pub async fn disable_vault(state: State<'_, DbState>, password: String) -> Result<String, String> {
+    let changed = vault::disable(&state.pool, &password).await?;
+    Ok(format!("Decrypted {} items", changed))
+}
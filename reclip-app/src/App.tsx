@@ -1,4 +1,4 @@
-import { useState, useEffect } from "react";
+import { useState, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import "./App.css";
 import MainView from "./components/MainView";
@@ -10,8 +10,10 @@ import TitleBar from "./components/TitleBar";
 import GlobalSearch from "./components/GlobalSearch";
 import Onboarding from "./components/Onboarding";
 import PinLock from "./components/PinLock";
+import VaultLock from "./components/VaultLock";
 import { useSettingsStore } from "./store/useSettingsStore";
 import { useClipStore } from "./store/useClipStore";
+import { DbVaultStatus } from "./types";
 
 function App() {
   const {
@@ -32,6 +34,8 @@ function App() {
   const { pasteQueue } = useClipStore();
   const [showGlobalSearch, setShowGlobalSearch] = useState(false);
   const [standaloneId, setStandaloneId] = useState<{ type: 'snippet' | 'note', id: number } | null>(null);
+  const [vaultLocked, setVaultLocked] = useState(false);
+  const lastVaultActivity = useRef(0);
 
   useEffect(() => {
     const params = new URLSearchParams(window.location.search);
@@ -50,6 +54,40 @@ function App() {
     loadListenToSelf();
   }, [loadIncognito, loadListenToSelf]);
 
+  // Vault mode: start locked, lock again when the backend says so and drop decrypted clips from memory
+  useEffect(() => {
+    invoke<DbVaultStatus>('get_vault_status')
+      .then(status => setVaultLocked(status.enabled && !status.unlocked))
+      .catch(e => console.error(e));
+
+    let unlisten: (() => void) | null = null;
+    import('@tauri-apps/api/event').then(async ({ listen }) => {
+      unlisten = await listen('vault-locked', () => {
+        setVaultLocked(true);
+        useClipStore.getState().setClips([]);
+        useClipStore.getState().setAllClips([]);
+      });
+    });
+    return () => { if (unlisten) unlisten(); };
+  }, []);
+
+  // Report activity so auto-lock only fires when idle, at most every 30 seconds
+  useEffect(() => {
+    const handleActivity = () => {
+      if (vaultLocked) return;
+      const now = Date.now();
+      if (now - lastVaultActivity.current < 30000) return;
+      lastVaultActivity.current = now;
+      invoke('vault_activity').catch(() => { });
+    };
+    window.addEventListener('keydown', handleActivity);
+    window.addEventListener('mousedown', handleActivity);
+    return () => {
+      window.removeEventListener('keydown', handleActivity);
+      window.removeEventListener('mousedown', handleActivity);
+    };
+  }, [vaultLocked]);
+
   // Theme & Accent Effect
   useEffect(() => {
     applyTheme();
@@ -301,8 +339,9 @@ function App() {
 
   return (
     <div className="app-container">
-      {isLocked && <PinLock onUnlock={() => setIsLocked(false)} />}
-      {showOnboarding && !isLocked && <Onboarding onComplete={() => setShowOnboarding(false)} />}
+      {vaultLocked && <VaultLock onUnlock={() => setVaultLocked(false)} />}
+      {isLocked && !vaultLocked && <PinLock onUnlock={() => setIsLocked(false)} />}
+      {showOnboarding && !isLocked && !vaultLocked && <Onboarding onComplete={() => setShowOnboarding(false)} />}
       <TitleBar
         incognitoMode={incognitoMode}
         toggleIncognito={toggleIncognito}
@@ -320,7 +359,7 @@ function App() {
         onOpenColors={() => setView('colors')}
         onOpenOrganizer={() => setView('organizer')}
       />
-      {view === 'main' ? (
+      {vaultLocked ? null : view === 'main' ? (
         <MainView />
       ) : view === 'snippets' ? (
         <SnippetsPage theme={theme} />
@@ -40,7 +40,7 @@ const QuickView = () => {
             e.preventDefault();
             const clip = clips[selectedIndex];
             if (clip) {
-                await invoke("paste_clip_to_system", { content: clip.content, clipType: clip.type });
+                await invoke("paste_clip_to_system", { content: clip.content, clipType: clip.type, clipId: clip.id });
                 await getCurrentWindow().hide();
             }
         } else if (e.key === 'Escape') {
@@ -78,7 +78,7 @@ const QuickView = () => {
                         className={`p-2 rounded cursor-pointer flex items-center gap-2 text-sm transition-colors ${index === selectedIndex ? 'bg-indigo-600 text-white' : 'hover:bg-white/5 opacity-80'
                             }`}
                         onClick={() => {
-                            invoke("paste_clip_to_system", { content: clip.content, clipType: clip.type });
+                            invoke("paste_clip_to_system", { content: clip.content, clipType: clip.type, clipId: clip.id });
                             getCurrentWindow().hide();
                         }}
                         onMouseEnter={() => setSelectedIndex(index)}
@@ -5,6 +5,7 @@ import { Clip } from '../types';
 import ClipContent, { ImageMetadata, ImageColorPalette } from './ClipContent';
 import { convertFileSrc, invoke } from '@tauri-apps/api/core';
 import UrlPreview from './UrlPreview';
+import Highlight from './Highlight';
 import './ClipCard.css';
 
 interface ClipCardProps {
@@ -118,7 +119,9 @@ export const ClipCard: React.FC<ClipCardProps> = ({
                                 )}
                                 {clip.sensitive && (
                                     <span
-                                        title="Sensitive - Auto-deletes in 30 seconds"
+                                        title={clip.sensitive_detector
+                                            ? `Sensitive: ${clip.sensitive_detector} (${Math.round((clip.sensitive_confidence ?? 1) * 100)}% confidence)`
+                                            : "Sensitive"}
                                         style={{
                                             fontSize: '0.65rem',
                                             background: 'rgba(239, 68, 68, 0.15)',
@@ -321,6 +324,24 @@ export const ClipCard: React.FC<ClipCardProps> = ({
                                                     📋 Copy as Text
                                                 </button>
                                             )}
+                                            {clip.has_original && (
+                                                <button
+                                                    className="menu-item-btn"
+                                                    onClick={async () => {
+                                                        setActiveMenuId(null);
+                                                        if (!confirm("Show the original text that a privacy filter masked?")) return;
+                                                        try {
+                                                            const original = await invoke<string>('reveal_redacted', { id: clip.id });
+                                                            alert(original);
+                                                        } catch (err) {
+                                                            alert(String(err));
+                                                        }
+                                                    }}
+                                                    style={{ display: 'flex', alignItems: 'center', gap: '8px', width: '100%', padding: '8px 12px', border: 'none', background: 'transparent', textAlign: 'left', cursor: 'pointer', color: 'inherit', fontSize: '0.9rem' }}
+                                                >
+                                                    🔓 Reveal Original
+                                                </button>
+                                            )}
                                             <button
                                                 className="menu-item-btn"
                                                 onClick={() => onShowQRCode(clip.content)}
@@ -344,7 +365,7 @@ export const ClipCard: React.FC<ClipCardProps> = ({
                         {
                             clip.type === 'image' && !compactMode && (
                                 <div style={{ padding: '4px 12px', borderTop: '1px solid var(--border-color, rgba(128,128,128,0.1))' }}>
-                                    <ImageColorPalette src={convertFileSrc(clip.content)} isCompact={compactMode} />
+                                    <ImageColorPalette src={convertFileSrc(clip.thumbnail ?? clip.content)} isCompact={compactMode} />
                                 </div>
                             )
                         }
@@ -377,10 +398,16 @@ export const ClipCard: React.FC<ClipCardProps> = ({
                                         onZoom={onZoom}
                                         onEditImage={onEditImage}
                                         clipId={clip.id}
+                                        thumbnail={clip.thumbnail}
                                     />
                                     {clip.type === 'text' && isUrl(clip.content) && <UrlPreview url={clip.content} />}
                                 </div>
                             )}
+                            {clip.highlight?.includes('<mark>') && (
+                                <div className="clip-highlight">
+                                    <Highlight text={clip.highlight} />
+                                </div>
+                            )}
                         </div>
                     </motion.div >
                 </div >
@@ -20,6 +20,7 @@ interface ClipContentProps {
     onZoom?: (src: string) => void;
     onEditImage?: (src: string, id?: number) => void;
     clipId?: number;
+    thumbnail?: string;
 }
 
 // ============= CONTENT TYPE DETECTION =============
@@ -860,7 +861,7 @@ export const ImageColorPalette: React.FC<{ src: string, isCompact: boolean }> =
 
 // ============= MAIN COMPONENT =============
 
-export default function ClipContent({ content, type, isCompact, showRaw = false, isDark = true, isExtracting = false, onZoom, onEditImage, clipId }: ClipContentProps) {
+export default function ClipContent({ content, type, isCompact, showRaw = false, isDark = true, isExtracting = false, onZoom, onEditImage, clipId, thumbnail }: ClipContentProps) {
     const [validity, setValidity] = useState<{ checked: boolean, valid: boolean, invalidPaths: string[], dirPaths: string[] }>({ checked: false, valid: true, invalidPaths: [], dirPaths: [] });
 
     // Helper to get file icon based on extension
@@ -977,6 +978,8 @@ export default function ClipContent({ content, type, isCompact, showRaw = false,
     // Handle Image
     if (type === 'image') {
         const src = convertFileSrc(content);
+        // Compact rows only need the small preview
+        const previewSrc = isCompact && thumbnail ? convertFileSrc(thumbnail) : src;
         return (
             <div
                 className="clip-image"
@@ -998,7 +1001,7 @@ export default function ClipContent({ content, type, isCompact, showRaw = false,
             >
                 <img
                     ref={useRef(null)} // Dummy ref to avoid error if reuse logic later
-                    src={src}
+                    src={previewSrc}
                     style={{
                         maxWidth: isCompact ? '120px' : '100%',
                         maxHeight: isCompact ? '60px' : '300px',
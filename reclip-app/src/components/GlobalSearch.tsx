@@ -1,11 +1,14 @@
 import { useState, useEffect, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import Highlight from './Highlight';
 
 interface SearchResult {
     id: number;
     module: string;
     title: string;
     preview: string;
+    highlight: string; // Match excerpt with <mark> tags
+    score: number; // 0 for the best match of its module to 1 for the weakest
     created_at: string;
 }
 
@@ -238,7 +241,7 @@ export default function GlobalSearch({ visible, onClose, onNavigate }: GlobalSea
                                             overflow: 'hidden',
                                             textOverflow: 'ellipsis',
                                         }}>
-                                            {result.preview}
+                                            <Highlight text={result.highlight || result.preview} />
                                         </div>
                                     </div>
                                 );
@@ -0,0 +1,23 @@
+import { Fragment } from 'react';
+
+interface HighlightProps {
+    text: string; // Search excerpt, hits wrapped in <mark> tags
+}
+
+const markStyle = { background: 'rgba(255, 255, 0, 0.4)', color: 'inherit', borderRadius: '2px' };
+
+/** Renders a search excerpt as text, only its <mark> tags become elements */
+export default function Highlight({ text }: HighlightProps) {
+    let marked = false;
+    return (
+        <>
+            {text.split(/(<mark>|<\/mark>)/).map((part, i) => {
+                if (part === '<mark>' || part === '</mark>') {
+                    marked = part === '<mark>';
+                    return null;
+                }
+                return marked ? <mark key={i} style={markStyle}>{part}</mark> : <Fragment key={i}>{part}</Fragment>;
+            })}
+        </>
+    );
+}
@@ -170,7 +170,7 @@ export default function MainView() {
     const pasteNextInQueue = async () => {
         if (pasteQueue.length === 0) return;
         const next = pasteQueue[0];
-        await pasteClip(next.content, next.type);
+        await pasteClip(next.content, next.type, next.id);
         setPasteQueue(pasteQueue.slice(1));
     };
 
@@ -216,7 +216,7 @@ export default function MainView() {
             } else {
                 setSelectedClipIds(new Set());
                 setLastSelectedId(null);
-                pasteClip(clip.content, clip.type);
+                pasteClip(clip.content, clip.type, clip.id);
             }
         }
     };
@@ -257,7 +257,7 @@ export default function MainView() {
                             setFocusOnDelete(false);
                         });
                     } else {
-                        pasteClip(selectedClip.content, selectedClip.type);
+                        pasteClip(selectedClip.content, selectedClip.type, selectedClip.id);
                     }
                 }
             } else if (e.ctrlKey && e.key >= '1' && e.key <= '9') {
@@ -265,7 +265,7 @@ export default function MainView() {
                 const targetClip = clips[index];
                 if (targetClip) {
                     setSelectedIndex(index);
-                    setTimeout(() => pasteClip(targetClip.content, targetClip.type), 50);
+                    setTimeout(() => pasteClip(targetClip.content, targetClip.type, targetClip.id), 50);
                 }
             } else if (e.key === 'Escape') {
                 setSelectedIndex(-1);
@@ -345,6 +345,8 @@ export default function MainView() {
 
     useEffect(() => {
         let unlistenCreate: (() => void) | null = null;
+        let unlistenUpdate: (() => void) | null = null;
+        let unlistenDelete: (() => void) | null = null;
         let unlistenPasteNext: (() => void) | null = null;
         let unlistenPasteIndex: (() => void) | null = null;
 
@@ -356,11 +358,24 @@ export default function MainView() {
 
 
             unlistenCreate = await listen("clip-created", (_event) => {
+                const { activeFilter, searchTerm } = useClipStore.getState();
                 loadClips(0, LIMIT, activeFilter, searchTerm, true);
                 // Refresh tray clips
                 invoke('refresh_tray_clips').catch(e => console.warn('Tray refresh failed:', e));
             });
 
+            // Edits from the API, CLI, sync or another window: reload what is loaded, keeping the scroll depth
+            unlistenUpdate = await listen<number>("clip-updated", (event) => {
+                const { activeFilter, searchTerm, allClips } = useClipStore.getState();
+                if (!allClips.some(c => c.id === event.payload)) return;
+                loadClips(0, Math.max(LIMIT, allClips.length), activeFilter, searchTerm, true);
+            });
+
+            unlistenDelete = await listen<number>("clip-deleted", (event) => {
+                useClipStore.getState().dropClips([event.payload]);
+                invoke('refresh_tray_clips').catch(e => console.warn('Tray refresh failed:', e));
+            });
+
 
             unlistenPasteNext = await listen("paste-next-trigger", (_event) => {
                 document.getElementById('hidden-paste-next-btn')?.click();
@@ -371,7 +386,7 @@ export default function MainView() {
                 const currentClips = clipsRef.current;
                 if (index >= 0 && index < currentClips.length) {
                     const item = currentClips[index];
-                    invoke("paste_clip_to_system", { content: item.content, clipType: item.type }).then(() => {
+                    invoke("paste_clip_to_system", { content: item.content, clipType: item.type, clipId: item.id }).then(() => {
                         // Optional: move window to back / hide?
                     });
                 }
@@ -382,12 +397,14 @@ export default function MainView() {
 
         return () => {
             if (unlistenCreate) unlistenCreate();
+            if (unlistenUpdate) unlistenUpdate();
+            if (unlistenDelete) unlistenDelete();
             if (unlistenPasteNext) unlistenPasteNext();
             if (unlistenPasteIndex) unlistenPasteIndex();
         };
     }, []);
 
-    async function pasteClip(content: string, clipType: string = 'text') {
+    async function pasteClip(content: string, clipType: string = 'text', clipId?: number) {
         let finalContent = content;
         if (clipType === 'text' && content.includes('{{')) {
             const date = new Date();
@@ -414,7 +431,8 @@ export default function MainView() {
         }
 
         try {
-            await invoke("paste_clip_to_system", { content: finalContent, clipType });
+            // The stored rich formats only match when the content was not templated
+            await invoke("paste_clip_to_system", { content: finalContent, clipType, clipId: finalContent === content ? clipId : undefined });
         } catch (error) {
             console.error("Failed to paste:", error);
         }
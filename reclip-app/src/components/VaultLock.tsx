@@ -0,0 +1,115 @@
+import { useState, useRef, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+
+interface VaultLockProps {
+    onUnlock: () => void;
+}
+
+export default function VaultLock({ onUnlock }: VaultLockProps) {
+    const [password, setPassword] = useState('');
+    const [error, setError] = useState('');
+    const [unlocking, setUnlocking] = useState(false);
+    const inputRef = useRef<HTMLInputElement>(null);
+
+    useEffect(() => {
+        setTimeout(() => inputRef.current?.focus(), 100);
+    }, []);
+
+    const handleSubmit = async (e: React.FormEvent) => {
+        e.preventDefault();
+        if (!password || unlocking) return;
+        setUnlocking(true);
+        try {
+            await invoke('unlock_vault', { password });
+            setPassword('');
+            onUnlock();
+        } catch (err) {
+            setError(String(err));
+            setPassword('');
+            inputRef.current?.focus();
+        } finally {
+            setUnlocking(false);
+        }
+    };
+
+    return (
+        <div style={{
+            position: 'fixed',
+            top: 0,
+            left: 0,
+            right: 0,
+            bottom: 0,
+            background: 'var(--bg-app, #0f0f1a)',
+            zIndex: 10002,
+            display: 'flex',
+            alignItems: 'center',
+            justifyContent: 'center',
+            flexDirection: 'column',
+        }}>
+            <div style={{
+                textAlign: 'center',
+                maxWidth: '320px',
+            }}>
+                <div style={{ fontSize: '3rem', marginBottom: '16px' }}>🔐</div>
+                <h2 style={{ margin: '0 0 8px', fontSize: '1.2rem' }}>ReClip is Locked</h2>
+                <p style={{ margin: '0 0 24px', fontSize: '0.85rem', opacity: 0.5 }}>
+                    Your history is encrypted. Enter the vault password to read it and resume capturing.
+                </p>
+
+                <form onSubmit={handleSubmit}>
+                    <input
+                        ref={inputRef}
+                        type="password"
+                        value={password}
+                        onChange={e => {
+                            setPassword(e.target.value);
+                            setError('');
+                        }}
+                        placeholder="Vault password"
+                        style={{
+                            width: '240px',
+                            padding: '12px',
+                            fontSize: '1rem',
+                            textAlign: 'center',
+                            background: 'var(--bg-card, #1e1e2e)',
+                            border: error ? '2px solid #ef4444' : '2px solid var(--border-color, rgba(128,128,128,0.2))',
+                            borderRadius: '12px',
+                            color: 'inherit',
+                            outline: 'none',
+                        }}
+                    />
+
+                    {error && (
+                        <div style={{
+                            color: '#ef4444',
+                            fontSize: '0.8rem',
+                            marginTop: '8px',
+                        }}>
+                            {error}
+                        </div>
+                    )}
+
+                    <div style={{ marginTop: '20px' }}>
+                        <button
+                            type="submit"
+                            disabled={unlocking}
+                            style={{
+                                padding: '10px 32px',
+                                borderRadius: '8px',
+                                border: 'none',
+                                background: 'var(--accent-color, #6366f1)',
+                                color: '#fff',
+                                fontSize: '0.9rem',
+                                fontWeight: 600,
+                                cursor: 'pointer',
+                                opacity: unlocking ? 0.6 : 1,
+                            }}
+                        >
+                            {unlocking ? 'Unlocking...' : 'Unlock'}
+                        </button>
+                    </div>
+                </form>
+            </div>
+        </div>
+    );
+}
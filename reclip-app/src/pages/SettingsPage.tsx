@@ -10,6 +10,7 @@ import { getVersion } from '@tauri-apps/api/app';
 import { ChangelogViewer } from '../components/ChangelogViewer';
 import { Settings, Keyboard, Shield, ScrollText, Bot, Wrench, Cloud, Info } from 'lucide-react';
 import { useSettingsStore } from '../store/useSettingsStore';
+import { ApiStatus, AppPolicyRule, AppPolicyTest, DbVaultStatus, Detector, GcReport, PrivacyRule, SecretStoreStatus, SyncConfig, VaultStatus } from '../types';
 import './SettingsPage.css';
 
 interface SettingsPageProps {
@@ -43,7 +44,19 @@ export default function SettingsPage({
     const [sensitiveAutoDelete, setSensitiveAutoDelete] = useState(() => localStorage.getItem('sensitiveAutoDelete') !== 'false');
     const [sensitiveTimer, setSensitiveTimer] = useState(() => localStorage.getItem('sensitiveDeleteTimer') || '30');
     const [exportStatus, setExportStatus] = useState("");
-    const [privacyRules, setPrivacyRules] = useState<any[]>([]);
+    const [maintenanceStatus, setMaintenanceStatus] = useState("");
+    const [apiToken, setApiToken] = useState("");
+    const [showApiToken, setShowApiToken] = useState(false);
+    const [apiEnabled, setApiEnabled] = useState(true);
+    const [apiBind, setApiBind] = useState("127.0.0.1");
+    const [apiPort, setApiPort] = useState(14201);
+    const [apiStatus, setApiStatus] = useState<ApiStatus | null>(null);
+    const [privacyRules, setPrivacyRules] = useState<PrivacyRule[]>([]);
+    const [detectors, setDetectors] = useState<Detector[]>([]);
+    const [builtinDetectors, setBuiltinDetectors] = useState<string[]>([]);
+    const [newDetectorName, setNewDetectorName] = useState("");
+    const [newDetectorPattern, setNewDetectorPattern] = useState("");
+    const [detectorError, setDetectorError] = useState("");
     const [shortcuts, setShortcuts] = useState<{ [key: string]: string }>({});
     const [recordingAction, setRecordingAction] = useState<string | null>(null);
 
@@ -79,6 +92,15 @@ export default function SettingsPage({
     // Privacy states
     const [newIgnoreApp, setNewIgnoreApp] = useState("");
     const [newRegex, setNewRegex] = useState("");
+    const [newRegexAction, setNewRegexAction] = useState<'REGEX_MASK' | 'REGEX_DROP'>('REGEX_MASK');
+    const [newPlaceholder, setNewPlaceholder] = useState("");
+    const [newKeepOriginal, setNewKeepOriginal] = useState(false);
+    const [regexError, setRegexError] = useState("");
+    const [newPolicy, setNewPolicy] = useState<AppPolicyRule>({ match_kind: 'substring', value: '', capture: 'text_only', mark_sensitive: false, delete_after: null, tags: [], strip_formatting: false });
+    const [newPolicyTags, setNewPolicyTags] = useState("");
+    const [policyError, setPolicyError] = useState("");
+    const [policyTest, setPolicyTest] = useState<AppPolicyTest | null>(null);
+    const [testingPolicy, setTestingPolicy] = useState(false);
 
     // Templates
     const [templates, setTemplates] = useState<any[]>([]);
@@ -122,10 +144,27 @@ export default function SettingsPage({
     const [driveUser, setDriveUser] = useState<string | null>(null);
     const [driveEmail, setDriveEmail] = useState<string | null>(null);
     const [driveClientId, setDriveClientId] = useState(() => localStorage.getItem('driveClientId') || '');
-    const [driveClientSecret, setDriveClientSecret] = useState(() => localStorage.getItem('driveClientSecret') || '');
+    // The secret is kept by the backend's secret store, not in local storage
+    const [driveClientSecret, setDriveClientSecret] = useState('');
 
     const [driveStatusMsg, setDriveStatusMsg] = useState("");
     const [syncing, setSyncing] = useState(false);
+    const [syncConfig, setSyncConfig] = useState<SyncConfig | null>(null);
+    const [syncStatusMsg, setSyncStatusMsg] = useState("");
+    const [vaultStatus, setVaultStatus] = useState<VaultStatus | null>(null);
+    const [vaultPassword, setVaultPassword] = useState("");
+    const [vaultPasswordConfirm, setVaultPasswordConfirm] = useState("");
+    const [vaultNewPassword, setVaultNewPassword] = useState("");
+    const [vaultMsg, setVaultMsg] = useState("");
+    const [dbVault, setDbVault] = useState<DbVaultStatus | null>(null);
+    const [dbVaultPassword, setDbVaultPassword] = useState("");
+    const [dbVaultPasswordConfirm, setDbVaultPasswordConfirm] = useState("");
+    const [dbVaultBusy, setDbVaultBusy] = useState(false);
+    const [dbVaultMsg, setDbVaultMsg] = useState("");
+    const [secretStore, setSecretStore] = useState<SecretStoreStatus | null>(null);
+    const [masterPassword, setMasterPassword] = useState("");
+    const [masterPasswordConfirm, setMasterPasswordConfirm] = useState("");
+    const [secretStoreMsg, setSecretStoreMsg] = useState("");
 
     // Custom Colors - load from localStorage on mount
     const [customColors, setCustomColors] = useState(() => {
@@ -145,17 +184,157 @@ export default function SettingsPage({
 
     const handleSyncNow = async () => {
         setSyncing(true);
-        setDriveStatusMsg("Syncing...");
+        setSyncStatusMsg("Syncing...");
         try {
-            const msg = await invoke<string>("sync_clips");
-            setDriveStatusMsg(msg);
+            const msg = await invoke<string>("sync_now");
+            setSyncStatusMsg(msg);
+            invoke<SyncConfig>("get_sync_config").then(setSyncConfig).catch(console.error);
+            fetchVaultStatus();
         } catch (e) {
-            setDriveStatusMsg("Sync Failed: " + e);
+            setSyncStatusMsg("Sync Failed: " + e);
         } finally {
             setSyncing(false);
         }
     };
 
+    const updateSyncConfig = (changes: Partial<SyncConfig>) => {
+        setSyncConfig(prev => prev ? { ...prev, ...changes } : prev);
+    };
+
+    const handleSyncConfigSave = async () => {
+        if (!syncConfig) return;
+        try {
+            await invoke("set_sync_config", { config: syncConfig });
+            setSyncStatusMsg("Saved");
+            fetchVaultStatus();
+        } catch (e) {
+            setSyncStatusMsg("Failed to save: " + e);
+        }
+    };
+
+    const handleSyncTest = async () => {
+        if (!syncConfig) return;
+        setSyncStatusMsg("Testing...");
+        try {
+            setSyncStatusMsg(await invoke<string>("test_sync_backend", { config: syncConfig }));
+        } catch (e) {
+            setSyncStatusMsg("Test failed: " + e);
+        }
+    };
+
+    const fetchVaultStatus = () => {
+        invoke<VaultStatus>("get_sync_vault_status").then(setVaultStatus).catch(() => setVaultStatus(null));
+    };
+
+    const handleVaultUnlock = async () => {
+        if (vaultStatus && !vaultStatus.encrypted && vaultPassword !== vaultPasswordConfirm) {
+            setVaultMsg("Passwords don't match");
+            return;
+        }
+        setVaultMsg(vaultStatus?.encrypted ? "Unlocking..." : "Encrypting...");
+        try {
+            await invoke("unlock_sync_vault", { password: vaultPassword });
+            setVaultMsg(vaultStatus?.encrypted ? "Unlocked" : "Sync data is encrypted from now on");
+            setVaultPassword("");
+            setVaultPasswordConfirm("");
+        } catch (e) {
+            setVaultMsg(String(e));
+        }
+        fetchVaultStatus();
+    };
+
+    const handleVaultLock = async () => {
+        await invoke("lock_sync_vault");
+        setVaultMsg("Locked");
+        fetchVaultStatus();
+    };
+
+    const handleVaultChangePassword = async () => {
+        if (vaultNewPassword !== vaultPasswordConfirm) {
+            setVaultMsg("Passwords don't match");
+            return;
+        }
+        try {
+            await invoke("change_sync_password", { current: vaultPassword, new: vaultNewPassword });
+            setVaultMsg("Password changed, other devices need to unlock with the new one");
+            setVaultPassword("");
+            setVaultNewPassword("");
+            setVaultPasswordConfirm("");
+        } catch (e) {
+            setVaultMsg(String(e));
+        }
+    };
+
+    const handleVaultRotate = async () => {
+        if (!confirm("Encrypt everything synced from now on with a new key? Existing data stays readable.")) return;
+        try {
+            setVaultMsg(await invoke<string>("rotate_sync_key"));
+        } catch (e) {
+            setVaultMsg(String(e));
+        }
+        fetchVaultStatus();
+    };
+
+    const fetchDbVault = () => {
+        invoke<DbVaultStatus>("get_vault_status").then(setDbVault).catch(console.error);
+    };
+
+    const handleDbVaultToggle = async () => {
+        const enabling = !dbVault?.enabled;
+        if (enabling && dbVaultPassword !== dbVaultPasswordConfirm) {
+            setDbVaultMsg("Passwords don't match");
+            return;
+        }
+        if (enabling && !confirm("Encrypt your history with this password? Without it, the history can't be recovered.")) return;
+        setDbVaultBusy(true);
+        setDbVaultMsg(enabling ? "Encrypting..." : "Decrypting...");
+        try {
+            setDbVaultMsg(await invoke<string>(enabling ? "enable_vault" : "disable_vault", { password: dbVaultPassword }));
+            setDbVaultPassword("");
+            setDbVaultPasswordConfirm("");
+        } catch (e) {
+            setDbVaultMsg(String(e));
+        } finally {
+            setDbVaultBusy(false);
+        }
+        fetchDbVault();
+    };
+
+    const handleDbVaultAutoLock = async (minutes: number) => {
+        try {
+            await invoke("set_vault_auto_lock", { minutes });
+            fetchDbVault();
+        } catch (e) {
+            setDbVaultMsg(String(e));
+        }
+    };
+
+    const fetchSecretStore = () => {
+        invoke<SecretStoreStatus>("get_secret_store_status").then(setSecretStore).catch(console.error);
+    };
+
+    const handleUnlockSecretStore = async () => {
+        if (secretStore && !secretStore.file_exists && masterPassword !== masterPasswordConfirm) {
+            setSecretStoreMsg("Passwords don't match");
+            return;
+        }
+        setSecretStoreMsg("Unlocking...");
+        try {
+            await invoke("unlock_secret_store", { password: masterPassword });
+            setSecretStoreMsg(secretStore?.file_exists ? "Unlocked" : "Credentials are saved encrypted from now on");
+            setMasterPassword("");
+            setMasterPasswordConfirm("");
+        } catch (e) {
+            setSecretStoreMsg(String(e));
+        }
+        fetchSecretStore();
+    };
+
+    const handlePickSyncFolder = async () => {
+        const folder = await open({ directory: true, multiple: false });
+        if (typeof folder === 'string') updateSyncConfig({ folder_path: folder });
+    };
+
     const handleColorChange = (key: string, value: string) => {
         const newColors = { ...customColors, [key]: value };
         setCustomColors(newColors);
@@ -226,7 +405,7 @@ export default function SettingsPage({
 
     const fetchPrivacyRules = async () => {
         try {
-            const rules = await invoke<any[]>("get_privacy_rules");
+            const rules = await invoke<PrivacyRule[]>("get_privacy_rules");
             setPrivacyRules(rules);
         } catch (e) {
             console.error("Failed to fetch privacy rules", e);
@@ -307,7 +486,20 @@ export default function SettingsPage({
     };
 
     useEffect(() => {
-        if (activeTab === 'security') fetchPrivacyRules();
+        if (activeTab === 'security') {
+            fetchPrivacyRules();
+            fetchDetectors();
+            invoke<string[]>("get_builtin_detectors").then(setBuiltinDetectors).catch(console.error);
+            invoke<string>("get_api_token").then(setApiToken).catch(console.error);
+            invoke<[boolean, string, number]>("get_api_settings").then(([enabled, bind, port]) => {
+                setApiEnabled(enabled);
+                setApiBind(bind);
+                setApiPort(port);
+            }).catch(console.error);
+            invoke<ApiStatus>("get_api_status").then(setApiStatus).catch(console.error);
+            fetchDbVault();
+            fetchSecretStore();
+        }
         if (activeTab === 'shortcuts') fetchShortcuts();
         if (activeTab === 'templates') fetchTemplates();
         if (activeTab === 'automations') fetchRegexRules();
@@ -320,6 +512,7 @@ export default function SettingsPage({
         }
         if (activeTab === 'backup') {
             fetchDriveStatus();
+            invoke<SyncConfig>("get_sync_config").then(setSyncConfig).catch(console.error);
         }
     }, [activeTab]);
 
@@ -353,12 +546,12 @@ export default function SettingsPage({
     };
 
     const handleConnectDrive = async () => {
-        if (!driveClientId || !driveClientSecret) {
+        if (!driveClientId) {
             alert("Please enter Client ID and Secret");
             return;
         }
         localStorage.setItem('driveClientId', driveClientId);
-        localStorage.setItem('driveClientSecret', driveClientSecret);
+        localStorage.removeItem('driveClientSecret');
 
         try {
             setDriveStatusMsg("Starting Auth...");
@@ -429,18 +622,108 @@ export default function SettingsPage({
         }
     };
 
+    const fetchDetectors = async () => {
+        try {
+            setDetectors(await invoke<Detector[]>("get_detectors"));
+        } catch (e) {
+            console.error("Failed to fetch detectors", e);
+        }
+    };
+
+    const handleAddDetector = async () => {
+        if (!newDetectorName.trim() || !newDetectorPattern) return;
+        try {
+            await invoke("add_detector", { name: newDetectorName, pattern: newDetectorPattern });
+            setNewDetectorName("");
+            setNewDetectorPattern("");
+            setDetectorError("");
+            fetchDetectors();
+        } catch (e) {
+            setDetectorError(String(e));
+        }
+    };
+
+    const handleToggleDetector = async (id: number, enabled: boolean) => {
+        try {
+            await invoke("set_detector_enabled", { id, enabled });
+            fetchDetectors();
+        } catch (e) {
+            console.error("Failed to update detector", e);
+        }
+    };
+
+    const handleDeleteDetector = async (id: number) => {
+        try {
+            await invoke("delete_detector", { id });
+            fetchDetectors();
+        } catch (e) {
+            console.error("Failed to delete detector", e);
+        }
+    };
+
     const handleAddRule = async (type: string, value: string) => {
         if (!value.trim()) return;
+        const isRegex = type.startsWith('REGEX_');
         try {
-            await invoke("add_privacy_rule", { ruleType: type, value: value.trim() });
+            await invoke("add_privacy_rule", {
+                ruleType: type,
+                value: value.trim(),
+                placeholder: type === 'REGEX_MASK' ? newPlaceholder : null,
+                keepOriginal: type === 'REGEX_MASK' && newKeepOriginal,
+            });
             fetchPrivacyRules();
             if (type === 'APP_IGNORE') setNewIgnoreApp("");
-            if (type === 'REGEX_MASK') setNewRegex("");
+            if (isRegex) {
+                setNewRegex("");
+                setNewPlaceholder("");
+                setRegexError("");
+            }
         } catch (e) {
+            if (isRegex) setRegexError(String(e));
             console.error("Failed to add rule", e);
         }
     };
 
+    const policyFromForm = (): AppPolicyRule => ({
+        ...newPolicy,
+        value: newPolicy.value.trim(),
+        tags: newPolicyTags.split(',').map(t => t.trim()).filter(Boolean).map(t => t.startsWith('#') ? t : `#${t}`),
+    });
+
+    const handleAddPolicy = async () => {
+        try {
+            await invoke("add_app_policy", { rule: policyFromForm() });
+            setNewPolicy({ ...newPolicy, value: '' });
+            setNewPolicyTags("");
+            setPolicyError("");
+            setPolicyTest(null);
+            fetchPrivacyRules();
+        } catch (e) {
+            setPolicyError(String(e));
+        }
+    };
+
+    // Gives the user three seconds to focus the app to test against
+    const handleTestPolicy = async (useForm: boolean) => {
+        setTestingPolicy(true);
+        setPolicyError("");
+        try {
+            setPolicyTest(await invoke<AppPolicyTest>("test_app_policy", { rule: useForm ? policyFromForm() : null, delayMs: 3000 }));
+        } catch (e) {
+            setPolicyError(String(e));
+        } finally {
+            setTestingPolicy(false);
+        }
+    };
+
+    const describePolicy = (p: AppPolicyTest['policy']) => {
+        const parts = [{ all: 'Capture all', no_images: 'No images', text_only: 'Text only', none: 'Capture nothing' }[p.capture]];
+        if (p.strip_formatting) parts.push('plain text');
+        if (p.mark_sensitive) parts.push(p.delete_after ? `sensitive, deleted after ${p.delete_after}s` : 'sensitive');
+        if (p.tags.length > 0) parts.push(p.tags.join(' '));
+        return parts.join(' · ');
+    };
+
     const handleDeleteRule = async (id: number) => {
         try {
             await invoke("delete_privacy_rule", { id });
@@ -523,12 +806,25 @@ export default function SettingsPage({
         localStorage.setItem("retainDays", retainDays.toString());
         localStorage.setItem("maxClips", maxClips.toString());
         try {
-            await invoke("run_maintenance", { days: retainDays, maxClips: maxClips });
+            const report = await invoke<GcReport>("run_maintenance", { days: retainDays, maxClips: maxClips });
+            if (report.reclaimed_bytes > 0) {
+                setMaintenanceStatus(`Freed ${(report.reclaimed_bytes / (1024 * 1024)).toFixed(1)} MB of unused images`);
+                setTimeout(() => setMaintenanceStatus(""), 3000);
+            }
         } catch (e) {
             console.error("Maintenance failed", e);
         }
     };
 
+    const handleApiSettingsSave = async () => {
+        try {
+            await invoke("set_api_settings", { enabled: apiEnabled, bindAddress: apiBind, port: apiPort });
+        } catch (e) {
+            console.error("Failed to apply API settings", e);
+        }
+        invoke<ApiStatus>("get_api_status").then(setApiStatus).catch(console.error);
+    };
+
     const handleExport = async () => {
         try {
             const filePath = await save({
@@ -1143,7 +1439,7 @@ export default function SettingsPage({
                                     🔐 Sensitive Content Protection
                                 </h3>
                                 <p style={{ fontSize: '0.8rem', opacity: 0.7, marginBottom: '12px' }}>
-                                    Automatically detects passwords, API keys, tokens, and private keys. Sensitive clips are tagged and can be auto-deleted.
+                                    Automatically detects passwords, API keys, tokens, and private keys. Sensitive clips are tagged and can be auto-deleted. Hover a clip's Sensitive badge to see which detector flagged it.
                                 </p>
 
                                 <label style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', cursor: 'pointer', marginBottom: '12px' }}>
@@ -1231,6 +1527,127 @@ export default function SettingsPage({
                                 </div>
                             </div>
 
+                            <div className="setting-item" style={{ marginTop: '24px' }}>
+                                <h3 style={{ fontSize: '0.9rem', marginBottom: '12px' }}>App Policies</h3>
+                                <p style={{ fontSize: '0.8rem', opacity: 0.7, marginBottom: '12px' }}>
+                                    Decide what is captured from specific apps. When several policies match, the strictest capture setting wins.
+                                </p>
+                                <div style={{ display: 'flex', gap: '8px', marginBottom: '8px' }}>
+                                    <select
+                                        value={newPolicy.match_kind}
+                                        onChange={(e) => setNewPolicy({ ...newPolicy, match_kind: e.target.value as AppPolicyRule['match_kind'] })}
+                                        style={{ padding: '8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)', cursor: 'pointer' }}
+                                    >
+                                        <option value="substring">Name or title contains</option>
+                                        <option value="path">Process path is</option>
+                                        <option value="title_regex">Title matches regex</option>
+                                        <option value="wildcard">Wildcard (* and ?)</option>
+                                    </select>
+                                    <input
+                                        type="text"
+                                        placeholder={{ substring: 'e.g., KeePass', path: 'e.g., /usr/bin/bitwarden', title_regex: 'e.g., (?i)- Slack$', wildcard: 'e.g., *terminal*' }[newPolicy.match_kind]}
+                                        value={newPolicy.value}
+                                        onChange={(e) => {
+                                            setNewPolicy({ ...newPolicy, value: e.target.value });
+                                            setPolicyError("");
+                                        }}
+                                        style={{ padding: '8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)', flex: 1 }}
+                                    />
+                                    <select
+                                        value={newPolicy.capture}
+                                        onChange={(e) => setNewPolicy({ ...newPolicy, capture: e.target.value as AppPolicyRule['capture'] })}
+                                        style={{ padding: '8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)', cursor: 'pointer' }}
+                                    >
+                                        <option value="all">Capture all</option>
+                                        <option value="text_only">Text only</option>
+                                        <option value="no_images">No images</option>
+                                        <option value="none">Capture nothing</option>
+                                    </select>
+                                </div>
+                                <div style={{ display: 'flex', alignItems: 'center', flexWrap: 'wrap', gap: '12px', marginBottom: '12px', fontSize: '0.8rem' }}>
+                                    <label style={{ display: 'flex', alignItems: 'center', gap: '6px', cursor: 'pointer' }}>
+                                        <input type="checkbox" checked={newPolicy.strip_formatting} onChange={(e) => setNewPolicy({ ...newPolicy, strip_formatting: e.target.checked })} style={{ accentColor: 'var(--accent-color)' }} />
+                                        Strip formatting
+                                    </label>
+                                    <label style={{ display: 'flex', alignItems: 'center', gap: '6px', cursor: 'pointer' }}>
+                                        <input type="checkbox" checked={newPolicy.mark_sensitive} onChange={(e) => setNewPolicy({ ...newPolicy, mark_sensitive: e.target.checked })} style={{ accentColor: 'var(--accent-color)' }} />
+                                        Always sensitive
+                                    </label>
+                                    {newPolicy.mark_sensitive && (
+                                        <label style={{ display: 'flex', alignItems: 'center', gap: '6px' }}>
+                                            Delete after
+                                            <input
+                                                type="number"
+                                                min={1}
+                                                placeholder="global"
+                                                value={newPolicy.delete_after ?? ''}
+                                                onChange={(e) => setNewPolicy({ ...newPolicy, delete_after: e.target.value ? Number(e.target.value) : null })}
+                                                style={{ width: '70px', padding: '4px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)' }}
+                                            />
+                                            s
+                                        </label>
+                                    )}
+                                    <input
+                                        type="text"
+                                        placeholder="Tags (e.g., work, chat)"
+                                        value={newPolicyTags}
+                                        onChange={(e) => setNewPolicyTags(e.target.value)}
+                                        style={{ flex: 1, padding: '4px 8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)' }}
+                                    />
+                                </div>
+                                <div style={{ display: 'flex', gap: '8px', marginBottom: '12px' }}>
+                                    <button
+                                        onClick={() => handleTestPolicy(true)}
+                                        disabled={testingPolicy || !newPolicy.value.trim()}
+                                        title="Switch to the app within 3 seconds"
+                                        style={{ padding: '8px 16px', borderRadius: '8px', border: '1px solid rgba(128,128,128,0.3)', background: 'transparent', color: 'inherit', cursor: 'pointer' }}
+                                    >
+                                        {testingPolicy ? 'Switch to the app…' : 'Test this policy'}
+                                    </button>
+                                    <button
+                                        onClick={() => handleTestPolicy(false)}
+                                        disabled={testingPolicy}
+                                        title="Switch to the app within 3 seconds"
+                                        style={{ padding: '8px 16px', borderRadius: '8px', border: '1px solid rgba(128,128,128,0.3)', background: 'transparent', color: 'inherit', cursor: 'pointer' }}
+                                    >
+                                        Test saved rules
+                                    </button>
+                                    <button
+                                        onClick={handleAddPolicy}
+                                        className="primary-btn"
+                                        style={{ marginLeft: 'auto', padding: '8px 16px', borderRadius: '8px', border: 'none', background: 'var(--accent-color, #4f46e5)', color: 'white', cursor: 'pointer', fontWeight: 600 }}
+                                    >
+                                        Add
+                                    </button>
+                                </div>
+                                {policyError && <p style={{ fontSize: '0.75rem', color: '#ef4444', marginTop: 0, whiteSpace: 'pre-wrap', fontFamily: 'monospace' }}>{policyError}</p>}
+                                {policyTest && (
+                                    <div style={{ fontSize: '0.8rem', padding: '8px', marginBottom: '12px', borderRadius: '6px', background: 'rgba(99, 102, 241, 0.08)' }}>
+                                        {policyTest.window ? (
+                                            <>
+                                                <div><b>{policyTest.window.name}</b> <span style={{ opacity: 0.6, fontFamily: 'monospace' }}>{policyTest.window.path}</span></div>
+                                                <div style={{ opacity: 0.7 }}>{policyTest.window.title}</div>
+                                                <div style={{ marginTop: '4px' }}>
+                                                    {policyTest.policy.matched.length === 0
+                                                        ? 'No rule matches, everything is captured.'
+                                                        : `Matches ${policyTest.policy.matched.join(', ')}: ${describePolicy(policyTest.policy)}`}
+                                                </div>
+                                            </>
+                                        ) : 'Could not read the focused window.'}
+                                    </div>
+                                )}
+                                <div style={{ maxHeight: '150px', overflowY: 'auto', background: 'rgba(0,0,0,0.02)', borderRadius: '6px', padding: '8px' }}>
+                                    {privacyRules.filter(r => r.rule_type === 'APP_POLICY').length === 0 && <p style={{ opacity: 0.5, fontSize: '0.8rem', textAlign: 'center' }}>No app policies.</p>}
+                                    {privacyRules.filter(r => r.rule_type === 'APP_POLICY').map(r => (
+                                        <div key={r.id} style={{ display: 'flex', justifyContent: 'space-between', alignItems: 'center', gap: '8px', padding: '6px', borderBottom: '1px solid rgba(128,128,128,0.1)' }}>
+                                            <span style={{ flex: 1, fontSize: '0.9rem', fontFamily: r.match_kind === 'substring' ? undefined : 'monospace' }}>{r.value}</span>
+                                            <span style={{ fontSize: '0.75rem', opacity: 0.7 }}>{describePolicy({ ...r, delete_after: r.delete_after ?? null, tags: r.tags ? JSON.parse(r.tags) : [], matched: [] })}</span>
+                                            <button onClick={() => handleDeleteRule(r.id)} style={{ background: 'none', border: 'none', cursor: 'pointer', opacity: 0.8 }}>🗑️</button>
+                                        </div>
+                                    ))}
+                                </div>
+                            </div>
+
                             <div className="setting-item" style={{ marginTop: '24px' }}>
                                 <h3 style={{ fontSize: '0.9rem', marginBottom: '12px' }}>Privacy Filters (Regex)</h3>
                                 <div style={{ display: 'flex', gap: '8px', marginBottom: '12px' }}>
@@ -1238,11 +1655,23 @@ export default function SettingsPage({
                                         type="text"
                                         placeholder="Regex Pattern (e.g., ^password.*)"
                                         value={newRegex}
-                                        onChange={(e) => setNewRegex(e.target.value)}
+                                        onChange={(e) => {
+                                            setNewRegex(e.target.value);
+                                            setRegexError("");
+                                        }}
                                         style={{ flex: 1, padding: '8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)' }}
                                     />
+                                    <select
+                                        value={newRegexAction}
+                                        onChange={(e) => setNewRegexAction(e.target.value as 'REGEX_MASK' | 'REGEX_DROP')}
+                                        title="Mask replaces what matches, Drop discards the whole clip"
+                                        style={{ padding: '8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)', cursor: 'pointer' }}
+                                    >
+                                        <option value="REGEX_MASK">Mask</option>
+                                        <option value="REGEX_DROP">Drop clip</option>
+                                    </select>
                                     <button
-                                        onClick={() => handleAddRule('REGEX_MASK', newRegex)}
+                                        onClick={() => handleAddRule(newRegexAction, newRegex)}
                                         className="primary-btn"
                                         style={{
                                             padding: '8px 16px',
@@ -1260,22 +1689,185 @@ export default function SettingsPage({
                                         Add
                                     </button>
                                 </div>
+                                {newRegexAction === 'REGEX_MASK' && (
+                                    <div style={{ display: 'flex', alignItems: 'center', gap: '12px', marginBottom: '12px' }}>
+                                        <input
+                                            type="text"
+                                            placeholder="Placeholder (default: [REDACTED])"
+                                            value={newPlaceholder}
+                                            onChange={(e) => setNewPlaceholder(e.target.value)}
+                                            style={{ flex: 1, padding: '8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)' }}
+                                        />
+                                        <label style={{ display: 'flex', alignItems: 'center', gap: '6px', fontSize: '0.8rem', cursor: 'pointer' }} title="The original is encrypted with a key kept with your credentials and only shown after you confirm">
+                                            <input
+                                                type="checkbox"
+                                                checked={newKeepOriginal}
+                                                onChange={(e) => setNewKeepOriginal(e.target.checked)}
+                                                style={{ accentColor: 'var(--accent-color)' }}
+                                            />
+                                            Keep encrypted original
+                                        </label>
+                                    </div>
+                                )}
+                                {regexError && <p style={{ fontSize: '0.75rem', color: '#ef4444', marginTop: 0, whiteSpace: 'pre-wrap', fontFamily: 'monospace' }}>{regexError}</p>}
                                 <div style={{ marginBottom: '12px', display: 'flex', gap: '8px' }}>
                                     {/* Presets */}
                                     <button onClick={() => setNewRegex('^password.*')} style={{ fontSize: '0.8rem', padding: '4px 8px', borderRadius: '4px', border: '1px solid rgba(128,128,128,0.2)', background: 'transparent', cursor: 'pointer', color: 'inherit' }}>Password Preset</button>
                                     <button onClick={() => setNewRegex('\\b\\d{4}[- ]?\\d{4}[- ]?\\d{4}[- ]?\\d{4}\\b')} style={{ fontSize: '0.8rem', padding: '4px 8px', borderRadius: '4px', border: '1px solid rgba(128,128,128,0.2)', background: 'transparent', cursor: 'pointer', color: 'inherit' }}>Credit Card Preset</button>
                                 </div>
                                 <div style={{ maxHeight: '150px', overflowY: 'auto', background: 'rgba(0,0,0,0.02)', borderRadius: '6px', padding: '8px' }}>
-                                    {privacyRules.filter(r => r.rule_type === 'REGEX_MASK').length === 0 && <p style={{ opacity: 0.5, fontSize: '0.8rem', textAlign: 'center' }}>No privacy filters.</p>}
-                                    {privacyRules.filter(r => r.rule_type === 'REGEX_MASK').map(r => (
-                                        <div key={r.id} style={{ display: 'flex', justifyContent: 'space-between', alignItems: 'center', padding: '6px', borderBottom: '1px solid rgba(128,128,128,0.1)' }}>
-                                            <span style={{ fontSize: '0.9rem', fontFamily: 'monospace' }}>{r.value}</span>
+                                    {privacyRules.filter(r => r.rule_type.startsWith('REGEX_')).length === 0 && <p style={{ opacity: 0.5, fontSize: '0.8rem', textAlign: 'center' }}>No privacy filters.</p>}
+                                    {privacyRules.filter(r => r.rule_type.startsWith('REGEX_')).map(r => (
+                                        <div key={r.id} style={{ display: 'flex', justifyContent: 'space-between', alignItems: 'center', gap: '8px', padding: '6px', borderBottom: '1px solid rgba(128,128,128,0.1)' }}>
+                                            <span style={{ flex: 1, fontSize: '0.9rem', fontFamily: 'monospace' }}>{r.value}</span>
+                                            <span style={{ fontSize: '0.75rem', opacity: 0.7 }}>
+                                                {r.rule_type === 'REGEX_DROP'
+                                                    ? 'Drops clip'
+                                                    : `→ ${r.placeholder || '[REDACTED]'}${r.keep_original ? ' (original kept)' : ''}`}
+                                            </span>
                                             <button onClick={() => handleDeleteRule(r.id)} style={{ background: 'none', border: 'none', cursor: 'pointer', opacity: 0.8 }}>🗑️</button>
                                         </div>
                                     ))}
                                 </div>
                             </div>
 
+                            <div className="setting-item" style={{ marginTop: '24px' }}>
+                                <h3 style={{ fontSize: '0.9rem', marginBottom: '12px' }}>Custom Detectors</h3>
+                                <p style={{ fontSize: '0.8rem', opacity: 0.7, marginBottom: '12px' }}>
+                                    Flag clips matching your own patterns as sensitive, next to the built-in ones
+                                    {builtinDetectors.length > 0 && <> ({builtinDetectors.join(', ')}, and random-looking tokens)</>}.
+                                </p>
+                                <div style={{ display: 'flex', gap: '8px', marginBottom: '12px' }}>
+                                    <input
+                                        type="text"
+                                        placeholder="Name (e.g., Employee ID)"
+                                        value={newDetectorName}
+                                        onChange={(e) => setNewDetectorName(e.target.value)}
+                                        style={{ width: '160px', padding: '8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)' }}
+                                    />
+                                    <input
+                                        type="text"
+                                        placeholder="Regex Pattern (e.g., EMP-\d{6})"
+                                        value={newDetectorPattern}
+                                        onChange={(e) => {
+                                            setNewDetectorPattern(e.target.value);
+                                            setDetectorError("");
+                                        }}
+                                        style={{ flex: 1, padding: '8px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)', fontFamily: 'monospace' }}
+                                    />
+                                    <button
+                                        onClick={handleAddDetector}
+                                        className="primary-btn"
+                                        style={{
+                                            padding: '8px 16px',
+                                            borderRadius: '8px',
+                                            border: 'none',
+                                            background: 'var(--accent-color, #4f46e5)',
+                                            color: 'white',
+                                            cursor: 'pointer',
+                                            fontWeight: 600,
+                                            display: 'flex',
+                                            alignItems: 'center',
+                                            gap: '8px'
+                                        }}
+                                    >
+                                        Add
+                                    </button>
+                                </div>
+                                {detectorError && <p style={{ fontSize: '0.75rem', color: '#ef4444', marginTop: 0, whiteSpace: 'pre-wrap', fontFamily: 'monospace' }}>{detectorError}</p>}
+                                <div style={{ maxHeight: '150px', overflowY: 'auto', background: 'rgba(0,0,0,0.02)', borderRadius: '6px', padding: '8px' }}>
+                                    {detectors.length === 0 && <p style={{ opacity: 0.5, fontSize: '0.8rem', textAlign: 'center' }}>No custom detectors.</p>}
+                                    {detectors.map(d => (
+                                        <div key={d.id} style={{ display: 'flex', justifyContent: 'space-between', alignItems: 'center', gap: '8px', padding: '6px', borderBottom: '1px solid rgba(128,128,128,0.1)', opacity: d.enabled ? 1 : 0.5 }}>
+                                            <input
+                                                type="checkbox"
+                                                checked={d.enabled}
+                                                onChange={(e) => handleToggleDetector(d.id, e.target.checked)}
+                                                title={d.enabled ? "Disable" : "Enable"}
+                                                style={{ accentColor: 'var(--accent-color)' }}
+                                            />
+                                            <span style={{ fontSize: '0.9rem', fontWeight: 600 }}>{d.name}</span>
+                                            <span style={{ flex: 1, fontSize: '0.85rem', fontFamily: 'monospace', opacity: 0.8, overflow: 'hidden', textOverflow: 'ellipsis', whiteSpace: 'nowrap' }}>{d.pattern}</span>
+                                            <button onClick={() => handleDeleteDetector(d.id)} style={{ background: 'none', border: 'none', cursor: 'pointer', opacity: 0.8 }}>🗑️</button>
+                                        </div>
+                                    ))}
+                                </div>
+                            </div>
+
+                            {/* Local API */}
+                            <div className="setting-item" style={{ marginTop: '24px', padding: '16px', background: 'rgba(99, 102, 241, 0.05)', borderRadius: '8px', border: '1px solid rgba(99, 102, 241, 0.2)' }}>
+                                <h3 style={{ fontSize: '0.9rem', marginBottom: '12px', marginTop: 0, display: 'flex', alignItems: 'center', gap: '8px' }}>
+                                    🔑 Local API Token
+                                </h3>
+                                <p style={{ fontSize: '0.8rem', opacity: 0.7, marginBottom: '12px' }}>
+                                    Editor plugins and scripts must send this as <code>Authorization: Bearer &lt;token&gt;</code> to the local API.
+                                </p>
+                                <div style={{ display: 'flex', gap: '8px', alignItems: 'center', marginBottom: '12px', flexWrap: 'wrap' }}>
+                                    <label style={{ display: 'flex', alignItems: 'center', gap: '6px', fontSize: '0.85rem' }}>
+                                        <input
+                                            type="checkbox"
+                                            checked={apiEnabled}
+                                            onChange={(e) => setApiEnabled(e.target.checked)}
+                                            style={{ accentColor: 'var(--accent-color)' }}
+                                        />
+                                        Enabled
+                                    </label>
+                                    <input
+                                        type="text"
+                                        value={apiBind}
+                                        onChange={(e) => setApiBind(e.target.value)}
+                                        style={{ width: '120px', padding: '6px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)' }}
+                                    />
+                                    <input
+                                        type="number"
+                                        value={apiPort}
+                                        onChange={(e) => setApiPort(parseInt(e.target.value) || 14201)}
+                                        style={{ width: '80px', padding: '6px', borderRadius: '6px', border: '1px solid rgba(128,128,128,0.3)', background: 'var(--bg-card)', color: 'var(--text-primary, inherit)' }}
+                                    />
+                                    <button
+                                        onClick={handleApiSettingsSave}
+                                        style={{ padding: '6px 12px', borderRadius: '6px', border: '1px solid var(--border-color, rgba(128,128,128,0.3))', background: 'transparent', color: 'inherit', cursor: 'pointer', fontSize: '0.8rem' }}
+                                    >
+                                        Apply & Restart
+                                    </button>
+                                </div>
+                                {apiStatus && (
+                                    <div style={{ fontSize: '0.75rem', opacity: 0.7, marginBottom: '12px' }}>
+                                        {apiStatus.running
+                                            ? `Listening on ${apiStatus.address} · ${apiStatus.request_count} requests`
+                                            : apiStatus.enabled ? 'Not running' : 'Disabled'}
+                                        {apiStatus.last_error && <div style={{ color: '#ef4444' }}>{apiStatus.last_error}</div>}
+                                    </div>
+                                )}
+                                <div style={{ display: 'flex', gap: '8px', alignItems: 'center' }}>
+                                    <code style={{ flex: 1, padding: '8px', borderRadius: '6px', background: 'var(--bg-card)', fontSize: '0.8rem', overflow: 'hidden', textOverflow: 'ellipsis', whiteSpace: 'nowrap' }}>
+                                        {showApiToken ? apiToken : '•'.repeat(24)}
+                                    </code>
+                                    <button
+                                        onClick={() => setShowApiToken(!showApiToken)}
+                                        style={{ padding: '6px 12px', borderRadius: '6px', border: '1px solid var(--border-color, rgba(128,128,128,0.3))', background: 'transparent', color: 'inherit', cursor: 'pointer', fontSize: '0.8rem' }}
+                                    >
+                                        {showApiToken ? 'Hide' : 'Show'}
+                                    </button>
+                                    <button
+                                        onClick={() => navigator.clipboard.writeText(apiToken)}
+                                        style={{ padding: '6px 12px', borderRadius: '6px', border: '1px solid var(--border-color, rgba(128,128,128,0.3))', background: 'transparent', color: 'inherit', cursor: 'pointer', fontSize: '0.8rem' }}
+                                    >
+                                        Copy
+                                    </button>
+                                    <button
+                                        onClick={async () => {
+                                            if (window.confirm('Generate a new token? Clients using the current one will stop working.')) {
+                                                setApiToken(await invoke<string>("regenerate_api_token"));
+                                            }
+                                        }}
+                                        style={{ padding: '6px 12px', borderRadius: '6px', border: '1px solid #ef4444', background: 'transparent', color: '#ef4444', cursor: 'pointer', fontSize: '0.8rem' }}
+                                    >
+                                        Regenerate
+                                    </button>
+                                </div>
+                            </div>
+
                             {/* PIN Lock */}
                             <div className="setting-item" style={{ marginTop: '24px', padding: '16px', background: 'rgba(99, 102, 241, 0.05)', borderRadius: '8px', border: '1px solid rgba(99, 102, 241, 0.2)' }}>
                                 <h3 style={{ fontSize: '0.9rem', marginBottom: '12px', marginTop: 0, display: 'flex', alignItems: 'center', gap: '8px' }}>
@@ -1304,6 +1896,123 @@ export default function SettingsPage({
                                 </div>
                             </div>
 
+                            {/* Encrypted Database */}
+                            {dbVault && (
+                                <div className="setting-item" style={{ marginTop: '16px', padding: '16px', background: 'rgba(99, 102, 241, 0.05)', borderRadius: '8px', border: '1px solid rgba(99, 102, 241, 0.2)' }}>
+                                    <h3 style={{ fontSize: '0.9rem', marginBottom: '12px', marginTop: 0, display: 'flex', alignItems: 'center', gap: '8px' }}>
+                                        🔐 Encrypted Database
+                                        <span style={{ fontSize: '0.75rem', fontWeight: 400, opacity: 0.7 }}>{dbVault.enabled ? 'On' : 'Off'}</span>
+                                    </h3>
+                                    <p style={{ fontSize: '0.8rem', opacity: 0.7, marginBottom: '12px' }}>
+                                        {dbVault.enabled
+                                            ? 'Clips and snippets are encrypted on disk. ReClip starts locked and stops capturing until unlocked.'
+                                            : 'Encrypt clips and snippets on disk with a password. The password is never stored and can\'t be recovered. Search gets slower on large histories.'}
+                                    </p>
+                                    <p style={{ fontSize: '0.75rem', opacity: 0.6, marginBottom: '12px' }}>
+                                        Images and their thumbnails, notes, snippet titles, dates and source apps stay unencrypted.
+                                    </p>
+                                    <div style={{ display: 'flex', flexDirection: 'column', gap: '8px' }}>
+                                        <input
+                                            type="password"
+                                            value={dbVaultPassword}
+                                            onChange={e => setDbVaultPassword(e.target.value)}
+                                            placeholder={dbVault.enabled ? 'Vault password' : 'New vault password'}
+                                            style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                        />
+                                        {!dbVault.enabled && (
+                                            <input
+                                                type="password"
+                                                value={dbVaultPasswordConfirm}
+                                                onChange={e => setDbVaultPasswordConfirm(e.target.value)}
+                                                placeholder="Repeat password"
+                                                style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                            />
+                                        )}
+                                        <div style={{ display: 'flex', gap: '8px' }}>
+                                            <button
+                                                onClick={handleDbVaultToggle}
+                                                disabled={!dbVaultPassword || dbVaultBusy}
+                                                className="primary-btn"
+                                                style={dbVault.enabled ? { flex: 1, padding: '8px 16px', borderRadius: '8px', border: '1px solid #ef4444', background: 'transparent', color: '#ef4444', cursor: 'pointer', fontWeight: 600 } : { flex: 1 }}
+                                            >
+                                                {dbVault.enabled ? 'Decrypt and Turn Off' : 'Encrypt Database'}
+                                            </button>
+                                            {dbVault.enabled && (
+                                                <button
+                                                    onClick={() => invoke('lock_vault')}
+                                                    style={{ flex: 1, padding: '8px 16px', borderRadius: '8px', border: '1px solid var(--border-color)', background: 'transparent', color: 'inherit', cursor: 'pointer' }}
+                                                >
+                                                    Lock Now
+                                                </button>
+                                            )}
+                                        </div>
+                                    </div>
+                                    {dbVault.enabled && (
+                                        <div style={{ display: 'flex', justifyContent: 'space-between', alignItems: 'center', marginTop: '12px' }}>
+                                            <span style={{ fontSize: '0.85rem' }}>Lock after inactivity</span>
+                                            <select
+                                                value={dbVault.auto_lock_minutes}
+                                                onChange={e => handleDbVaultAutoLock(parseInt(e.target.value))}
+                                                style={{ padding: '6px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                            >
+                                                <option value={0}>Never</option>
+                                                <option value={5}>5 minutes</option>
+                                                <option value={15}>15 minutes</option>
+                                                <option value={30}>30 minutes</option>
+                                                <option value={60}>1 hour</option>
+                                                <option value={240}>4 hours</option>
+                                            </select>
+                                        </div>
+                                    )}
+                                    {dbVaultMsg && <div style={{ marginTop: '10px', fontSize: '0.85rem', textAlign: 'center', color: 'var(--accent-color)' }}>{dbVaultMsg}</div>}
+                                </div>
+                            )}
+
+                            {/* Credential Storage */}
+                            {secretStore && (
+                                <div className="setting-item" style={{ marginTop: '16px', padding: '16px', background: 'rgba(99, 102, 241, 0.05)', borderRadius: '8px', border: '1px solid rgba(99, 102, 241, 0.2)' }}>
+                                    <h3 style={{ fontSize: '0.9rem', marginBottom: '12px', marginTop: 0, display: 'flex', alignItems: 'center', gap: '8px' }}>
+                                        🗝️ Credentials
+                                        <span style={{ fontSize: '0.75rem', fontWeight: 400, opacity: 0.7 }}>
+                                            {secretStore.kind === 'secret-service' ? 'System keyring' : secretStore.kind === 'file' ? 'Encrypted file' : 'This session only'}
+                                        </span>
+                                    </h3>
+                                    <p style={{ fontSize: '0.8rem', opacity: 0.7, marginBottom: secretStore.persistent ? 0 : '12px' }}>
+                                        {secretStore.kind === 'secret-service'
+                                            ? 'Google Drive tokens and sync passwords are kept in the system keyring.'
+                                            : secretStore.persistent
+                                                ? 'Google Drive tokens and sync passwords are kept in a file encrypted with your master password.'
+                                                : secretStore.file_exists
+                                                    ? 'Enter the master password to use saved Google Drive tokens and sync passwords.'
+                                                    : 'No system keyring was found. Choose a master password to keep Google Drive tokens and sync passwords in an encrypted file, otherwise they are forgotten when ReClip closes.'}
+                                    </p>
+                                    {!secretStore.persistent && (
+                                        <div style={{ display: 'flex', flexDirection: 'column', gap: '8px' }}>
+                                            <input
+                                                type="password"
+                                                value={masterPassword}
+                                                onChange={e => setMasterPassword(e.target.value)}
+                                                placeholder="Master password"
+                                                style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                            />
+                                            {!secretStore.file_exists && (
+                                                <input
+                                                    type="password"
+                                                    value={masterPasswordConfirm}
+                                                    onChange={e => setMasterPasswordConfirm(e.target.value)}
+                                                    placeholder="Repeat password"
+                                                    style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                />
+                                            )}
+                                            <button onClick={handleUnlockSecretStore} className="primary-btn" disabled={!masterPassword}>
+                                                {secretStore.file_exists ? 'Unlock' : 'Set Master Password'}
+                                            </button>
+                                        </div>
+                                    )}
+                                    {secretStoreMsg && <div style={{ marginTop: '10px', fontSize: '0.85rem', textAlign: 'center', color: 'var(--accent-color)' }}>{secretStoreMsg}</div>}
+                                </div>
+                            )}
+
                             {/* Reset Onboarding */}
                             <div className="setting-item" style={{ marginTop: '16px' }}>
                                 <button
@@ -1412,6 +2121,7 @@ export default function SettingsPage({
                             <p style={{ fontSize: '0.75rem', opacity: 0.6, marginBottom: '16px' }}>
                                 💡 Disable both options above to keep all clips indefinitely (unlimited storage).
                             </p>
+                            {maintenanceStatus && <div style={{ marginBottom: '16px', fontSize: '0.85rem', opacity: 0.8 }}>{maintenanceStatus}</div>}
 
                             <div className="setting-item" style={{ marginTop: '24px', borderTop: '1px solid rgba(255,0,0,0.2)', paddingTop: '24px' }}>
                                 <h3 style={{ color: '#ef4444', marginTop: 0, fontSize: '0.9rem' }}>Danger Zone</h3>
@@ -1797,6 +2507,263 @@ export default function SettingsPage({
                                 {exportStatus && <div style={{ marginTop: '8px', fontSize: '0.85rem', opacity: 0.8 }}>{exportStatus}</div>}
                             </div>
 
+                            <div style={{ borderTop: '1px solid var(--border-color)', paddingTop: '24px', marginBottom: '32px' }}>
+                                <h3 style={{ marginBottom: '16px' }}>Cloud Sync</h3>
+                                {syncConfig && (
+                                    <div style={{ background: 'var(--bg-card)', padding: '20px', borderRadius: '12px', border: '1px solid var(--border-color)' }}>
+                                        <p style={{ margin: '0 0 16px 0', fontSize: '0.9rem', opacity: 0.8 }}>
+                                            Clips, snippets and notes are merged across devices, the latest change wins. Sensitive clips stay on this device.
+                                        </p>
+                                        <div style={{ marginBottom: '12px' }}>
+                                            <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Storage</label>
+                                            <select
+                                                value={syncConfig.backend}
+                                                onChange={e => updateSyncConfig({ backend: e.target.value as SyncConfig['backend'] })}
+                                                style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                            >
+                                                <option value="">Off</option>
+                                                <option value="drive">Google Drive</option>
+                                                <option value="folder">Folder (Syncthing, Nextcloud, network share)</option>
+                                                <option value="webdav">WebDAV</option>
+                                                <option value="s3">S3 compatible (AWS, MinIO, R2, B2)</option>
+                                            </select>
+                                        </div>
+
+                                        {syncConfig.backend === 'drive' && (
+                                            <p style={{ fontSize: '0.8rem', opacity: 0.7, margin: '0 0 12px 0' }}>
+                                                {driveConnected ? `Using the Google Drive of ${driveEmail || driveUser || 'the connected account'}.` : 'Connect Google Drive below first.'}
+                                            </p>
+                                        )}
+
+                                        {syncConfig.backend === 'folder' && (
+                                            <div style={{ marginBottom: '12px' }}>
+                                                <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Folder</label>
+                                                <div style={{ display: 'flex', gap: '8px' }}>
+                                                    <input
+                                                        type="text"
+                                                        value={syncConfig.folder_path}
+                                                        onChange={e => updateSyncConfig({ folder_path: e.target.value })}
+                                                        placeholder="/home/me/Sync/ReClip"
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                    <button onClick={handlePickSyncFolder} style={{ padding: '8px 12px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'transparent', color: 'inherit', cursor: 'pointer' }}>
+                                                        Browse
+                                                    </button>
+                                                </div>
+                                            </div>
+                                        )}
+
+                                        {syncConfig.backend === 'webdav' && (
+                                            <>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>URL</label>
+                                                    <input
+                                                        type="text"
+                                                        value={syncConfig.webdav_url}
+                                                        onChange={e => updateSyncConfig({ webdav_url: e.target.value })}
+                                                        placeholder="https://cloud.example.com/remote.php/dav/files/me/ReClip"
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>User</label>
+                                                    <input
+                                                        type="text"
+                                                        value={syncConfig.webdav_user}
+                                                        onChange={e => updateSyncConfig({ webdav_user: e.target.value })}
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Password</label>
+                                                    <input
+                                                        type="password"
+                                                        value={syncConfig.webdav_password}
+                                                        onChange={e => updateSyncConfig({ webdav_password: e.target.value })}
+                                                        placeholder={syncConfig.webdav_password_set ? 'Saved, leave empty to keep' : ''}
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                            </>
+                                        )}
+
+                                        {syncConfig.backend === 's3' && (
+                                            <>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Endpoint (empty for AWS)</label>
+                                                    <input
+                                                        type="text"
+                                                        value={syncConfig.s3_endpoint}
+                                                        onChange={e => updateSyncConfig({ s3_endpoint: e.target.value })}
+                                                        placeholder="http://localhost:9000"
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Region</label>
+                                                    <input
+                                                        type="text"
+                                                        value={syncConfig.s3_region}
+                                                        onChange={e => updateSyncConfig({ s3_region: e.target.value })}
+                                                        placeholder="us-east-1"
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Bucket</label>
+                                                    <input
+                                                        type="text"
+                                                        value={syncConfig.s3_bucket}
+                                                        onChange={e => updateSyncConfig({ s3_bucket: e.target.value })}
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Prefix</label>
+                                                    <input
+                                                        type="text"
+                                                        value={syncConfig.s3_prefix}
+                                                        onChange={e => updateSyncConfig({ s3_prefix: e.target.value })}
+                                                        placeholder="reclip/"
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Access key</label>
+                                                    <input
+                                                        type="text"
+                                                        value={syncConfig.s3_access_key}
+                                                        onChange={e => updateSyncConfig({ s3_access_key: e.target.value })}
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                                <div style={{ marginBottom: '12px' }}>
+                                                    <label style={{ display: 'block', fontSize: '0.8rem', fontWeight: 600, marginBottom: '6px' }}>Secret key</label>
+                                                    <input
+                                                        type="password"
+                                                        value={syncConfig.s3_secret_key}
+                                                        onChange={e => updateSyncConfig({ s3_secret_key: e.target.value })}
+                                                        placeholder={syncConfig.s3_secret_key_set ? 'Saved, leave empty to keep' : ''}
+                                                        style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                    />
+                                                </div>
+                                                <label style={{ display: 'flex', alignItems: 'center', gap: '6px', fontSize: '0.85rem', marginBottom: '12px' }}>
+                                                    <input
+                                                        type="checkbox"
+                                                        checked={syncConfig.s3_path_style}
+                                                        onChange={e => updateSyncConfig({ s3_path_style: e.target.checked })}
+                                                        style={{ accentColor: 'var(--accent-color)' }}
+                                                    />
+                                                    Path-style URLs (MinIO and most self-hosted stores)
+                                                </label>
+                                            </>
+                                        )}
+
+                                        <div style={{ display: 'flex', gap: '12px' }}>
+                                            <button onClick={handleSyncConfigSave} className="primary-btn" style={{ flex: 1 }}>
+                                                Save
+                                            </button>
+                                            <button
+                                                onClick={handleSyncTest}
+                                                disabled={!syncConfig.backend}
+                                                style={{ flex: 1, padding: '10px', borderRadius: '8px', border: '1px solid var(--border-color)', background: 'transparent', color: 'inherit', cursor: 'pointer' }}
+                                            >
+                                                Test Connection
+                                            </button>
+                                            <button
+                                                onClick={handleSyncNow}
+                                                className="primary-btn"
+                                                style={{ flex: 1 }}
+                                                disabled={syncing || !syncConfig.backend}
+                                            >
+                                                {syncing ? 'Syncing...' : 'Sync Now'}
+                                            </button>
+                                        </div>
+                                        {syncConfig.last_sync && <div style={{ marginTop: '8px', fontSize: '0.75rem', opacity: 0.6 }}>Last sync: {syncConfig.last_sync}</div>}
+                                        {syncStatusMsg && <div style={{ marginTop: '12px', fontSize: '0.85rem', textAlign: 'center', color: 'var(--accent-color)' }}>{syncStatusMsg}</div>}
+
+                                        {vaultStatus && (
+                                            <div style={{ marginTop: '20px', paddingTop: '16px', borderTop: '1px solid var(--border-color)' }}>
+                                                <div style={{ display: 'flex', justifyContent: 'space-between', alignItems: 'center', marginBottom: '10px' }}>
+                                                    <span style={{ fontWeight: 600, fontSize: '0.9rem' }}>Encryption</span>
+                                                    <span style={{ fontSize: '0.8rem', opacity: 0.7 }}>
+                                                        {!vaultStatus.encrypted ? 'Off' : vaultStatus.unlocked ? `Unlocked, ${vaultStatus.keys} key${vaultStatus.keys === 1 ? '' : 's'}` : 'Locked'}
+                                                    </span>
+                                                </div>
+
+                                                {!vaultStatus.encrypted && (
+                                                    <p style={{ fontSize: '0.8rem', opacity: 0.7, margin: '0 0 10px 0' }}>
+                                                        Encrypt synced data with a password. Every device needs it once per session, it is never stored. There is no way to recover it.
+                                                    </p>
+                                                )}
+
+                                                {!vaultStatus.unlocked && (
+                                                    <div style={{ display: 'flex', flexDirection: 'column', gap: '8px' }}>
+                                                        <input
+                                                            type="password"
+                                                            value={vaultPassword}
+                                                            onChange={e => setVaultPassword(e.target.value)}
+                                                            placeholder="Sync password"
+                                                            style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                        />
+                                                        {!vaultStatus.encrypted && (
+                                                            <input
+                                                                type="password"
+                                                                value={vaultPasswordConfirm}
+                                                                onChange={e => setVaultPasswordConfirm(e.target.value)}
+                                                                placeholder="Repeat password"
+                                                                style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                            />
+                                                        )}
+                                                        <button onClick={handleVaultUnlock} className="primary-btn" disabled={!vaultPassword}>
+                                                            {vaultStatus.encrypted ? 'Unlock' : 'Encrypt Sync Data'}
+                                                        </button>
+                                                    </div>
+                                                )}
+
+                                                {vaultStatus.unlocked && (
+                                                    <div style={{ display: 'flex', flexDirection: 'column', gap: '8px' }}>
+                                                        <input
+                                                            type="password"
+                                                            value={vaultPassword}
+                                                            onChange={e => setVaultPassword(e.target.value)}
+                                                            placeholder="Current password"
+                                                            style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                        />
+                                                        <input
+                                                            type="password"
+                                                            value={vaultNewPassword}
+                                                            onChange={e => setVaultNewPassword(e.target.value)}
+                                                            placeholder="New password"
+                                                            style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                        />
+                                                        <input
+                                                            type="password"
+                                                            value={vaultPasswordConfirm}
+                                                            onChange={e => setVaultPasswordConfirm(e.target.value)}
+                                                            placeholder="Repeat new password"
+                                                            style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
+                                                        />
+                                                        <div style={{ display: 'flex', gap: '12px' }}>
+                                                            <button onClick={handleVaultChangePassword} disabled={!vaultPassword || !vaultNewPassword} style={{ flex: 1, padding: '10px', borderRadius: '8px', border: '1px solid var(--border-color)', background: 'transparent', color: 'inherit', cursor: 'pointer' }}>
+                                                                Change Password
+                                                            </button>
+                                                            <button onClick={handleVaultRotate} style={{ flex: 1, padding: '10px', borderRadius: '8px', border: '1px solid var(--border-color)', background: 'transparent', color: 'inherit', cursor: 'pointer' }}>
+                                                                Rotate Key
+                                                            </button>
+                                                            <button onClick={handleVaultLock} style={{ flex: 1, padding: '10px', borderRadius: '8px', border: '1px solid var(--border-color)', background: 'transparent', color: 'inherit', cursor: 'pointer' }}>
+                                                                Lock
+                                                            </button>
+                                                        </div>
+                                                    </div>
+                                                )}
+                                                {vaultMsg && <div style={{ marginTop: '10px', fontSize: '0.85rem', textAlign: 'center', color: 'var(--accent-color)' }}>{vaultMsg}</div>}
+                                            </div>
+                                        )}
+                                    </div>
+                                )}
+                            </div>
+
                             <div style={{ borderTop: '1px solid var(--border-color)', paddingTop: '24px' }}>
                                 <h3 style={{ marginBottom: '16px' }}>Google Drive Sync</h3>
 
@@ -1824,7 +2791,7 @@ export default function SettingsPage({
                                                 type="password"
                                                 value={driveClientSecret}
                                                 onChange={e => setDriveClientSecret(e.target.value)}
-                                                placeholder="Client Secret"
+                                                placeholder="Client Secret (empty keeps the saved one)"
                                                 className="input-field"
                                                 style={{ width: '100%', padding: '8px', borderRadius: '6px', border: '1px solid var(--border-color)', background: 'var(--bg-input)', color: 'inherit' }}
                                             />
@@ -1852,14 +2819,6 @@ export default function SettingsPage({
                                         </div>
 
                                         <div style={{ display: 'flex', gap: '12px' }}>
-                                            <button
-                                                onClick={handleSyncNow}
-                                                className="primary-btn"
-                                                style={{ flex: 1 }}
-                                                disabled={syncing}
-                                            >
-                                                {syncing ? 'Syncing...' : 'Sync Now'}
-                                            </button>
                                             <button
                                                 onClick={handleDisconnectDrive}
                                                 style={{ padding: '10px', borderRadius: '8px', background: 'rgba(239, 68, 68, 0.1)', color: '#ef4444', border: 'none', cursor: 'pointer', fontWeight: 600 }}
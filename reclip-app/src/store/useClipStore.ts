@@ -51,6 +51,7 @@ interface ClipState {
     loadClips: (page: number, limit: number, filter: string, search: string, reset?: boolean) => Promise<void>;
     clearClips: () => Promise<void>;
     deleteClips: (ids: number[]) => Promise<void>;
+    dropClips: (ids: number[]) => void;
 }
 
 export const useClipStore = create<ClipState>((set, get) => ({
@@ -166,13 +167,22 @@ export const useClipStore = create<ClipState>((set, get) => ({
     deleteClips: async (ids) => {
         try {
             await invoke('delete_clips', { ids });
-            set((state) => ({
-                clips: state.clips.filter(c => !ids.includes(c.id)),
-                allClips: state.allClips.filter(c => !ids.includes(c.id)),
-                totalClipCount: state.totalClipCount - ids.length
-            }));
+            get().dropClips(ids);
         } catch (e) {
             console.error('Failed to delete clips:', e);
         }
+    },
+
+    // Also called for clip-deleted events, which follow our own deletes: only loaded clips are counted
+    dropClips: (ids) => {
+        set((state) => {
+            const dropped = state.allClips.filter(c => ids.includes(c.id)).length;
+            return {
+                clips: state.clips.filter(c => !ids.includes(c.id)),
+                allClips: state.allClips.filter(c => !ids.includes(c.id)),
+                pasteQueue: state.pasteQueue.filter(c => !ids.includes(c.id)),
+                totalClipCount: Math.max(0, state.totalClipCount - dropped)
+            };
+        });
     }
 }));
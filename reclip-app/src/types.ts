@@ -9,7 +9,123 @@ export interface Clip {
     tags?: string; // JSON string
     sender_app?: string;
     sensitive: boolean;
+    sensitive_detector?: string; // Detector that flagged the clip
+    sensitive_confidence?: number; // 0 to 1
+    has_original?: boolean; // A privacy filter masked the clip and kept the original encrypted
+    ocr_text?: string; // Text recognized in an image clip, searchable
     position?: number | null;
+    origin: 'clipboard' | 'primary';
+    thumbnail?: string; // Downscaled preview of large image clips
+    highlight?: string; // Search match excerpt with <mark> tags
+}
+
+export type AppCapture = 'all' | 'no_images' | 'text_only' | 'none';
+export type AppMatchKind = 'substring' | 'path' | 'title_regex' | 'wildcard';
+
+export interface PrivacyRule {
+    id: number;
+    rule_type: 'APP_IGNORE' | 'APP_POLICY' | 'REGEX_MASK' | 'REGEX_DROP';
+    value: string;
+    is_active: boolean;
+    placeholder?: string | null; // REGEX_MASK replacement, [REDACTED] when unset
+    keep_original: boolean;
+    match_kind: AppMatchKind;
+    capture: AppCapture;
+    mark_sensitive: boolean;
+    delete_after?: number | null; // Seconds, the global timer when unset
+    tags?: string | null; // JSON array
+    strip_formatting: boolean;
+}
+
+export interface AppPolicyRule {
+    match_kind: AppMatchKind;
+    value: string;
+    capture: AppCapture;
+    mark_sensitive: boolean;
+    delete_after: number | null;
+    tags: string[];
+    strip_formatting: boolean;
+}
+
+export interface AppPolicyTest {
+    window: { name: string; path: string; title: string } | null;
+    policy: {
+        capture: AppCapture;
+        mark_sensitive: boolean;
+        delete_after: number | null;
+        tags: string[];
+        strip_formatting: boolean;
+        matched: string[];
+    };
+}
+
+export interface Detector {
+    id: number;
+    name: string;
+    pattern: string;
+    confidence: number;
+    enabled: boolean;
+}
+
+export interface ClipFormat {
+    id: number;
+    clip_id: number;
+    mime: string;
+    path?: string | null;
+    size: number;
+}
+
+export interface GcReport {
+    removed_blobs: number;
+    removed_files: number;
+    reclaimed_bytes: number;
+}
+
+export interface SyncConfig {
+    backend: '' | 'drive' | 'folder' | 'webdav' | 's3';
+    folder_path: string;
+    webdav_url: string;
+    webdav_user: string;
+    webdav_password: string;
+    s3_endpoint: string;
+    s3_region: string;
+    s3_bucket: string;
+    s3_prefix: string;
+    s3_access_key: string;
+    s3_secret_key: string;
+    s3_path_style: boolean;
+    last_sync?: string | null;
+    webdav_password_set?: boolean; // Stored, the secrets themselves are never sent; empty on save keeps them
+    s3_secret_key_set?: boolean;
+}
+
+export interface VaultStatus {
+    encrypted: boolean;
+    unlocked: boolean;
+    keys: number;
+}
+
+/** Where credentials are kept: the system keyring, a master-password file, or memory until that file is unlocked */
+export interface SecretStoreStatus {
+    kind: 'secret-service' | 'file' | 'memory';
+    persistent: boolean;
+    file_exists: boolean;
+}
+
+/** Vault mode of the local database */
+export interface DbVaultStatus {
+    enabled: boolean;
+    unlocked: boolean;
+    auto_lock_minutes: number;
+}
+
+export interface ApiStatus {
+    enabled: boolean;
+    running: boolean;
+    address?: string | null;
+    uptime_secs?: number | null;
+    request_count: number;
+    last_error?: string | null;
 }
 
 export interface Snippet {